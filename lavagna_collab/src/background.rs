@@ -0,0 +1,68 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// The paper under the drawing, the same for everyone in a room
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Background {
+    #[default]
+    Plain,
+    /// Squared paper
+    Grid,
+    /// A dot at each corner of the squares
+    Dots,
+    /// Horizontal lines, like a notebook
+    Ruled,
+    /// Triangles, for drawing in isometric projection
+    Isometric,
+}
+
+impl Background {
+    pub const ALL: [Self; 5] = [
+        Self::Plain,
+        Self::Grid,
+        Self::Dots,
+        Self::Ruled,
+        Self::Isometric,
+    ];
+
+    #[must_use]
+    pub fn is_plain(&self) -> bool {
+        *self == Self::Plain
+    }
+
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Plain => "plain",
+            Self::Grid => "grid",
+            Self::Dots => "dots",
+            Self::Ruled => "ruled",
+            Self::Isometric => "isometric",
+        }
+    }
+}
+
+impl fmt::Display for Background {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Background {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|x| x.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                let names: Vec<_> = Self::ALL.iter().map(|x| x.name()).collect();
+                format!(
+                    "unknown background {s}, expected one of {}",
+                    names.join(", ")
+                )
+            })
+    }
+}
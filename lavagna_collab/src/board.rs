@@ -0,0 +1,902 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    simplify, simplify_tolerance, AddressedEvent, Background, CollabId, Event, ItemId, Placement,
+    StrokeStyle,
+};
+
+/// A complete line, as drawn by a peer
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct Line {
+    pub color: u32,
+    pub line_width: u8,
+    pub points: Vec<(i16, i16)>,
+    /// Pen pressure of each point, empty when always at full pressure
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pressures: Vec<u8>,
+    #[serde(default, skip_serializing_if = "StrokeStyle::is_plain")]
+    pub style: StrokeStyle,
+    /// Color of the inside, the line is closed to be filled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fill: Option<u32>,
+    #[serde(default)]
+    pub id: ItemId,
+    #[serde(default, skip_serializing_if = "Placement::is_identity")]
+    pub placement: Placement,
+    /// The layer the item is on, see `Board::layers`
+    #[serde(default, skip_serializing_if = "ItemId::is_unset")]
+    pub layer: ItemId,
+    /// The page the item is on, see `Board::pages`
+    #[serde(default, skip_serializing_if = "ItemId::is_unset")]
+    pub page: ItemId,
+}
+
+impl Line {
+    /// `scale` is the one of the view of the peer drawing the line
+    fn simplify(&mut self, scale: f32) {
+        let tolerance = simplify_tolerance(f32::from(self.line_width), scale);
+
+        if self.pressures.iter().all(|&p| p == u8::MAX) {
+            self.pressures.clear();
+        }
+
+        if self.pressures.len() == self.points.len() {
+            let points: Vec<_> = self
+                .points
+                .iter()
+                .copied()
+                .zip(self.pressures.iter().copied())
+                .collect();
+            (self.points, self.pressures) =
+                simplify(&points, tolerance, |&((x, y), _)| (x.into(), y.into()))
+                    .into_iter()
+                    .unzip();
+        } else {
+            self.points = simplify(&self.points, tolerance, |&(x, y)| (x.into(), y.into()));
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum ShapeKind {
+    Line,
+    Rectangle,
+    Ellipse,
+    Arrow,
+    /// Closed polygon through `Shape::vertices`
+    Polygon,
+}
+
+impl ShapeKind {
+    /// Closed shapes can be filled
+    #[must_use]
+    pub fn is_closed(self) -> bool {
+        matches!(self, Self::Rectangle | Self::Ellipse | Self::Polygon)
+    }
+
+    /// How a shape of this kind is drawn with `style`: closed shapes have no
+    /// arrowheads, arrows always have one at the end
+    #[must_use]
+    pub fn stroke_style(self, style: StrokeStyle) -> StrokeStyle {
+        match self {
+            Self::Line => style,
+            Self::Arrow => StrokeStyle {
+                end_arrow: true,
+                ..style
+            },
+            Self::Rectangle | Self::Ellipse | Self::Polygon => StrokeStyle {
+                dash: style.dash,
+                ..StrokeStyle::default()
+            },
+        }
+    }
+}
+
+/// A shape, defined by the start and the end of the drag which drew it
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Shape {
+    pub kind: ShapeKind,
+    pub color: u32,
+    pub line_width: u8,
+    pub from: (i16, i16),
+    pub to: (i16, i16),
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub vertices: Vec<(i16, i16)>,
+    /// Arrowheads are drawn only on open shapes, `ShapeKind::Arrow` always
+    /// has one at the end
+    #[serde(default, skip_serializing_if = "StrokeStyle::is_plain")]
+    pub style: StrokeStyle,
+    /// Color of the inside, only closed shapes are filled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fill: Option<u32>,
+    #[serde(default)]
+    pub id: ItemId,
+    #[serde(default, skip_serializing_if = "Placement::is_identity")]
+    pub placement: Placement,
+    /// The layer the item is on, see `Board::layers`
+    #[serde(default, skip_serializing_if = "ItemId::is_unset")]
+    pub layer: ItemId,
+    /// The page the item is on, see `Board::pages`
+    #[serde(default, skip_serializing_if = "ItemId::is_unset")]
+    pub page: ItemId,
+}
+
+/// Some typed text
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Label {
+    pub content: String,
+    pub color: u32,
+    /// Font size
+    pub size: u8,
+    /// Where the text starts, vertically centered
+    pub position: (i16, i16),
+    #[serde(default)]
+    pub id: ItemId,
+    #[serde(default, skip_serializing_if = "Placement::is_identity")]
+    pub placement: Placement,
+    /// The layer the item is on, see `Board::layers`
+    #[serde(default, skip_serializing_if = "ItemId::is_unset")]
+    pub layer: ItemId,
+    /// The page the item is on, see `Board::pages`
+    #[serde(default, skip_serializing_if = "ItemId::is_unset")]
+    pub page: ItemId,
+}
+
+/// Average width of a character, relative to the font size
+const LABEL_CHAR_WIDTH: f32 = 0.6;
+
+fn point((x, y): (i16, i16)) -> (f32, f32) {
+    (x.into(), y.into())
+}
+
+/// What lines, shapes and labels have in common
+trait Item: Clone {
+    fn id(&self) -> ItemId;
+    fn id_mut(&mut self) -> &mut ItemId;
+    fn placement_mut(&mut self) -> &mut Placement;
+}
+
+macro_rules! impl_item {
+    ($($t:ty),*) => {
+        $(impl Item for $t {
+            fn id(&self) -> ItemId {
+                self.id
+            }
+            fn id_mut(&mut self) -> &mut ItemId {
+                &mut self.id
+            }
+            fn placement_mut(&mut self) -> &mut Placement {
+                &mut self.placement
+            }
+        })*
+    };
+}
+
+impl_item!(Line, Shape, Label);
+
+fn transform_items<T: Item>(items: &mut [T], ids: &[ItemId], placement: &Placement) {
+    for item in items.iter_mut().filter(|item| ids.contains(&item.id())) {
+        let moved = item.placement_mut().then(placement);
+        *item.placement_mut() = moved;
+    }
+}
+
+fn duplicate_items<T: Item>(
+    items: &mut Vec<T>,
+    ids: &[ItemId],
+    src: CollabId,
+    first: u32,
+    placement: &Placement,
+) {
+    let copies: Vec<_> = (first..)
+        .zip(ids)
+        .filter_map(|(n, id)| {
+            let mut copy = items.iter().find(|item| item.id() == *id)?.clone();
+            *copy.id_mut() = ItemId { src, n };
+            let moved = copy.placement_mut().then(placement);
+            *copy.placement_mut() = moved;
+            Some(copy)
+        })
+        .collect();
+    items.extend(copies);
+}
+
+/// Items are stacked in layers, which can be hidden or locked. The first
+/// layer, the one at the bottom, has an unset id: items saved before layers
+/// were introduced are on it.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Layer {
+    pub id: ItemId,
+    pub name: String,
+    #[serde(default = "visible")]
+    pub visible: bool,
+    /// Items on locked layers cannot be changed
+    #[serde(default)]
+    pub locked: bool,
+}
+
+fn visible() -> bool {
+    true
+}
+
+impl Layer {
+    #[must_use]
+    pub fn new(id: ItemId, name: String) -> Self {
+        Self {
+            id,
+            name,
+            visible: true,
+            locked: false,
+        }
+    }
+}
+
+/// The board is a stack of pages, shown one at a time. The first page has an
+/// unset id: items saved before pages were introduced are on it.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Page {
+    pub id: ItemId,
+}
+
+impl Page {
+    #[must_use]
+    pub fn new(id: ItemId) -> Self {
+        Self { id }
+    }
+}
+
+/// Position of `page`. Items on pages not known, e.g. copied from another
+/// board, are on the first one.
+fn page_index(pages: &[Page], page: ItemId) -> usize {
+    pages.iter().position(|x| x.id == page).unwrap_or(0)
+}
+
+/// Items on hidden or locked layers cannot be changed. Items on layers not
+/// known can, as in the app.
+fn is_editable(layers: &[Layer], layer: ItemId) -> bool {
+    layers
+        .iter()
+        .find(|x| x.id == layer)
+        .map_or(true, |x| x.visible && !x.locked)
+}
+
+/// Everything drawn on the blackboard, in drawing order
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct Board {
+    pub lines: Vec<Line>,
+    #[serde(default)]
+    pub shapes: Vec<Shape>,
+    #[serde(default)]
+    pub labels: Vec<Label>,
+    /// Ids of the items from the bottom of the stack to the top, in the order
+    /// they have been added. Items missing from it, because they were saved
+    /// before it existed, are at the bottom.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub order: Vec<ItemId>,
+    /// Bottom first, empty when only the first layer has been used
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub layers: Vec<Layer>,
+    #[serde(default, skip_serializing_if = "Background::is_plain")]
+    pub background: Background,
+    /// In order, empty when only the first page has been used
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pages: Vec<Page>,
+    /// The page shown, the same for everyone in the room
+    #[serde(default, skip_serializing_if = "ItemId::is_unset")]
+    pub page: ItemId,
+}
+
+impl Board {
+    /// Events which draw the whole board, for peers joining the room
+    pub fn events(&self) -> impl Iterator<Item = Event> + '_ {
+        let background =
+            (!self.background.is_plain()).then_some(Event::Background(self.background));
+        let layers = (!self.layers.is_empty()).then(|| Event::Layers(self.layers.clone()));
+        let pages = (!self.pages.is_empty()).then(|| Event::Pages {
+            pages: self.pages.clone(),
+            current: self.page,
+        });
+        background
+            .into_iter()
+            .chain(layers)
+            .chain(pages)
+            .chain(self.stacked_items())
+    }
+
+    /// Events adding the items, from the bottom of the stack to the top, as
+    /// peers stack items in the order they receive them
+    fn stacked_items(&self) -> Vec<Event> {
+        let rank: HashMap<_, _> = (1..).zip(&self.order).map(|(i, &id)| (id, i)).collect();
+
+        let lines = self.lines.iter().map(|x| (x.id, Event::Line(x.clone())));
+        let shapes = self.shapes.iter().map(|x| (x.id, Event::Shape(x.clone())));
+        let labels = self.labels.iter().map(|x| (x.id, Event::Label(x.clone())));
+
+        let mut items: Vec<_> = lines.chain(shapes).chain(labels).collect();
+        items.sort_by_key(|(id, _)| rank.get(id).copied().unwrap_or(0));
+        items.into_iter().map(|(_, event)| event).collect()
+    }
+
+    /// # Errors
+    ///
+    /// Fails if the data is not a valid board
+    pub fn load(reader: impl Read) -> Result<Self, ciborium::de::Error<std::io::Error>> {
+        let mut board: Self = ciborium::de::from_reader(reader)?;
+        board.assign_missing_ids();
+        Ok(board)
+    }
+
+    /// Boards saved before items had ids get new ones, owned by nobody
+    fn assign_missing_ids(&mut self) {
+        let ids = self.ids_mut().filter(|id| id.is_unset());
+
+        for (n, id) in (1..).zip(ids) {
+            *id = ItemId {
+                src: CollabId::LEGACY,
+                n,
+            };
+        }
+    }
+
+    /// Move, scale and rotate everything
+    pub fn transform(&mut self, placement: &Placement) {
+        for placed in self.placements_mut() {
+            *placed = placed.then(placement);
+        }
+    }
+
+    /// Give new ids to every item, e.g. to paste a copy. Items sharing the
+    /// same id, like chunks of a long stroke, keep sharing it.
+    pub fn reassign_ids(&mut self, mut new_id: impl FnMut() -> ItemId) {
+        let mut new_ids = HashMap::new();
+        for id in self.ids_mut() {
+            *id = *new_ids.entry(*id).or_insert_with(&mut new_id);
+        }
+    }
+
+    /// Put every item on `page`, e.g. to paste on the page shown
+    pub fn move_to_page(&mut self, page: ItemId) {
+        let pages = self
+            .lines
+            .iter_mut()
+            .map(|x| &mut x.page)
+            .chain(self.shapes.iter_mut().map(|x| &mut x.page))
+            .chain(self.labels.iter_mut().map(|x| &mut x.page));
+        for x in pages {
+            *x = page;
+        }
+    }
+
+    /// Put every item on `layer`, e.g. to paste on the active layer
+    pub fn move_to_layer(&mut self, layer: ItemId) {
+        let layers = self
+            .lines
+            .iter_mut()
+            .map(|x| &mut x.layer)
+            .chain(self.shapes.iter_mut().map(|x| &mut x.layer))
+            .chain(self.labels.iter_mut().map(|x| &mut x.layer));
+        for x in layers {
+            *x = layer;
+        }
+    }
+
+    /// Ids of all the items, without repetitions
+    #[must_use]
+    pub fn ids(&self) -> Vec<ItemId> {
+        let mut ids = Vec::new();
+        let all = self
+            .lines
+            .iter()
+            .map(|x| x.id)
+            .chain(self.shapes.iter().map(|x| x.id))
+            .chain(self.labels.iter().map(|x| x.id));
+        for id in all {
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+        ids
+    }
+
+    /// Smallest rectangle, as `(min, max)`, containing the points of every
+    /// item. The extent of labels is estimated from their font size.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn bounds(&self) -> Option<((f32, f32), (f32, f32))> {
+        let line_points = self.lines.iter().flat_map(|line| {
+            line.points
+                .iter()
+                .map(move |&p| line.placement.apply(point(p)))
+        });
+
+        let shape_points = self.shapes.iter().flat_map(|shape| {
+            let (from, to) = (shape.from, shape.to);
+            [from, to, (from.0, to.1), (to.0, from.1)]
+                .into_iter()
+                .chain(shape.vertices.iter().copied())
+                .map(move |p| shape.placement.apply(point(p)))
+        });
+
+        let label_points = self.labels.iter().flat_map(|label| {
+            let (x, y) = point(label.position);
+            let size = f32::from(label.size);
+            let width = size * LABEL_CHAR_WIDTH * label.content.chars().count() as f32;
+            [(x, y - size / 2.0), (x + width, y + size / 2.0)].map(|p| label.placement.apply(p))
+        });
+
+        line_points
+            .chain(shape_points)
+            .chain(label_points)
+            .fold(None, |bounds, (x, y)| {
+                let ((x0, y0), (x1, y1)) = bounds.unwrap_or(((x, y), (x, y)));
+                Some(((x0.min(x), y0.min(y)), (x1.max(x), y1.max(y))))
+            })
+    }
+
+    fn placements_mut(&mut self) -> impl Iterator<Item = &mut Placement> {
+        self.lines
+            .iter_mut()
+            .map(|x| &mut x.placement)
+            .chain(self.shapes.iter_mut().map(|x| &mut x.placement))
+            .chain(self.labels.iter_mut().map(|x| &mut x.placement))
+    }
+
+    fn ids_mut(&mut self) -> impl Iterator<Item = &mut ItemId> {
+        self.lines
+            .iter_mut()
+            .map(|x| &mut x.id)
+            .chain(self.shapes.iter_mut().map(|x| &mut x.id))
+            .chain(self.labels.iter_mut().map(|x| &mut x.id))
+            .chain(self.order.iter_mut())
+    }
+
+    /// Put a new item on top of the stack
+    fn push_item(&mut self, id: ItemId) {
+        self.order.push(id);
+    }
+
+    /// Items on hidden or locked layers are kept
+    fn clear_shown_page(&mut self) {
+        let Board {
+            lines,
+            shapes,
+            labels,
+            layers,
+            pages,
+            page,
+            ..
+        } = self;
+        let shown = page_index(pages, *page);
+        let kept = |page, layer| page_index(pages, page) != shown || !is_editable(layers, layer);
+        lines.retain(|x| kept(x.page, x.layer));
+        shapes.retain(|x| kept(x.page, x.layer));
+        labels.retain(|x| kept(x.page, x.layer));
+        self.retain_order();
+    }
+
+    /// Copies of `items`, owned by `src` and numbered from `first`, go on
+    /// top in the same order as `items`
+    fn duplicate(&mut self, items: &[ItemId], src: CollabId, first: u32, placement: &Placement) {
+        duplicate_items(&mut self.lines, items, src, first, placement);
+        duplicate_items(&mut self.shapes, items, src, first, placement);
+        duplicate_items(&mut self.labels, items, src, first, placement);
+
+        let ids: HashSet<_> = self.ids().into_iter().collect();
+        let copies = (first..).take(items.len()).map(|n| ItemId { src, n });
+        for id in copies.filter(|id| ids.contains(id)) {
+            self.push_item(id);
+        }
+    }
+
+    /// Forget the items which have been removed
+    fn retain_order(&mut self) {
+        let ids: HashSet<_> = self.ids().into_iter().collect();
+        self.order.retain(|id| ids.contains(id));
+    }
+
+    /// # Errors
+    ///
+    /// Fails if the board cannot be written
+    pub fn save(&self, writer: impl Write) -> Result<(), ciborium::ser::Error<std::io::Error>> {
+        ciborium::ser::into_writer(self, writer)
+    }
+}
+
+/// Rebuild the board from the events sent by all the peers in a room
+#[derive(Default)]
+pub struct Recorder {
+    board: Board,
+    /// Lines being drawn, with the scale of the view of their peer
+    pending: HashMap<CollabId, (Line, f32)>,
+}
+
+impl Recorder {
+    #[must_use]
+    pub fn new(board: Board) -> Self {
+        Self {
+            board,
+            pending: HashMap::default(),
+        }
+    }
+
+    #[must_use]
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    /// Returns `true` if the board has changed
+    pub fn record(&mut self, event: &AddressedEvent) -> bool {
+        match &event.event {
+            Event::Move(e) => {
+                if e.pressed {
+                    let (line, scale) = self.pending.entry(event.src).or_default();
+                    *scale = e.scale;
+                    line.id = ItemId {
+                        src: event.src,
+                        n: e.stroke,
+                    };
+                    line.color = e.color;
+                    line.line_width = e.line_width;
+                    line.style = e.style;
+                    line.layer = e.layer;
+                    line.page = e.page;
+                    line.points.push((e.x, e.y));
+                    line.pressures.push(e.pressure);
+                }
+                false
+            }
+            Event::Release => self.release(event.src),
+            // Only the page shown is cleared, as in the app
+            Event::Clear => {
+                self.board.clear_shown_page();
+                true
+            }
+            Event::Line(line) => {
+                self.board.lines.push(line.clone());
+                self.board.push_item(line.id);
+                true
+            }
+            Event::Shape(shape) => {
+                self.board.shapes.push(shape.clone());
+                self.board.push_item(shape.id);
+                true
+            }
+            Event::Recognized(shape) => {
+                self.pending.remove(&event.src);
+                self.board.shapes.push(shape.clone());
+                self.board.push_item(shape.id);
+                true
+            }
+            Event::Label(label) => {
+                self.board.labels.push(label.clone());
+                self.board.push_item(label.id);
+                true
+            }
+            Event::Transform { items, placement } => {
+                transform_items(&mut self.board.lines, items, placement);
+                transform_items(&mut self.board.shapes, items, placement);
+                transform_items(&mut self.board.labels, items, placement);
+                true
+            }
+            Event::Delete(items) => {
+                self.board.lines.retain(|x| !items.contains(&x.id));
+                self.board.shapes.retain(|x| !items.contains(&x.id));
+                self.board.labels.retain(|x| !items.contains(&x.id));
+                self.board.retain_order();
+                true
+            }
+            Event::Duplicate {
+                items,
+                first,
+                placement,
+            } => {
+                self.board.duplicate(items, event.src, *first, placement);
+                true
+            }
+            Event::Fill { items, color } => {
+                let lines = self.board.lines.iter_mut().map(|x| (x.id, &mut x.fill));
+                let shapes = self.board.shapes.iter_mut().map(|x| (x.id, &mut x.fill));
+                for (_, fill) in lines.chain(shapes).filter(|(id, _)| items.contains(id)) {
+                    *fill = *color;
+                }
+                true
+            }
+            Event::Layers(layers) => {
+                self.board.layers.clone_from(layers);
+                true
+            }
+            Event::Pages { pages, current } => {
+                self.board.pages.clone_from(pages);
+                self.board.page = *current;
+                true
+            }
+            Event::Background(background) => {
+                self.board.background = *background;
+                true
+            }
+            Event::Ping(_) | Event::Pong { .. } => false,
+        }
+    }
+
+    /// Complete the line pending for `src`, e.g. because the peer left in
+    /// the middle of a stroke. Returns `true` if the board has changed.
+    pub fn release(&mut self, src: CollabId) -> bool {
+        match self.pending.remove(&src) {
+            Some((mut line, scale)) if !line.points.is_empty() => {
+                line.simplify(scale);
+                self.board.push_item(line.id);
+                self.board.lines.push(line);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MoveEvent;
+
+    /// A line as saved before items had ids
+    #[derive(Serialize)]
+    struct LegacyLine {
+        color: u32,
+        line_width: u8,
+        points: Vec<(i16, i16)>,
+    }
+
+    #[derive(Serialize)]
+    struct LegacyBoard {
+        lines: Vec<LegacyLine>,
+    }
+
+    fn legacy_board() -> Vec<u8> {
+        let line = |x| LegacyLine {
+            color: 0xffff_ffff,
+            line_width: 4,
+            points: vec![(x, 0), (x, 10)],
+        };
+        let board = LegacyBoard {
+            lines: vec![line(0), line(10)],
+        };
+        let mut data = Vec::new();
+        ciborium::ser::into_writer(&board, &mut data).unwrap();
+        data
+    }
+
+    fn id(n: u32) -> ItemId {
+        ItemId {
+            src: CollabId(1),
+            n,
+        }
+    }
+
+    /// A bit of everything a board can have
+    fn board() -> Board {
+        let top = Layer::new(id(1), "Top".to_owned());
+        let second = Page::new(id(2));
+
+        Board {
+            lines: vec![Line {
+                color: 0xff00_00ff,
+                line_width: 4,
+                points: vec![(0, 0), (10, 5), (20, 0)],
+                pressures: vec![10, 200, 255],
+                style: StrokeStyle {
+                    end_arrow: true,
+                    ..StrokeStyle::default()
+                },
+                fill: Some(0x00ff_00ff),
+                id: id(3),
+                placement: Placement::around((10.0, 0.0), (5.0, 5.0), 0.5, 2.0),
+                layer: top.id,
+                page: second.id,
+            }],
+            shapes: vec![Shape {
+                kind: ShapeKind::Ellipse,
+                color: 0xffff_ffff,
+                line_width: 2,
+                from: (-10, -10),
+                to: (10, 10),
+                vertices: Vec::new(),
+                style: StrokeStyle::default(),
+                fill: None,
+                id: id(4),
+                placement: Placement::default(),
+                layer: ItemId::default(),
+                page: ItemId::default(),
+            }],
+            labels: vec![Label {
+                content: "hello".to_owned(),
+                color: 0xffff_00ff,
+                size: 20,
+                position: (0, 30),
+                id: id(5),
+                placement: Placement::default(),
+                layer: top.id,
+                page: ItemId::default(),
+            }],
+            // The label was added before the shape
+            order: vec![id(3), id(5), id(4)],
+            layers: vec![Layer::new(ItemId::default(), "Bottom".to_owned()), top],
+            background: Background::Dots,
+            pages: vec![Page::new(ItemId::default()), second.clone()],
+            page: second.id,
+        }
+    }
+
+    #[test]
+    fn save_and_load() {
+        let board = board();
+
+        let mut data = Vec::new();
+        board.save(&mut data).unwrap();
+
+        assert_eq!(Board::load(data.as_slice()).unwrap(), board);
+    }
+
+    #[test]
+    fn replay_events() {
+        let board = board();
+
+        let mut recorder = Recorder::default();
+        for event in board.events() {
+            let event = AddressedEvent {
+                src: CollabId::KEEPER,
+                event,
+            };
+            recorder.record(&event);
+        }
+
+        assert_eq!(*recorder.board(), board);
+    }
+
+    /// Record a stroke drawn by a peer whose view is at `scale`
+    fn draw(scale: f32, points: &[(i16, i16)]) -> Vec<(i16, i16)> {
+        let src = CollabId(1);
+        let mut recorder = Recorder::default();
+
+        for &(x, y) in points {
+            let event = Event::Move(MoveEvent {
+                color: 0xffff_ffff,
+                line_width: 10,
+                x,
+                y,
+                pressed: true,
+                pressure: u8::MAX,
+                stroke: 1,
+                laser: false,
+                style: StrokeStyle::default(),
+                layer: ItemId::default(),
+                page: ItemId::default(),
+                scale,
+            });
+            recorder.record(&AddressedEvent { src, event });
+        }
+        recorder.record(&AddressedEvent {
+            src,
+            event: Event::Release,
+        });
+
+        recorder.board().lines[0].points.clone()
+    }
+
+    #[test]
+    fn strokes_are_simplified_at_the_scale_of_their_peer() {
+        let points = [(0, 0), (20, 1), (40, 0)];
+
+        assert_eq!(draw(1.0, &points), [(0, 0), (40, 0)]);
+        assert_eq!(draw(0.25, &points), points);
+    }
+
+    fn item_ids(board: &Board) -> Vec<ItemId> {
+        board
+            .events()
+            .filter_map(|event| match event {
+                Event::Line(x) => Some(x.id),
+                Event::Shape(x) => Some(x.id),
+                Event::Label(x) => Some(x.id),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn replay_in_the_order_items_were_added() {
+        let Board {
+            lines,
+            shapes,
+            labels,
+            ..
+        } = board();
+        let (line, shape, label) = (&lines[0], &shapes[0], &labels[0]);
+        let added = [
+            Event::Shape(shape.clone()),
+            Event::Line(line.clone()),
+            Event::Label(label.clone()),
+            Event::Duplicate {
+                items: vec![label.id, shape.id],
+                first: 10,
+                placement: Placement::default(),
+            },
+            Event::Delete(vec![line.id]),
+            Event::Line(Line {
+                id: id(20),
+                ..line.clone()
+            }),
+        ];
+
+        let mut recorder = Recorder::default();
+        for event in added {
+            recorder.record(&AddressedEvent {
+                src: CollabId(1),
+                event,
+            });
+        }
+
+        let expected = [shape.id, label.id, id(10), id(11), id(20)];
+        assert_eq!(item_ids(recorder.board()), expected);
+        assert_eq!(recorder.board().order, expected);
+    }
+
+    #[test]
+    fn clear_keeps_items_on_hidden_or_locked_layers() {
+        let clear = |board: Board| {
+            let mut recorder = Recorder::new(board);
+            recorder.record(&AddressedEvent {
+                src: CollabId(1),
+                event: Event::Clear,
+            });
+            item_ids(recorder.board())
+        };
+
+        let mut board = board();
+        board.page = ItemId::default();
+        assert_eq!(clear(board.clone()), [id(3)]);
+
+        board.layers[1].locked = true;
+        assert_eq!(clear(board.clone()), [id(3), id(5)]);
+
+        board.layers[1].locked = false;
+        board.layers[1].visible = false;
+        assert_eq!(clear(board), [id(3), id(5)]);
+    }
+
+    #[test]
+    fn items_saved_without_order_are_at_the_bottom() {
+        let mut board = board();
+        board.order = vec![id(5)];
+
+        assert_eq!(item_ids(&board), [id(3), id(4), id(5)]);
+    }
+
+    #[test]
+    fn load_legacy_board() {
+        let board = Board::load(legacy_board().as_slice()).unwrap();
+
+        assert_eq!(board.lines.len(), 2);
+        assert_eq!(board.lines[1].points, [(10, 0), (10, 10)]);
+        assert!(board.shapes.is_empty() && board.labels.is_empty());
+        assert!(board.layers.is_empty() && board.pages.is_empty());
+        assert!(board.background.is_plain());
+        assert_ne!(board.lines[0].id, board.lines[1].id);
+    }
+
+    #[test]
+    fn legacy_ids_are_reserved() {
+        let board = Board::load(legacy_board().as_slice()).unwrap();
+        let ids = board.ids();
+
+        assert_eq!(ids.len(), 2);
+        for id in ids {
+            assert!(!id.is_unset());
+            assert_eq!(id.src, CollabId::LEGACY);
+            assert_ne!(id.src, CollabId::KEEPER);
+            assert!(!CollabId::PEERS.contains(&id.src.0));
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+
+use crate::Board;
+
+const METADATA_START: &str = r#"<metadata id="lavagna">"#;
+const METADATA_END: &str = "</metadata>";
+
+impl Board {
+    /// Text to put in the system clipboard: an SVG document, which can be
+    /// pasted in other applications, carrying the board in its metadata so
+    /// it can be pasted back in lavagna without losing anything.
+    #[must_use]
+    pub fn to_clipboard(&self) -> String {
+        let mut data = Vec::new();
+        // Writing to a Vec cannot fail
+        let _ = self.save(&mut data);
+        let metadata = format!("{METADATA_START}{}{METADATA_END}", STANDARD.encode(data));
+        self.svg_document(&metadata)
+    }
+
+    /// `None` if the text has not been copied from lavagna
+    #[must_use]
+    pub fn from_clipboard(text: &str) -> Option<Self> {
+        let start = text.find(METADATA_START)? + METADATA_START.len();
+        let end = start + text[start..].find(METADATA_END)?;
+        let data = STANDARD.decode(text[start..end].trim()).ok()?;
+        Self::load(data.as_slice()).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CollabId, ItemId, Label, Line, Placement, Shape, ShapeKind, StrokeStyle};
+
+    fn board() -> Board {
+        let id = |n| ItemId {
+            src: CollabId(7),
+            n,
+        };
+
+        Board {
+            lines: vec![Line {
+                color: 0xffff_ffff,
+                line_width: 4,
+                points: vec![(0, 0), (10, 5), (20, 0)],
+                id: id(1),
+                ..Line::default()
+            }],
+            shapes: vec![Shape {
+                kind: ShapeKind::Arrow,
+                color: 0xff00_00ff,
+                line_width: 2,
+                from: (0, 0),
+                to: (30, 40),
+                vertices: Vec::new(),
+                style: StrokeStyle::default(),
+                fill: None,
+                id: id(2),
+                placement: Placement::around((0.0, 0.0), (1.0, 2.0), 0.3, 1.5),
+                layer: ItemId::default(),
+                page: ItemId::default(),
+            }],
+            labels: vec![Label {
+                // Escaped in the SVG, not in the metadata
+                content: "<a & b>".to_owned(),
+                color: 0x00ff_00ff,
+                size: 16,
+                position: (5, -5),
+                id: id(3),
+                placement: Placement::default(),
+                layer: ItemId::default(),
+                page: ItemId::default(),
+            }],
+            ..Board::default()
+        }
+    }
+
+    #[test]
+    fn paste_what_was_copied() {
+        let board = board();
+        let text = board.to_clipboard();
+
+        assert!(text.starts_with("<svg"));
+        assert_eq!(Board::from_clipboard(&text), Some(board));
+    }
+
+    #[test]
+    fn foreign_svg_is_rejected() {
+        let plain = r#"<svg xmlns="http://www.w3.org/2000/svg"><path d="M0 0L1 1"/></svg>"#;
+        assert_eq!(Board::from_clipboard(plain), None);
+        assert_eq!(Board::from_clipboard("some text"), None);
+        assert_eq!(Board::from_clipboard(""), None);
+
+        let wrap = |data: &str| format!("<svg>{METADATA_START}{data}{METADATA_END}</svg>");
+        assert_eq!(Board::from_clipboard(&wrap("not base64!")), None);
+        assert_eq!(
+            Board::from_clipboard(&wrap(&STANDARD.encode("not cbor"))),
+            None
+        );
+
+        // Cut before the end of the metadata
+        let copied = board().to_clipboard();
+        let start = copied.find(METADATA_START).unwrap();
+        assert_eq!(Board::from_clipboard(&copied[..start + 40]), None);
+    }
+}
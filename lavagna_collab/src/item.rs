@@ -0,0 +1,84 @@
+#![allow(clippy::module_name_repetitions)]
+
+use serde::{Deserialize, Serialize};
+
+use crate::CollabId;
+
+/// Identifies a line, shape or label. Items are numbered by the peer which
+/// created them, starting from 1, so every peer agrees on the id.
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct ItemId {
+    pub src: CollabId,
+    pub n: u32,
+}
+
+impl ItemId {
+    /// Items saved before ids were introduced
+    #[must_use]
+    pub fn is_unset(&self) -> bool {
+        self.n == 0
+    }
+}
+
+/// Where an item has been moved, scaled and rotated after being drawn:
+/// every point `p` is drawn at `rotation(scale * p) + translation`
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq)]
+pub struct Placement {
+    pub translation: (f32, f32),
+    /// Radians, counterclockwise
+    pub rotation: f32,
+    pub scale: f32,
+}
+
+impl Default for Placement {
+    fn default() -> Self {
+        Self {
+            translation: (0.0, 0.0),
+            rotation: 0.0,
+            scale: 1.0,
+        }
+    }
+}
+
+impl Placement {
+    #[must_use]
+    pub fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+
+    #[must_use]
+    pub fn apply(&self, (x, y): (f32, f32)) -> (f32, f32) {
+        let (sin, cos) = self.rotation.sin_cos();
+        let (x, y) = (x * self.scale, y * self.scale);
+        (
+            x * cos - y * sin + self.translation.0,
+            x * sin + y * cos + self.translation.1,
+        )
+    }
+
+    /// This placement followed by `next`
+    #[must_use]
+    pub fn then(&self, next: &Self) -> Self {
+        Self {
+            translation: next.apply(self.translation),
+            rotation: self.rotation + next.rotation,
+            scale: self.scale * next.scale,
+        }
+    }
+
+    /// Rotate and scale around `pivot`, then translate
+    #[must_use]
+    pub fn around(pivot: (f32, f32), translation: (f32, f32), rotation: f32, scale: f32) -> Self {
+        let rotated = Self {
+            translation: (0.0, 0.0),
+            rotation,
+            scale,
+        };
+        let (x, y) = rotated.apply(pivot);
+        Self {
+            translation: (pivot.0 - x + translation.0, pivot.1 - y + translation.1),
+            rotation,
+            scale,
+        }
+    }
+}
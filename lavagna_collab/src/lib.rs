@@ -0,0 +1,151 @@
+#![deny(unsafe_code)]
+#![warn(clippy::all, clippy::pedantic)]
+
+mod background;
+mod board;
+mod clipboard;
+mod item;
+mod relay;
+mod simplify;
+mod style;
+mod svg;
+
+pub use crate::background::Background;
+pub use crate::board::{Board, Label, Layer, Line, Page, Recorder, Shape, ShapeKind};
+pub use crate::item::{ItemId, Placement};
+pub use crate::relay::{RelayStatus, RELAY_ACTIVATE};
+pub use crate::simplify::{simplify, simplify_tolerance};
+pub use crate::style::{Dash, StrokeStyle};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum Event {
+    Move(MoveEvent),
+    Release,
+    Clear,
+    /// A complete line, sent to peers joining a room with some content
+    Line(Line),
+    Shape(Shape),
+    /// A shape replacing the line the sender is drawing
+    Recognized(Shape),
+    Label(Label),
+    /// Move, scale and rotate some items
+    Transform {
+        items: Vec<ItemId>,
+        placement: Placement,
+    },
+    Delete(Vec<ItemId>),
+    /// Copy some items, then move the copies. Copies are numbered by the
+    /// sender from `first`, in the same order of `items`.
+    Duplicate {
+        items: Vec<ItemId>,
+        first: u32,
+        placement: Placement,
+    },
+    /// Fill closed lines and shapes, or empty them with `None`
+    Fill {
+        items: Vec<ItemId>,
+        color: Option<u32>,
+    },
+    /// All the layers, bottom first, replacing the current ones
+    Layers(Vec<Layer>),
+    /// The paper under the drawing
+    Background(Background),
+    /// All the pages, in order, replacing the current ones, and the page
+    /// shown to everyone
+    Pages {
+        pages: Vec<Page>,
+        current: ItemId,
+    },
+    /// Sent regularly, to measure the round trip time
+    Ping(u32),
+    /// Answer to a `Ping` sent by `dst`
+    Pong {
+        dst: CollabId,
+        nonce: u32,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone)]
+pub struct MoveEvent {
+    pub color: u32,
+    pub line_width: u8,
+    pub x: i16,
+    pub y: i16,
+    pub pressed: bool,
+    /// Pen pressure, `u8::MAX` when the device does not support it
+    #[serde(default = "full_pressure")]
+    pub pressure: u8,
+    /// Item number of the line being drawn
+    #[serde(default)]
+    pub stroke: u32,
+    /// Pointing with the laser, which leaves a trail but draws nothing
+    #[serde(default)]
+    pub laser: bool,
+    #[serde(default, skip_serializing_if = "StrokeStyle::is_plain")]
+    pub style: StrokeStyle,
+    #[serde(default, skip_serializing_if = "ItemId::is_unset")]
+    pub layer: ItemId,
+    #[serde(default, skip_serializing_if = "ItemId::is_unset")]
+    pub page: ItemId,
+    /// Scale of the view of the peer drawing, below 1 when zoomed in, see
+    /// `simplify_tolerance`
+    #[serde(default = "unit_scale", skip_serializing_if = "is_unit_scale")]
+    pub scale: f32,
+}
+
+fn full_pressure() -> u8 {
+    u8::MAX
+}
+
+fn unit_scale() -> f32 {
+    1.0
+}
+
+// As serde expects it
+#[allow(clippy::trivially_copy_pass_by_ref)]
+fn is_unit_scale(scale: &f32) -> bool {
+    (scale - 1.0).abs() < f32::EPSILON
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AddressedEvent {
+    pub src: CollabId,
+    pub event: Event,
+}
+
+impl AddressedEvent {
+    /// # Panics
+    ///
+    /// Never, writing to a `Vec` cannot fail
+    #[must_use]
+    pub fn encode(&self) -> Vec<u8> {
+        let mut payload = Vec::new();
+        ciborium::ser::into_writer(self, &mut payload).unwrap();
+        payload
+    }
+
+    #[must_use]
+    pub fn decode(payload: &[u8]) -> Option<Self> {
+        ciborium::de::from_reader(payload).ok()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct CollabId(pub u16);
+
+impl CollabId {
+    /// Owner of the items saved before items had ids
+    pub const LEGACY: Self = Self(u16::MAX);
+    /// The keeper, which records persistent rooms on the server
+    pub const KEEPER: Self = Self(u16::MAX - 1);
+    /// Ids peers can pick, the others are reserved
+    pub const PEERS: std::ops::Range<u16> = 0..u16::MAX - 1;
+}
+
+impl From<u16> for CollabId {
+    fn from(value: u16) -> Self {
+        Self(value)
+    }
+}
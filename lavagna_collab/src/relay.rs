@@ -0,0 +1,74 @@
+#![allow(clippy::module_name_repetitions)]
+
+use std::fmt;
+use std::str::FromStr;
+
+/// Sent by a peer, as a text message, when it starts using the relay instead
+/// of WebRTC
+pub const RELAY_ACTIVATE: &str = "relay";
+
+/// Sent by the relay server, as a text message, every time some peer joins or
+/// leaves the room. Payloads are sent as binary messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RelayStatus {
+    /// Peers connected to the room via WebRTC
+    pub peers: usize,
+    /// Peers using the relay
+    pub relayed: usize,
+}
+
+impl fmt::Display for RelayStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.peers, self.relayed)
+    }
+}
+
+impl FromStr for RelayStatus {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        let mut values = s.split(' ').map(str::parse);
+        let (Some(Ok(peers)), Some(Ok(relayed)), None) =
+            (values.next(), values.next(), values.next())
+        else {
+            return Err(());
+        };
+        Ok(Self { peers, relayed })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_round_trip() {
+        let status = RelayStatus {
+            peers: 3,
+            relayed: 12,
+        };
+        assert_eq!(status.to_string(), "3 12");
+        assert_eq!(status.to_string().parse(), Ok(status));
+        assert_eq!(
+            RelayStatus::default().to_string().parse(),
+            Ok(RelayStatus::default())
+        );
+    }
+
+    #[test]
+    fn malformed_status() {
+        for text in [
+            "",
+            "3",
+            "3 ",
+            " 3 12",
+            "3 12 1",
+            "3  12",
+            "-1 2",
+            "a b",
+            RELAY_ACTIVATE,
+        ] {
+            assert_eq!(text.parse::<RelayStatus>(), Err(()), "{text:?}");
+        }
+    }
+}
@@ -0,0 +1,121 @@
+#![allow(clippy::module_name_repetitions)]
+
+/// Points closer than this fraction of the line width to the simplified
+/// stroke are removed, the difference is not visible
+const SIMPLIFY_TOLERANCE: f32 = 0.1;
+
+/// Tolerance for a line drawn with the view at `scale`: when zoomed in,
+/// details smaller than the line width are visible to whoever draws them.
+/// The scale of the peer drawing is used, so every peer and the keeper end
+/// up with the same points.
+#[must_use]
+pub fn simplify_tolerance(line_width: f32, scale: f32) -> f32 {
+    line_width * SIMPLIFY_TOLERANCE * scale.clamp(0.0, 1.0)
+}
+
+/// Ramer-Douglas-Peucker: remove the points which are closer than `tolerance`
+/// to the simplified stroke. First and last points are always kept.
+pub fn simplify<P: Copy>(points: &[P], tolerance: f32, xy: impl Fn(&P) -> (f32, f32)) -> Vec<P> {
+    if points.len() < 3 {
+        return points.to_vec();
+    }
+
+    let mut keep = vec![false; points.len()];
+    keep[0] = true;
+    keep[points.len() - 1] = true;
+
+    let mut ranges = vec![(0, points.len() - 1)];
+
+    while let Some((start, end)) = ranges.pop() {
+        let a = xy(&points[start]);
+        let b = xy(&points[end]);
+
+        let farthest = (start + 1..end)
+            .map(|i| (i, distance_to_segment(xy(&points[i]), a, b)))
+            .max_by(|(_, d1), (_, d2)| d1.total_cmp(d2));
+
+        if let Some((i, distance)) = farthest {
+            if distance > tolerance {
+                keep[i] = true;
+                ranges.push((start, i));
+                ranges.push((i, end));
+            }
+        }
+    }
+
+    points
+        .iter()
+        .zip(keep)
+        .filter_map(|(p, keep)| keep.then_some(*p))
+        .collect()
+}
+
+#[allow(clippy::many_single_char_names)]
+fn distance_to_segment(p: (f32, f32), a: (f32, f32), b: (f32, f32)) -> f32 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let length_squared = dx * dx + dy * dy;
+
+    let t = if length_squared > 0.0 {
+        (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / length_squared).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+
+    let (x, y) = (a.0 + t * dx, a.1 + t * dy);
+    ((p.0 - x).powi(2) + (p.1 - y).powi(2)).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // As `simplify` expects it
+    #[allow(clippy::trivially_copy_pass_by_ref)]
+    fn xy(&p: &(f32, f32)) -> (f32, f32) {
+        p
+    }
+
+    #[test]
+    fn short_strokes_are_kept() {
+        assert!(simplify(&[], 1.0, xy).is_empty());
+        assert_eq!(simplify(&[(0.0, 0.0)], 1.0, xy), [(0.0, 0.0)]);
+
+        let two = [(0.0, 0.0), (0.0, 0.1)];
+        assert_eq!(simplify(&two, 1.0, xy), two);
+    }
+
+    #[test]
+    fn collinear_points_collapse_to_the_endpoints() {
+        let points: Vec<_> = (0..10u8)
+            .map(|i| (f32::from(i), f32::from(i) * 2.0))
+            .collect();
+        assert_eq!(simplify(&points, 0.01, xy), [(0.0, 0.0), (9.0, 18.0)]);
+    }
+
+    #[test]
+    fn points_at_the_tolerance_are_removed() {
+        // The middle point is exactly 1 away from the segment between the others
+        let points = [(0.0, 0.0), (5.0, 1.0), (10.0, 0.0)];
+        assert_eq!(simplify(&points, 1.0, xy), [(0.0, 0.0), (10.0, 0.0)]);
+        assert_eq!(simplify(&points, 0.99, xy), points);
+    }
+
+    #[test]
+    fn zooming_in_keeps_details() {
+        assert!((simplify_tolerance(10.0, 1.0) - 1.0).abs() < f32::EPSILON);
+        assert!((simplify_tolerance(10.0, 0.5) - 0.5).abs() < f32::EPSILON);
+
+        // Zooming out does not lose details, which were not visible anyway
+        assert!((simplify_tolerance(10.0, 4.0) - 1.0).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn first_and_last_points_are_kept() {
+        // A closed stroke, whose ends are the same point
+        let points = [(0.0, 0.0), (0.0, 0.1), (10.0, 0.0), (0.1, 0.0), (0.0, 0.0)];
+        let simplified = simplify(&points, 1.0, xy);
+        assert_eq!(simplified.first(), points.first());
+        assert_eq!(simplified.last(), points.last());
+        assert_eq!(simplified, [(0.0, 0.0), (10.0, 0.0), (0.0, 0.0)]);
+    }
+}
@@ -0,0 +1,102 @@
+#![allow(clippy::module_name_repetitions)]
+
+use serde::{Deserialize, Serialize};
+
+/// Angle between the line and each side of an arrowhead, in radians
+const ARROW_HEAD_ANGLE: f32 = 0.5;
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Dash {
+    #[default]
+    Solid,
+    Dashed,
+    Dotted,
+}
+
+/// How lines and shapes are stroked
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Default)]
+pub struct StrokeStyle {
+    #[serde(default)]
+    pub dash: Dash,
+    /// Arrowhead at the first point
+    #[serde(default)]
+    pub start_arrow: bool,
+    /// Arrowhead at the last point
+    #[serde(default)]
+    pub end_arrow: bool,
+}
+
+impl StrokeStyle {
+    /// Solid, without arrowheads
+    #[must_use]
+    pub fn is_plain(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Lengths of the dashes and of the gaps between them, `None` when
+    /// solid. Dots are dashes of zero length, drawn with round caps.
+    #[must_use]
+    pub fn dash_pattern(&self, line_width: f32) -> Option<(f32, f32)> {
+        match self.dash {
+            Dash::Solid => None,
+            Dash::Dashed => Some((line_width * 3.0 + 6.0, line_width * 2.0 + 6.0)),
+            Dash::Dotted => Some((0.0, line_width * 2.0 + 2.0)),
+        }
+    }
+
+    /// Arrowheads at the ends of the line through `points`, each as its two
+    /// sides and its tip in between
+    #[must_use]
+    pub fn arrow_heads(&self, points: &[(f32, f32)], line_width: f32) -> Vec<[(f32, f32); 3]> {
+        let mut heads = Vec::new();
+
+        if points.len() < 2 {
+            return heads;
+        }
+
+        if self.start_arrow {
+            heads.push(arrow_head(points.iter(), line_width));
+        }
+        if self.end_arrow {
+            heads.push(arrow_head(points.iter().rev(), line_width));
+        }
+
+        heads
+    }
+}
+
+/// The arrowhead at the first of `points`, pointing away from the first
+/// point as far as the head is long, so it follows the end of curved lines
+fn arrow_head<'a>(
+    mut points: impl Iterator<Item = &'a (f32, f32)>,
+    line_width: f32,
+) -> [(f32, f32); 3] {
+    let head_length = (line_width * 3.0).max(15.0);
+
+    let tip = points.next().copied().unwrap_or_default();
+    let distance = |&(x, y): &(f32, f32)| (x - tip.0).hypot(y - tip.1);
+
+    let mut base = tip;
+    for p in points {
+        base = *p;
+        if distance(p) >= head_length {
+            break;
+        }
+    }
+
+    let length = distance(&base).max(f32::EPSILON);
+    let back = (
+        (base.0 - tip.0) / length * head_length,
+        (base.1 - tip.1) / length * head_length,
+    );
+
+    let side = |angle: f32| {
+        let (sin, cos) = angle.sin_cos();
+        (
+            tip.0 + back.0 * cos - back.1 * sin,
+            tip.1 + back.0 * sin + back.1 * cos,
+        )
+    };
+
+    [side(ARROW_HEAD_ANGLE), tip, side(-ARROW_HEAD_ANGLE)]
+}
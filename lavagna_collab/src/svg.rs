@@ -0,0 +1,380 @@
+use std::fmt::Write;
+
+use crate::{Board, ItemId, Label, Line, Placement, Shape, ShapeKind, StrokeStyle};
+
+/// Empty space around the drawing
+const MARGIN: f32 = 10.0;
+
+impl Board {
+    /// Render the board as a standalone SVG document. Pen pressure is not
+    /// rendered, lines have constant width. Hidden layers are left out.
+    /// `metadata` is added verbatim, it must be valid XML. Every item is
+    /// rendered, whatever its page.
+    pub(crate) fn svg_document(&self, metadata: &str) -> String {
+        let ((x0, y0), (x1, y1)) = self.bounds().unwrap_or_default();
+        let (x, y) = (x0 - MARGIN, y0 - MARGIN);
+        let (width, height) = (x1 - x0 + 2.0 * MARGIN, y1 - y0 + 2.0 * MARGIN);
+
+        let mut svg = String::new();
+
+        // The board has y pointing up, SVG has it pointing down
+        let _ = write!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="{x} {} {width} {height}">"#,
+            -y - height,
+        );
+        svg.push_str(metadata);
+        svg.push_str(r#"<g transform="scale(1,-1)" fill="none" stroke-linecap="round" stroke-linejoin="round">"#);
+
+        // Layers bottom first, items on unknown layers go on the first one
+        let rank = |layer: ItemId| self.layers.iter().position(|x| x.id == layer).unwrap_or(0);
+
+        for i in 0..self.layers.len().max(1) {
+            if self.layers.get(i).is_some_and(|layer| !layer.visible) {
+                continue;
+            }
+            for line in self.lines.iter().filter(|x| rank(x.layer) == i) {
+                write_line(&mut svg, line);
+            }
+            for shape in self.shapes.iter().filter(|x| rank(x.layer) == i) {
+                write_shape(&mut svg, shape);
+            }
+            for label in self.labels.iter().filter(|x| rank(x.layer) == i) {
+                write_label(&mut svg, label);
+            }
+        }
+
+        svg.push_str("</g></svg>");
+        svg
+    }
+}
+
+fn write_line(svg: &mut String, line: &Line) {
+    let Some((first, rest)) = line.points.split_first() else {
+        return;
+    };
+
+    let mut d = format!("M{} {}", first.0, first.1);
+    for (x, y) in rest {
+        let _ = write!(d, " L{x} {y}");
+    }
+
+    // Under the line, closed
+    if let Some(fill) = line.fill {
+        let _ = write!(
+            svg,
+            r#"<path d="{d} Z" stroke="none"{}{}/>"#,
+            paint_fill(fill),
+            transform(&line.placement),
+        );
+    }
+
+    let _ = write!(
+        svg,
+        r#"<path d="{d}"{}{}{}/>"#,
+        stroke(line.color, line.line_width),
+        dash_array(line.style, line.line_width),
+        transform(&line.placement),
+    );
+
+    let points: Vec<_> = line.points.iter().map(|&(x, y)| point(x, y)).collect();
+    write_arrow_heads(
+        svg,
+        line.style,
+        &points,
+        line.color,
+        line.line_width,
+        &line.placement,
+    );
+}
+
+fn write_shape(svg: &mut String, shape: &Shape) {
+    let ((x0, y0), (x1, y1)) = (shape.from, shape.to);
+    let style = shape.kind.stroke_style(shape.style);
+    let fill = match shape.fill {
+        Some(fill) if shape.kind.is_closed() => paint_fill(fill),
+        _ => String::new(),
+    };
+    let stroke = format!(
+        "{}{}{fill}",
+        stroke(shape.color, shape.line_width),
+        dash_array(style, shape.line_width),
+    );
+    let transform = transform(&shape.placement);
+
+    let _ = match shape.kind {
+        ShapeKind::Line | ShapeKind::Arrow => write!(
+            svg,
+            r#"<line x1="{x0}" y1="{y0}" x2="{x1}" y2="{y1}"{stroke}{transform}/>"#
+        ),
+        ShapeKind::Rectangle => write!(
+            svg,
+            r#"<rect x="{}" y="{}" width="{}" height="{}"{stroke}{transform}/>"#,
+            x0.min(x1),
+            y0.min(y1),
+            (i32::from(x1) - i32::from(x0)).abs(),
+            (i32::from(y1) - i32::from(y0)).abs(),
+        ),
+        ShapeKind::Ellipse => write!(
+            svg,
+            r#"<ellipse cx="{}" cy="{}" rx="{}" ry="{}"{stroke}{transform}/>"#,
+            (f32::from(x0) + f32::from(x1)) / 2.0,
+            (f32::from(y0) + f32::from(y1)) / 2.0,
+            (f32::from(x1) - f32::from(x0)).abs() / 2.0,
+            (f32::from(y1) - f32::from(y0)).abs() / 2.0,
+        ),
+        ShapeKind::Polygon => {
+            let points: Vec<_> = shape
+                .vertices
+                .iter()
+                .map(|(x, y)| format!("{x},{y}"))
+                .collect();
+            write!(
+                svg,
+                r#"<polygon points="{}"{stroke}{transform}/>"#,
+                points.join(" "),
+            )
+        }
+    };
+
+    let points = [point(x0, y0), point(x1, y1)];
+    write_arrow_heads(
+        svg,
+        style,
+        &points,
+        shape.color,
+        shape.line_width,
+        &shape.placement,
+    );
+}
+
+fn point(x: i16, y: i16) -> (f32, f32) {
+    (x.into(), y.into())
+}
+
+/// Arrowheads are always solid
+fn write_arrow_heads(
+    svg: &mut String,
+    style: StrokeStyle,
+    points: &[(f32, f32)],
+    color: u32,
+    line_width: u8,
+    placement: &Placement,
+) {
+    for [(lx, ly), (tx, ty), (rx, ry)] in style.arrow_heads(points, line_width.into()) {
+        let _ = write!(
+            svg,
+            r#"<path d="M{lx} {ly} L{tx} {ty} L{rx} {ry}"{}{}/>"#,
+            stroke(color, line_width),
+            transform(placement),
+        );
+    }
+}
+
+fn write_label(svg: &mut String, label: &Label) {
+    let (x, y) = label.position;
+
+    // Flipped back, or the text would be upside down
+    let _ = write!(
+        svg,
+        r#"<g{}><text transform="translate({x},{y}) scale(1,-1)" font-size="{}" dominant-baseline="middle" stroke="none" fill="{}"{}>{}</text></g>"#,
+        transform(&label.placement),
+        label.size,
+        color(label.color),
+        opacity("fill-opacity", label.color),
+        escape(&label.content),
+    );
+}
+
+fn stroke(rgba: u32, width: u8) -> String {
+    format!(
+        r#" stroke="{}" stroke-width="{width}"{}"#,
+        color(rgba),
+        opacity("stroke-opacity", rgba),
+    )
+}
+
+fn paint_fill(rgba: u32) -> String {
+    format!(
+        r#" fill="{}"{}"#,
+        color(rgba),
+        opacity("fill-opacity", rgba),
+    )
+}
+
+fn dash_array(style: StrokeStyle, line_width: u8) -> String {
+    match style.dash_pattern(line_width.into()) {
+        Some((dash, gap)) => format!(r#" stroke-dasharray="{dash} {gap}""#),
+        None => String::new(),
+    }
+}
+
+fn transform(placement: &Placement) -> String {
+    if placement.is_identity() {
+        return String::new();
+    }
+
+    let (x, y) = placement.translation;
+    format!(
+        r#" transform="translate({x},{y}) rotate({}) scale({})""#,
+        placement.rotation.to_degrees(),
+        placement.scale,
+    )
+}
+
+/// Colors are encoded as `0xAABBGGRR`
+fn color(rgba: u32) -> String {
+    let [r, g, b, _] = rgba.to_le_bytes();
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+fn opacity(attribute: &str, rgba: u32) -> String {
+    let [.., a] = rgba.to_le_bytes();
+    if a == u8::MAX {
+        String::new()
+    } else {
+        format!(r#" {attribute}="{}""#, f32::from(a) / f32::from(u8::MAX))
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CollabId, Dash, Layer};
+
+    fn svg(board: &Board) -> String {
+        board.svg_document("<metadata/>")
+    }
+
+    #[test]
+    fn document() {
+        let board = Board {
+            lines: vec![Line {
+                color: 0xffff_ffff,
+                line_width: 4,
+                points: vec![(0, 0), (20, 10)],
+                ..Line::default()
+            }],
+            ..Board::default()
+        };
+        let svg = svg(&board);
+
+        assert!(svg.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg""#));
+        assert!(svg.contains("<metadata/>"));
+        // y points down in SVG
+        assert!(svg.contains(r#"<g transform="scale(1,-1)""#));
+        assert!(svg.ends_with("</g></svg>"));
+    }
+
+    #[test]
+    fn line() {
+        let board = Board {
+            lines: vec![Line {
+                color: 0x80ff_0000,
+                line_width: 4,
+                points: vec![(0, 0), (10, 5), (20, 0)],
+                fill: Some(0xff00_ff00),
+                ..Line::default()
+            }],
+            ..Board::default()
+        };
+        let svg = svg(&board);
+
+        // The fill goes under the line
+        let fill = svg
+            .find(r##"<path d="M0 0 L10 5 L20 0 Z" stroke="none" fill="#00ff00"/>"##)
+            .unwrap();
+        let line = svg
+            .find(r##"<path d="M0 0 L10 5 L20 0" stroke="#0000ff" stroke-width="4" stroke-opacity="0.5019608"/>"##)
+            .unwrap();
+        assert!(fill < line);
+    }
+
+    #[test]
+    fn dashed_arrow() {
+        let board = Board {
+            shapes: vec![Shape {
+                kind: ShapeKind::Arrow,
+                color: 0xff00_00ff,
+                line_width: 2,
+                from: (0, 0),
+                to: (30, 0),
+                vertices: Vec::new(),
+                style: StrokeStyle {
+                    dash: Dash::Dashed,
+                    ..StrokeStyle::default()
+                },
+                fill: None,
+                id: ItemId::default(),
+                placement: Placement::default(),
+                layer: ItemId::default(),
+                page: ItemId::default(),
+            }],
+            ..Board::default()
+        };
+        let svg = svg(&board);
+
+        assert!(svg.contains(
+            r##"<line x1="0" y1="0" x2="30" y2="0" stroke="#ff0000" stroke-width="2" stroke-dasharray="12 10"/>"##
+        ));
+
+        // A solid head, with its tip at the end
+        let head = &svg[svg.find("<path").unwrap()..];
+        assert!(head.contains(" L30 0 L"));
+        assert!(!head.contains("stroke-dasharray"));
+    }
+
+    #[test]
+    fn label() {
+        let board = Board {
+            labels: vec![Label {
+                content: "<a & b>".to_owned(),
+                color: 0xff00_ff00,
+                size: 16,
+                position: (5, -5),
+                id: ItemId::default(),
+                placement: Placement::default(),
+                layer: ItemId::default(),
+                page: ItemId::default(),
+            }],
+            ..Board::default()
+        };
+
+        assert!(svg(&board).contains(
+            r##"<g><text transform="translate(5,-5) scale(1,-1)" font-size="16" dominant-baseline="middle" stroke="none" fill="#00ff00">&lt;a &amp; b&gt;</text></g>"##
+        ));
+    }
+
+    #[test]
+    fn hidden_layers_are_left_out() {
+        let hidden = ItemId {
+            src: CollabId(1),
+            n: 1,
+        };
+        let mut layer = Layer::new(hidden, "Hidden".to_owned());
+        layer.visible = false;
+
+        let line = |x, layer| Line {
+            color: 0xffff_ffff,
+            line_width: 4,
+            points: vec![(x, 0), (x, 10)],
+            layer,
+            ..Line::default()
+        };
+        let board = Board {
+            lines: vec![line(0, ItemId::default()), line(50, hidden)],
+            layers: vec![Layer::new(ItemId::default(), "Bottom".to_owned()), layer],
+            ..Board::default()
+        };
+        let svg = svg(&board);
+
+        assert!(svg.contains(r#"d="M0 0 L0 10""#));
+        assert!(!svg.contains(r#"d="M50 0 L50 10""#));
+    }
+}
@@ -0,0 +1,280 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::ws::Message;
+use lavagna_collab::{AddressedEvent, Board, CollabId, Event, Recorder};
+use matchbox_socket::{PeerState, WebRtcSocket};
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tracing::{debug, error, info};
+
+use crate::rooms::{PeerSender, RelayId};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+const SAVE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// What happens on the relay of a room. Peers using the relay cannot reach
+/// the keeper, which is a WebRTC peer, so the server tells it directly.
+pub(crate) enum RelayEvent {
+    /// A peer started using the relay, and needs the whole board
+    Joined(RelayId, PeerSender),
+    Payload(RelayId, Vec<u8>),
+    Left(RelayId),
+}
+
+/// The keeper is a headless peer which joins every room, records what is
+/// drawn, stores it on disk and sends it to peers joining later.
+pub(crate) struct Keeper {
+    dir: PathBuf,
+    server_url: String,
+}
+
+impl Keeper {
+    pub(crate) fn new(dir: PathBuf, bind: SocketAddr) -> Self {
+        // The keeper connects to this same server
+        let ip = match bind.ip() {
+            IpAddr::V4(ip) if ip.is_unspecified() => IpAddr::V4(Ipv4Addr::LOCALHOST),
+            IpAddr::V6(ip) if ip.is_unspecified() => IpAddr::V6(Ipv6Addr::LOCALHOST),
+            ip => ip,
+        };
+        let server_url = format!("ws://{}", SocketAddr::new(ip, bind.port()));
+
+        Self { dir, server_url }
+    }
+
+    /// The task ends when the keeper leaves the room, or cannot join it.
+    /// What happens on the relay is received from `relay_events`.
+    pub(crate) fn spawn(
+        self: &Arc<Self>,
+        room: &str,
+        relay_events: UnboundedReceiver<RelayEvent>,
+    ) -> JoinHandle<()> {
+        tokio::spawn(run(self.clone(), room.to_owned(), relay_events))
+    }
+
+    fn board_path(&self, room: &str) -> PathBuf {
+        // Room names are arbitrary, escape anything which is not safe in a file name
+        let mut name = String::with_capacity(room.len());
+        for b in room.bytes() {
+            if b.is_ascii_alphanumeric() || b == b'-' {
+                name.push(b as char);
+            } else {
+                let _ = write!(name, "_{b:02x}");
+            }
+        }
+        self.dir.join(format!("{name}.cbor"))
+    }
+}
+
+fn percent_encode(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || b"-._~".contains(&b) {
+            encoded.push(b as char);
+        } else {
+            let _ = write!(encoded, "%{b:02X}");
+        }
+    }
+    encoded
+}
+
+fn load(path: &Path) -> Board {
+    let Ok(file) = File::open(path) else {
+        return Board::default();
+    };
+
+    Board::load(BufReader::new(file)).unwrap_or_else(|e| {
+        error!("cannot load {}: {e}", path.display());
+        Board::default()
+    })
+}
+
+fn save(path: &Path, board: &Board) {
+    // Write to a temporary file first, so a crash never leaves a truncated board
+    let tmp_path = path.with_extension("cbor.tmp");
+
+    let result = File::create(&tmp_path)
+        .map_err(|e| e.to_string())
+        .and_then(|file| board.save(BufWriter::new(file)).map_err(|e| e.to_string()))
+        .and_then(|()| std::fs::rename(&tmp_path, path).map_err(|e| e.to_string()));
+
+    match result {
+        Ok(()) => debug!("saved {}", path.display()),
+        Err(e) => error!("cannot save {}: {e}", path.display()),
+    }
+}
+
+/// Events which draw the whole board, for peers joining the room
+fn replay(board: &Board) -> impl Iterator<Item = AddressedEvent> + '_ {
+    board.events().map(|event| AddressedEvent {
+        src: CollabId::KEEPER,
+        event,
+    })
+}
+
+/// Answer pings, so peers can see the round trip time to the keeper
+fn pong(event: &AddressedEvent) -> Option<AddressedEvent> {
+    let Event::Ping(nonce) = event.event else {
+        return None;
+    };
+    Some(AddressedEvent {
+        src: CollabId::KEEPER,
+        event: Event::Pong {
+            dst: event.src,
+            nonce,
+        },
+    })
+}
+
+/// Peers using the relay, with their collab id once known
+type Relays = HashMap<RelayId, (PeerSender, Option<CollabId>)>;
+
+/// Returns `true` if the board has changed
+fn handle_relay_event(event: RelayEvent, recorder: &mut Recorder, relays: &mut Relays) -> bool {
+    match event {
+        RelayEvent::Joined(id, sender) => {
+            for event in replay(recorder.board()) {
+                let _ = sender.send(Message::Binary(event.encode()));
+            }
+            relays.insert(id, (sender, None));
+            false
+        }
+        RelayEvent::Payload(id, payload) => {
+            let (Some(event), Some((sender, src))) =
+                (AddressedEvent::decode(&payload), relays.get_mut(&id))
+            else {
+                return false;
+            };
+
+            *src = Some(event.src);
+
+            if let Some(pong) = pong(&event) {
+                let _ = sender.send(Message::Binary(pong.encode()));
+            }
+
+            recorder.record(&event)
+        }
+        // Keep what the peer was drawing, as the other peers do
+        RelayEvent::Left(id) => relays
+            .remove(&id)
+            .and_then(|(_, src)| src)
+            .is_some_and(|src| recorder.release(src)),
+    }
+}
+
+async fn run(keeper: Arc<Keeper>, room: String, mut relay_events: UnboundedReceiver<RelayEvent>) {
+    let path = keeper.board_path(&room);
+    let mut recorder = Recorder::new(load(&path));
+
+    let url = format!("{}/{}?keeper", keeper.server_url, percent_encode(&room));
+    let (mut socket, mut message_loop) = WebRtcSocket::new_reliable(url);
+
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    let mut dirty = false;
+    let mut last_save = Instant::now();
+    let mut collab_ids = HashMap::new();
+    let mut relays = Relays::new();
+
+    info!("keeper joining room {room}");
+
+    loop {
+        // The message loop ends when the server closes the connection,
+        // because there is nobody left in the room.
+        tokio::select! {
+            _ = &mut message_loop => break,
+            _ = interval.tick() => {}
+        }
+
+        for (peer, state) in socket.update_peers() {
+            match state {
+                PeerState::Connected => {
+                    for event in replay(recorder.board()) {
+                        socket.send(event.encode().into(), peer);
+                    }
+                }
+                PeerState::Disconnected => {
+                    // Keep what the peer was drawing, as the other peers do
+                    if let Some(src) = collab_ids.remove(&peer) {
+                        dirty |= recorder.release(src);
+                    }
+                }
+            }
+        }
+
+        for (peer, payload) in socket.receive() {
+            let Some(event) = AddressedEvent::decode(&payload) else {
+                continue;
+            };
+
+            collab_ids.insert(peer, event.src);
+
+            if let Some(pong) = pong(&event) {
+                let _ = socket.try_send(pong.encode().into(), peer);
+            }
+
+            dirty |= recorder.record(&event);
+        }
+
+        while let Ok(event) = relay_events.try_recv() {
+            dirty |= handle_relay_event(event, &mut recorder, &mut relays);
+        }
+
+        if dirty && last_save.elapsed() >= SAVE_INTERVAL {
+            save(&path, recorder.board());
+            dirty = false;
+            last_save = Instant::now();
+        }
+    }
+
+    if dirty {
+        save(&path, recorder.board());
+    }
+
+    info!("keeper left room {room}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keeper() -> Keeper {
+        Keeper::new(PathBuf::from("boards"), "0.0.0.0:3536".parse().unwrap())
+    }
+
+    #[test]
+    fn boards_stay_in_the_dir() {
+        let keeper = keeper();
+
+        for (room, file) in [
+            ("room-1", "room-1.cbor"),
+            ("../x", "_2e_2e_2fx.cbor"),
+            ("a/b", "a_2fb.cbor"),
+            ("/etc/passwd", "_2fetc_2fpasswd.cbor"),
+            ("a\\b", "a_5cb.cbor"),
+            ("è", "_c3_a8.cbor"),
+        ] {
+            let path = keeper.board_path(room);
+            assert_eq!(path.parent(), Some(Path::new("boards")), "{room}");
+            assert_eq!(path.file_name().unwrap(), file, "{room}");
+        }
+    }
+
+    #[test]
+    fn escaped_names_do_not_clash() {
+        let keeper = keeper();
+        assert_ne!(keeper.board_path("a_2fb"), keeper.board_path("a/b"));
+    }
+
+    #[test]
+    fn the_keeper_connects_to_this_server() {
+        assert_eq!(keeper().server_url, "ws://127.0.0.1:3536");
+        assert_eq!(percent_encode("a b/c"), "a%20b%2Fc");
+    }
+}
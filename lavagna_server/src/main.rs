@@ -0,0 +1,77 @@
+#![deny(unsafe_code)]
+#![warn(clippy::all, clippy::pedantic)]
+
+mod keeper;
+mod relay;
+mod rooms;
+mod signaling;
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use axum::routing::get;
+use axum::Router;
+use clap::Parser;
+use tracing::info;
+
+use crate::keeper::Keeper;
+use crate::rooms::Rooms;
+
+/// Signaling server for lavagna, compatible with `matchbox_server`
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// Address the server listens on
+    #[clap(short = 'b', long, default_value = "0.0.0.0:3536")]
+    bind: SocketAddr,
+    /// Maximum number of peers allowed in a single room
+    #[clap(short = 'm', long)]
+    max_peers: Option<usize>,
+    /// Keep rooms content in this directory, so it survives when everybody leaves
+    #[clap(short = 'p', long)]
+    persist_dir: Option<PathBuf>,
+    #[clap(short = 'v', long)]
+    verbose: bool,
+}
+
+#[tokio::main]
+async fn main() {
+    let args = Args::parse();
+
+    let level = if args.verbose {
+        tracing::Level::DEBUG
+    } else {
+        tracing::Level::INFO
+    };
+    tracing_subscriber::fmt().with_max_level(level).init();
+
+    let keeper = args.persist_dir.map(|dir| {
+        if let Err(e) = std::fs::create_dir_all(&dir) {
+            tracing::error!("cannot create {}: {e}", dir.display());
+            std::process::exit(1);
+        }
+        Keeper::new(dir, args.bind)
+    });
+
+    let rooms = Rooms::new(args.max_peers, keeper);
+
+    let app = Router::new()
+        .route("/api/rooms", get(signaling::list_rooms))
+        .route("/:room", get(signaling::join_room))
+        .route("/relay/:room", get(relay::join_relay))
+        .with_state(rooms);
+
+    let listener = match tokio::net::TcpListener::bind(args.bind).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            tracing::error!("cannot bind {}: {e}", args.bind);
+            std::process::exit(1);
+        }
+    };
+
+    info!("listening on {}", args.bind);
+
+    if let Err(e) = axum::serve(listener, app).await {
+        tracing::error!("server error: {e}");
+    }
+}
@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+
+use axum::extract::ws::{Message, WebSocket};
+use axum::extract::{Path, Query, State, WebSocketUpgrade};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use futures::{SinkExt, StreamExt};
+use lavagna_collab::RELAY_ACTIVATE;
+use tokio::sync::mpsc;
+
+use crate::rooms::Rooms;
+
+/// Fallback for peers which cannot use WebRTC. Every payload received from a
+/// peer is forwarded to the other peers using the relay in the same room.
+pub(crate) async fn join_relay(
+    ws: WebSocketUpgrade,
+    Path(room): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+    State(rooms): State<Rooms>,
+) -> Response {
+    let active = params.contains_key("active");
+
+    if active && rooms.is_full(&room) {
+        return (StatusCode::SERVICE_UNAVAILABLE, "room is full").into_response();
+    }
+
+    ws.on_upgrade(move |socket| handle_relay(socket, room, rooms, active))
+}
+
+async fn handle_relay(socket: WebSocket, room: String, rooms: Rooms, active: bool) {
+    let (mut ws_tx, mut ws_rx) = socket.split();
+    let (tx, mut rx) = mpsc::unbounded_channel();
+
+    let Some(id) = rooms.join_relay(&room, tx, active) else {
+        let _ = ws_tx.close().await;
+        return;
+    };
+
+    let forward = tokio::spawn(async move {
+        while let Some(message) = rx.recv().await {
+            if ws_tx.send(message).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    while let Some(Ok(message)) = ws_rx.next().await {
+        match message {
+            Message::Binary(payload) => rooms.relay(&room, id, &payload),
+            Message::Text(text) if text == RELAY_ACTIVATE => rooms.activate_relay(&room, id),
+            Message::Close(_) => break,
+            _ => {}
+        }
+    }
+
+    rooms.leave_relay(&room, id);
+    forward.abort();
+}
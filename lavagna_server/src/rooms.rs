@@ -0,0 +1,664 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use axum::extract::ws::Message;
+use lavagna_collab::RelayStatus;
+use matchbox_protocol::{JsonPeerEvent, PeerEvent, PeerId};
+use serde::Serialize;
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::keeper::{Keeper, RelayEvent};
+
+pub(crate) type PeerSender = UnboundedSender<Message>;
+
+/// All the rooms currently open, shared between connections
+#[derive(Clone)]
+pub(crate) struct Rooms {
+    max_peers: Option<usize>,
+    keeper: Option<Arc<Keeper>>,
+    inner: Arc<Mutex<HashMap<String, Room>>>,
+}
+
+#[derive(Default)]
+struct Room {
+    peers: HashMap<PeerId, PeerSender>,
+    keeper: KeeperState,
+    /// Tells the keeper what happens on the relay, while there is a keeper
+    to_keeper: Option<UnboundedSender<RelayEvent>>,
+    relays: HashMap<RelayId, Relay>,
+}
+
+/// Identifies a connection to the relay
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct RelayId(Uuid);
+
+impl std::fmt::Display for RelayId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// A connection to the relay. Until it is active, the peer is using WebRTC
+/// and it is only interested in the room status.
+struct Relay {
+    sender: PeerSender,
+    active: bool,
+}
+
+/// The keeper is a peer too, but it is not counted as a room participant
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum KeeperState {
+    #[default]
+    Absent,
+    /// Not joined yet, identified so a task ending late does not reset the
+    /// state of the keeper spawned after it
+    Spawned(Uuid),
+    Joined(PeerId),
+}
+
+impl Room {
+    fn webrtc_participants(&self) -> usize {
+        match self.keeper {
+            KeeperState::Joined(_) => self.peers.len() - 1,
+            _ => self.peers.len(),
+        }
+    }
+
+    fn relayed(&self) -> usize {
+        self.relays.values().filter(|r| r.active).count()
+    }
+
+    fn participants(&self) -> usize {
+        self.webrtc_participants() + self.relayed()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.peers.is_empty() && self.relays.is_empty()
+    }
+
+    fn tell_keeper(&self, event: RelayEvent) {
+        if let Some(to_keeper) = &self.to_keeper {
+            let _ = to_keeper.send(event);
+        }
+    }
+
+    /// Once only the keeper is left, tell it to save the board and go
+    fn dismiss_idle_keeper(&self) {
+        if self.participants() > 0 {
+            return;
+        }
+
+        if let KeeperState::Joined(keeper_id) = self.keeper {
+            if let Some(keeper) = self.peers.get(&keeper_id) {
+                let _ = keeper.send(Message::Close(None));
+            }
+        }
+    }
+
+    /// Let relay users know if they can keep using WebRTC or not
+    fn notify_relays(&self) {
+        let status = RelayStatus {
+            peers: self.webrtc_participants(),
+            relayed: self.relayed(),
+        };
+
+        for relay in self.relays.values() {
+            let _ = relay.sender.send(Message::Text(status.to_string()));
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct RoomInfo {
+    name: String,
+    peers: usize,
+}
+
+impl Rooms {
+    pub(crate) fn new(max_peers: Option<usize>, keeper: Option<Keeper>) -> Self {
+        Self {
+            max_peers,
+            keeper: keeper.map(Arc::new),
+            inner: Arc::default(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, Room>> {
+        // A poisoned lock only means another connection panicked, the map is
+        // still consistent because every update is a single insert/remove.
+        self.inner
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    pub(crate) fn is_full(&self, room: &str) -> bool {
+        let rooms = self.lock();
+        let peers = rooms.get(room).map_or(0, Room::participants);
+        self.max_peers.is_some_and(|max| peers >= max)
+    }
+
+    pub(crate) fn list(&self) -> Vec<RoomInfo> {
+        let mut list: Vec<_> = self
+            .lock()
+            .iter()
+            .map(|(name, room)| RoomInfo {
+                name: name.clone(),
+                peers: room.participants(),
+            })
+            .collect();
+        list.sort_by(|a, b| a.name.cmp(&b.name));
+        list
+    }
+
+    /// Add a new peer to the room, notifying everyone already there.
+    /// Returns `None` if the room is full.
+    pub(crate) fn join(
+        &self,
+        room_name: &str,
+        sender: PeerSender,
+        is_keeper: bool,
+    ) -> Option<PeerId> {
+        let mut rooms = self.lock();
+        let room = rooms.entry(room_name.to_owned()).or_default();
+
+        if is_keeper {
+            // Too late, everybody left, or there is already a keeper
+            if room.participants() == 0 || matches!(room.keeper, KeeperState::Joined(_)) {
+                // A keeper is spawned again as soon as somebody joins
+                if matches!(room.keeper, KeeperState::Spawned(_)) {
+                    room.keeper = KeeperState::Absent;
+                    room.to_keeper = None;
+                }
+                if room.is_empty() {
+                    rooms.remove(room_name);
+                }
+                return None;
+            }
+        } else if self.max_peers.is_some_and(|max| room.participants() >= max) {
+            return None;
+        }
+
+        let id = PeerId(Uuid::new_v4());
+
+        send(&sender, &PeerEvent::IdAssigned(id));
+
+        for peer in room.peers.values() {
+            send(peer, &PeerEvent::NewPeer(id));
+        }
+
+        room.peers.insert(id, sender);
+
+        if is_keeper {
+            room.keeper = KeeperState::Joined(id);
+        } else if room.keeper == KeeperState::Absent {
+            self.spawn_keeper(room, room_name);
+        }
+
+        room.notify_relays();
+
+        info!("peer {id} joined room {room_name}");
+
+        Some(id)
+    }
+
+    /// Start recording the room, if rooms are persistent
+    fn spawn_keeper(&self, room: &mut Room, room_name: &str) {
+        let Some(keeper) = &self.keeper else {
+            return;
+        };
+
+        let spawn_id = Uuid::new_v4();
+        room.keeper = KeeperState::Spawned(spawn_id);
+
+        let (to_keeper, relay_events) = mpsc::unbounded_channel();
+        let task = keeper.spawn(room_name, relay_events);
+        room.to_keeper = Some(to_keeper);
+
+        // Peers already using the relay need the board too
+        for (&id, relay) in room.relays.iter().filter(|(_, r)| r.active) {
+            room.tell_keeper(RelayEvent::Joined(id, relay.sender.clone()));
+        }
+
+        let rooms = self.clone();
+        let room_name = room_name.to_owned();
+        tokio::spawn(async move {
+            let _ = task.await;
+            rooms.keeper_ended(&room_name, spawn_id);
+        });
+    }
+
+    /// If the keeper never joined, e.g. because it could not connect, another
+    /// one is spawned when the next peer joins
+    fn keeper_ended(&self, room_name: &str, spawn_id: Uuid) {
+        let mut rooms = self.lock();
+
+        let Some(room) = rooms.get_mut(room_name) else {
+            return;
+        };
+
+        if room.keeper == KeeperState::Spawned(spawn_id) {
+            room.keeper = KeeperState::Absent;
+            room.to_keeper = None;
+            warn!("keeper could not join room {room_name}");
+        }
+    }
+
+    pub(crate) fn leave(&self, room_name: &str, id: PeerId) {
+        let mut rooms = self.lock();
+
+        let Some(room) = rooms.get_mut(room_name) else {
+            return;
+        };
+
+        room.peers.remove(&id);
+
+        let keeper_left = room.keeper == KeeperState::Joined(id);
+        if keeper_left {
+            room.keeper = KeeperState::Absent;
+            room.to_keeper = None;
+        }
+
+        for peer in room.peers.values() {
+            send(peer, &PeerEvent::PeerLeft(id));
+        }
+
+        room.notify_relays();
+
+        info!("peer {id} left room {room_name}");
+
+        // The keeper left unexpectedly, replace it
+        if keeper_left && room.participants() > 0 {
+            self.spawn_keeper(room, room_name);
+        }
+
+        room.dismiss_idle_keeper();
+
+        if room.is_empty() {
+            rooms.remove(room_name);
+            debug!("room {room_name} closed");
+        }
+    }
+
+    /// Connect to the relay of a room. If `active`, the peer is going to use
+    /// the relay straight away, otherwise it is just waiting in case WebRTC
+    /// does not work. Returns `None` if the room is full.
+    pub(crate) fn join_relay(
+        &self,
+        room_name: &str,
+        sender: PeerSender,
+        active: bool,
+    ) -> Option<RelayId> {
+        let mut rooms = self.lock();
+        let room = rooms.entry(room_name.to_owned()).or_default();
+
+        if active && self.max_peers.is_some_and(|max| room.participants() >= max) {
+            if room.is_empty() {
+                rooms.remove(room_name);
+            }
+            return None;
+        }
+
+        let id = RelayId(Uuid::new_v4());
+        room.relays.insert(id, Relay { sender, active });
+        room.notify_relays();
+
+        info!("relay {id} joined room {room_name}");
+
+        if active {
+            self.relay_activated(room, room_name, id);
+        }
+
+        Some(id)
+    }
+
+    pub(crate) fn activate_relay(&self, room_name: &str, id: RelayId) {
+        let mut rooms = self.lock();
+
+        let Some(room) = rooms.get_mut(room_name) else {
+            return;
+        };
+
+        let Some(relay) = room.relays.get_mut(&id).filter(|r| !r.active) else {
+            return;
+        };
+
+        relay.active = true;
+        info!("relay {id} activated in room {room_name}");
+
+        room.notify_relays();
+        self.relay_activated(room, room_name, id);
+    }
+
+    /// The keeper records what is drawn on the relay, and sends the board to
+    /// the peer which started using it
+    fn relay_activated(&self, room: &mut Room, room_name: &str, id: RelayId) {
+        if room.keeper == KeeperState::Absent {
+            self.spawn_keeper(room, room_name);
+        } else if let Some(relay) = room.relays.get(&id) {
+            room.tell_keeper(RelayEvent::Joined(id, relay.sender.clone()));
+        }
+    }
+
+    pub(crate) fn leave_relay(&self, room_name: &str, id: RelayId) {
+        let mut rooms = self.lock();
+
+        let Some(room) = rooms.get_mut(room_name) else {
+            return;
+        };
+
+        if room.relays.remove(&id).is_some_and(|r| r.active) {
+            room.tell_keeper(RelayEvent::Left(id));
+        }
+        room.notify_relays();
+
+        info!("relay {id} left room {room_name}");
+
+        room.dismiss_idle_keeper();
+
+        if room.is_empty() {
+            rooms.remove(room_name);
+            debug!("room {room_name} closed");
+        }
+    }
+
+    /// Send a payload to every other peer using the relay in the same room
+    pub(crate) fn relay(&self, room_name: &str, src: RelayId, payload: &[u8]) {
+        let rooms = self.lock();
+
+        let Some(room) = rooms.get(room_name) else {
+            return;
+        };
+
+        if !room.relays.get(&src).is_some_and(|r| r.active) {
+            return;
+        }
+
+        room.tell_keeper(RelayEvent::Payload(src, payload.to_vec()));
+
+        for (_, relay) in room.relays.iter().filter(|(&id, r)| id != src && r.active) {
+            let _ = relay.sender.send(Message::Binary(payload.to_vec()));
+        }
+    }
+
+    /// Forward a signal (offer, answer, ice candidate) to a peer in the same room
+    pub(crate) fn signal(
+        &self,
+        room_name: &str,
+        sender: PeerId,
+        receiver: PeerId,
+        data: serde_json::Value,
+    ) {
+        let rooms = self.lock();
+
+        let Some(peer) = rooms
+            .get(room_name)
+            .and_then(|room| room.peers.get(&receiver))
+        else {
+            debug!("signal from {sender} to unknown peer {receiver}");
+            return;
+        };
+
+        send(peer, &PeerEvent::Signal { sender, data });
+    }
+}
+
+fn send(peer: &PeerSender, event: &JsonPeerEvent) {
+    // If the receiver is gone, the peer is leaving and will be removed soon
+    let _ = peer.send(Message::Text(event.to_string()));
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc::UnboundedReceiver;
+
+    use super::*;
+
+    const ROOM: &str = "room";
+
+    fn peer() -> (PeerSender, UnboundedReceiver<Message>) {
+        mpsc::unbounded_channel()
+    }
+
+    fn peers_in(rooms: &Rooms, room: &str) -> Option<usize> {
+        let list = rooms.list();
+        list.iter().find(|x| x.name == room).map(|x| x.peers)
+    }
+
+    #[test]
+    fn full_rooms_reject_peers() {
+        let rooms = Rooms::new(Some(2), None);
+        let (a, _a) = peer();
+        let (b, _b) = peer();
+        let (c, _c) = peer();
+
+        assert!(rooms.join(ROOM, a, false).is_some());
+        assert!(!rooms.is_full(ROOM));
+        assert!(rooms.join(ROOM, b, false).is_some());
+        assert!(rooms.is_full(ROOM));
+        assert!(rooms.join(ROOM, c.clone(), false).is_none());
+        assert_eq!(peers_in(&rooms, ROOM), Some(2));
+
+        // Other rooms are not affected
+        assert!(!rooms.is_full("other"));
+        assert!(rooms.join("other", c, false).is_some());
+    }
+
+    #[test]
+    fn full_rooms_reject_peers_on_the_relay() {
+        let rooms = Rooms::new(Some(1), None);
+        let (a, _a) = peer();
+        let (b, _b) = peer();
+        let (c, _c) = peer();
+
+        assert!(rooms.join(ROOM, a, false).is_some());
+        assert!(rooms.join_relay(ROOM, b, true).is_none());
+
+        // Waiting on the relay does not count
+        assert!(rooms.join_relay(ROOM, c, false).is_some());
+        assert_eq!(peers_in(&rooms, ROOM), Some(1));
+    }
+
+    #[test]
+    fn the_keeper_is_not_counted() {
+        let rooms = Rooms::new(Some(1), None);
+        let (a, _a) = peer();
+        let (keeper, _keeper) = peer();
+
+        rooms.join(ROOM, a, false);
+
+        // The keeper joins even if the room is full
+        assert!(rooms.join(ROOM, keeper, true).is_some());
+        assert_eq!(peers_in(&rooms, ROOM), Some(1));
+    }
+
+    #[test]
+    fn rooms_are_listed_by_name() {
+        let rooms = Rooms::new(None, None);
+        for room in ["b", "a", "b"] {
+            rooms.join(room, peer().0, false);
+        }
+
+        let list: Vec<_> = rooms
+            .list()
+            .into_iter()
+            .map(|x| (x.name, x.peers))
+            .collect();
+        assert_eq!(list, [("a".to_owned(), 1), ("b".to_owned(), 2)]);
+    }
+
+    #[test]
+    fn rooms_close_when_the_last_peer_leaves() {
+        let rooms = Rooms::new(None, None);
+        let (a, _a) = peer();
+        let (b, mut b_rx) = peer();
+        let (relay, _relay) = peer();
+
+        let a = rooms.join(ROOM, a, false).unwrap();
+        let b = rooms.join(ROOM, b, false).unwrap();
+        let relay = rooms.join_relay(ROOM, relay, true).unwrap();
+
+        while b_rx.try_recv().is_ok() {}
+        rooms.leave(ROOM, a);
+        assert_eq!(peers_in(&rooms, ROOM), Some(2));
+        let left = JsonPeerEvent::PeerLeft(a).to_string();
+        assert_eq!(b_rx.try_recv().ok(), Some(Message::Text(left)));
+
+        rooms.leave(ROOM, b);
+        assert_eq!(peers_in(&rooms, ROOM), Some(1));
+
+        rooms.leave_relay(ROOM, relay);
+        assert!(rooms.list().is_empty());
+    }
+
+    #[test]
+    fn the_keeper_is_dismissed_when_everybody_leaves() {
+        let rooms = Rooms::new(None, None);
+        let (a, _a) = peer();
+        let (keeper, mut keeper_rx) = peer();
+
+        let a = rooms.join(ROOM, a, false).unwrap();
+        let keeper = rooms.join(ROOM, keeper, true).unwrap();
+        while keeper_rx.try_recv().is_ok() {}
+
+        rooms.leave(ROOM, a);
+        let messages: Vec<_> = std::iter::from_fn(|| keeper_rx.try_recv().ok()).collect();
+        assert_eq!(messages.last(), Some(&Message::Close(None)));
+
+        // The room closes once the keeper has saved the board and left
+        assert_eq!(peers_in(&rooms, ROOM), Some(0));
+        rooms.leave(ROOM, keeper);
+        assert!(rooms.list().is_empty());
+    }
+
+    /// The last status sent to a peer waiting on the relay
+    fn last_status(rx: &mut UnboundedReceiver<Message>) -> Option<RelayStatus> {
+        std::iter::from_fn(|| rx.try_recv().ok())
+            .filter_map(|message| match message {
+                Message::Text(text) => text.parse().ok(),
+                _ => None,
+            })
+            .last()
+    }
+
+    #[test]
+    fn relays_are_activated() {
+        let rooms = Rooms::new(None, None);
+        let (waiting, mut waiting_rx) = peer();
+        let (active, mut active_rx) = peer();
+
+        rooms.join(ROOM, peer().0, false);
+        rooms.join(ROOM, peer().0, true);
+        let waiting = rooms.join_relay(ROOM, waiting, false).unwrap();
+
+        // The keeper is not counted, as it is not by peers
+        let status = |peers, relayed| Some(RelayStatus { peers, relayed });
+        assert_eq!(last_status(&mut waiting_rx), status(1, 0));
+        assert_eq!(peers_in(&rooms, ROOM), Some(1));
+
+        let active = rooms.join_relay(ROOM, active, true).unwrap();
+        assert_eq!(last_status(&mut waiting_rx), status(1, 1));
+
+        // Payloads reach active relays only
+        rooms.relay(ROOM, waiting, b"ignored");
+        assert_eq!(
+            active_rx.try_recv().ok(),
+            Some(Message::Text("1 1".to_owned()))
+        );
+        assert!(active_rx.try_recv().is_err());
+
+        rooms.activate_relay(ROOM, waiting);
+        assert_eq!(last_status(&mut active_rx), status(1, 2));
+        assert_eq!(last_status(&mut waiting_rx), status(1, 2));
+        assert_eq!(peers_in(&rooms, ROOM), Some(3));
+
+        rooms.relay(ROOM, waiting, b"hello");
+        assert_eq!(
+            active_rx.try_recv().ok(),
+            Some(Message::Binary(b"hello".to_vec()))
+        );
+        assert!(waiting_rx.try_recv().is_err());
+
+        rooms.leave_relay(ROOM, active);
+        assert_eq!(last_status(&mut waiting_rx), status(1, 1));
+    }
+
+    /// The keeper never connects, there is no server
+    fn persistent_rooms() -> Rooms {
+        let keeper = Keeper::new(std::env::temp_dir(), "127.0.0.1:1".parse().unwrap());
+        Rooms::new(None, Some(keeper))
+    }
+
+    fn keeper_state(rooms: &Rooms) -> KeeperState {
+        rooms
+            .lock()
+            .get(ROOM)
+            .map_or_else(KeeperState::default, |x| x.keeper)
+    }
+
+    // Spawned keepers do not run until the test awaits
+    #[tokio::test]
+    async fn the_keeper_is_spawned_then_joins() {
+        let rooms = persistent_rooms();
+        assert_eq!(keeper_state(&rooms), KeeperState::Absent);
+
+        let a = rooms.join(ROOM, peer().0, false).unwrap();
+        assert!(matches!(keeper_state(&rooms), KeeperState::Spawned(_)));
+
+        // Only one keeper per room
+        rooms.join(ROOM, peer().0, false);
+        let KeeperState::Spawned(spawn_id) = keeper_state(&rooms) else {
+            panic!("keeper not spawned");
+        };
+
+        let keeper = rooms.join(ROOM, peer().0, true).unwrap();
+        assert_eq!(keeper_state(&rooms), KeeperState::Joined(keeper));
+        assert!(rooms.join(ROOM, peer().0, true).is_none());
+
+        // A task ending late does not reset the state
+        rooms.keeper_ended(ROOM, spawn_id);
+        assert_eq!(keeper_state(&rooms), KeeperState::Joined(keeper));
+
+        rooms.leave(ROOM, a);
+        assert_eq!(keeper_state(&rooms), KeeperState::Joined(keeper));
+    }
+
+    #[tokio::test]
+    async fn the_keeper_is_spawned_again_after_leaving() {
+        let rooms = persistent_rooms();
+
+        rooms.join(ROOM, peer().0, false);
+        let keeper = rooms.join(ROOM, peer().0, true).unwrap();
+
+        rooms.leave(ROOM, keeper);
+        assert!(matches!(keeper_state(&rooms), KeeperState::Spawned(_)));
+    }
+
+    #[tokio::test]
+    async fn the_keeper_is_spawned_again_if_it_cannot_join() {
+        let rooms = persistent_rooms();
+
+        rooms.join(ROOM, peer().0, false);
+        let KeeperState::Spawned(spawn_id) = keeper_state(&rooms) else {
+            panic!("keeper not spawned");
+        };
+
+        rooms.keeper_ended(ROOM, spawn_id);
+        assert_eq!(keeper_state(&rooms), KeeperState::Absent);
+
+        rooms.join(ROOM, peer().0, false);
+        assert!(matches!(keeper_state(&rooms), KeeperState::Spawned(x) if x != spawn_id));
+    }
+
+    #[tokio::test]
+    async fn a_late_keeper_does_not_join() {
+        let rooms = persistent_rooms();
+
+        let a = rooms.join(ROOM, peer().0, false).unwrap();
+        rooms.leave(ROOM, a);
+
+        assert!(rooms.join(ROOM, peer().0, true).is_none());
+        assert!(rooms.list().is_empty());
+    }
+}
@@ -0,0 +1,70 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use axum::extract::ws::{Message, WebSocket};
+use axum::extract::{Path, Query, State, WebSocketUpgrade};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use futures::{SinkExt, StreamExt};
+use matchbox_protocol::{JsonPeerRequest, PeerRequest};
+use tokio::sync::mpsc;
+use tracing::warn;
+
+use crate::rooms::Rooms;
+
+pub(crate) async fn list_rooms(State(rooms): State<Rooms>) -> impl IntoResponse {
+    Json(rooms.list())
+}
+
+pub(crate) async fn join_room(
+    ws: WebSocketUpgrade,
+    Path(room): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+    State(rooms): State<Rooms>,
+) -> Response {
+    let is_keeper = params.contains_key("keeper");
+
+    // Reject early, so the client gets a meaningful http error
+    if !is_keeper && rooms.is_full(&room) {
+        return (StatusCode::SERVICE_UNAVAILABLE, "room is full").into_response();
+    }
+
+    ws.on_upgrade(move |socket| handle_peer(socket, room, rooms, is_keeper))
+}
+
+async fn handle_peer(socket: WebSocket, room: String, rooms: Rooms, is_keeper: bool) {
+    let (mut ws_tx, mut ws_rx) = socket.split();
+    let (tx, mut rx) = mpsc::unbounded_channel();
+
+    // The room may have been filled up while upgrading the connection
+    let Some(id) = rooms.join(&room, tx, is_keeper) else {
+        let _ = ws_tx.close().await;
+        return;
+    };
+
+    let forward = tokio::spawn(async move {
+        while let Some(message) = rx.recv().await {
+            if ws_tx.send(message).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    while let Some(Ok(message)) = ws_rx.next().await {
+        let text = match message {
+            Message::Text(text) => text,
+            Message::Close(_) => break,
+            _ => continue,
+        };
+
+        match JsonPeerRequest::from_str(&text) {
+            Ok(PeerRequest::Signal { receiver, data }) => rooms.signal(&room, id, receiver, data),
+            Ok(PeerRequest::KeepAlive) => {}
+            Err(e) => warn!("invalid request from {id}: {e}"),
+        }
+    }
+
+    rooms.leave(&room, id);
+    forward.abort();
+}
@@ -0,0 +1,400 @@
+#![allow(clippy::needless_pass_by_value)]
+#![allow(clippy::cast_possible_truncation)]
+#![allow(clippy::cast_precision_loss)]
+
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+use lavagna_collab::Background;
+
+use crate::theme::Theme;
+use crate::{MainCamera, Stats};
+
+/// Distance between the lines of the paper, when not zoomed out
+pub(crate) const SPACING: f32 = 40.0;
+
+/// When zoomed out, the spacing grows by this factor until lines are at least
+/// `MIN_SCREEN_SPACING` pixels apart
+const SPACING_FACTOR: f32 = 5.0;
+
+const MIN_SCREEN_SPACING: f32 = 16.0;
+
+/// Width of the lines, in pixels
+const LINE_WIDTH: f32 = 1.0;
+
+/// Width of the dots, in pixels
+const DOT_WIDTH: f32 = 3.0;
+
+const PATTERN_COLOR: Srgba = Srgba::new(1.0, 1.0, 1.0, 0.15);
+
+/// The paper under the drawing: plain, or a grid, dots, ruled lines or
+/// isometric triangles. It is drawn around the visible area only, again when
+/// panning or zooming, and is shared with peers.
+pub(crate) struct BackgroundPlugin {
+    background: Option<Background>,
+}
+
+impl BackgroundPlugin {
+    /// `background` is the paper chosen with options, if any, which is sent
+    /// to the room once connected
+    pub(crate) fn new(background: Option<Background>) -> Self {
+        Self { background }
+    }
+}
+
+impl Plugin for BackgroundPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Paper {
+            background: self.background.unwrap_or_default(),
+            announce: self.background.is_some(),
+        })
+        .add_event::<SetBackgroundEvent>()
+        .add_event::<NextBackgroundEvent>()
+        .add_systems(Startup, setup)
+        .add_systems(Update, handle_set_background_event)
+        .add_systems(Update, handle_next_background_event)
+        .add_systems(Update, announce)
+        .add_systems(Update, draw.after(handle_set_background_event));
+    }
+}
+
+#[derive(Resource)]
+pub(crate) struct Paper {
+    background: Background,
+    /// Whether the paper must be sent to the room when joining it
+    announce: bool,
+}
+
+#[derive(Event)]
+pub(crate) struct SetBackgroundEvent {
+    pub(crate) background: Background,
+    forward: bool,
+}
+
+impl SetBackgroundEvent {
+    pub(crate) fn new(background: Background) -> Self {
+        Self {
+            background,
+            forward: true,
+        }
+    }
+    pub(crate) fn local_only(background: Background) -> Self {
+        Self {
+            background,
+            forward: false,
+        }
+    }
+    pub(crate) fn must_be_forwarded(&self) -> bool {
+        self.forward
+    }
+}
+
+impl Paper {
+    pub(crate) fn background(&self) -> Background {
+        self.background
+    }
+}
+
+/// Distance between the lines of the paper drawn with the view at `scale`
+pub(crate) fn paper_spacing(scale: f32) -> f32 {
+    let mut spacing = SPACING;
+    while spacing / scale < MIN_SCREEN_SPACING {
+        spacing *= SPACING_FACTOR;
+    }
+    spacing
+}
+
+/// The corner of the paper drawn with lines `spacing` apart closest to `p`:
+/// where lines cross, or the corners of the triangles of isometric paper. On
+/// ruled paper, `p` only moves to the closest line, plain paper has nothing
+/// to snap to.
+pub(crate) fn snap_to_paper(background: Background, spacing: f32, p: Vec2) -> Vec2 {
+    match background {
+        Background::Plain => p,
+        Background::Ruled => Vec2::new(p.x, (p.y / spacing).round() * spacing),
+        Background::Grid | Background::Dots => (p / spacing).round() * spacing,
+        Background::Isometric => snap_to_triangles(spacing, p),
+    }
+}
+
+fn snap_to_triangles(spacing: f32, p: Vec2) -> Vec2 {
+    // Columns are shifted by half a triangle each
+    let column = spacing * 3f32.sqrt() / 2.0;
+    let corner = |i: f32| {
+        let y = ((p.y - i * spacing / 2.0) / spacing).round() * spacing + i * spacing / 2.0;
+        Vec2::new(i * column, y)
+    };
+
+    let i = p.x / column;
+    let (left, right) = (corner(i.floor()), corner(i.ceil()));
+    if p.distance_squared(left) <= p.distance_squared(right) {
+        left
+    } else {
+        right
+    }
+}
+
+/// Switch to the next kind of paper
+#[derive(Event)]
+pub(crate) struct NextBackgroundEvent;
+
+fn handle_set_background_event(
+    mut events: EventReader<SetBackgroundEvent>,
+    mut paper: ResMut<Paper>,
+) {
+    for event in events.read() {
+        paper.background = event.background;
+    }
+}
+
+fn handle_next_background_event(
+    mut events: EventReader<NextBackgroundEvent>,
+    paper: Res<Paper>,
+    mut set_background_event: EventWriter<SetBackgroundEvent>,
+) {
+    for _ in events.read() {
+        let all = Background::ALL;
+        let i = all.iter().position(|x| *x == paper.background).unwrap_or(0);
+        let next = all[(i + 1) % all.len()];
+        set_background_event.send(SetBackgroundEvent::new(next));
+    }
+}
+
+/// The paper chosen with options is sent to the room as soon as there is
+/// somebody to send it to
+fn announce(
+    stats: Res<Stats>,
+    mut paper: ResMut<Paper>,
+    mut set_background_event: EventWriter<SetBackgroundEvent>,
+) {
+    if paper.announce && stats.collab.peers > 0 {
+        paper.announce = false;
+        set_background_event.send(SetBackgroundEvent::new(paper.background));
+    }
+}
+
+/// The lines or dots of the paper
+#[derive(Component)]
+struct Pattern;
+
+fn setup(mut commands: Commands) {
+    // Under every item
+    let transform = Transform::from_xyz(0., 0., 0.);
+
+    commands.spawn((
+        Pattern,
+        ShapeBundle {
+            path: PathBuilder::new().build(),
+            spatial: transform.into(),
+            ..default()
+        },
+        Stroke::new(PATTERN_COLOR, LINE_WIDTH),
+    ));
+}
+
+/// What the pattern has been drawn for
+#[derive(PartialEq)]
+struct View {
+    background: Background,
+    theme: Theme,
+    /// Visible area, rounded out to the spacing
+    area: Rect,
+    spacing: f32,
+    scale: f32,
+}
+
+fn draw(
+    paper: Res<Paper>,
+    theme: Res<Theme>,
+    camera_q: Query<(&Transform, &OrthographicProjection), With<MainCamera>>,
+    mut pattern_q: Query<(&mut Path, &mut Stroke), With<Pattern>>,
+    mut drawn: Local<Option<View>>,
+) {
+    let Ok((camera, projection)) = camera_q.get_single() else {
+        return;
+    };
+
+    let scale = projection.scale;
+    let spacing = paper_spacing(scale);
+
+    // Panning by less than the spacing does not need a new pattern
+    let center = camera.translation.truncate();
+    let area = Rect {
+        min: ((projection.area.min + center) / spacing).floor() * spacing - spacing,
+        max: ((projection.area.max + center) / spacing).ceil() * spacing + spacing,
+    };
+
+    let view = View {
+        background: paper.background,
+        theme: *theme,
+        area,
+        spacing,
+        scale,
+    };
+
+    if drawn.as_ref() == Some(&view) {
+        return;
+    }
+
+    let Ok((mut path, mut stroke)) = pattern_q.get_single_mut() else {
+        return;
+    };
+
+    let width = match view.background {
+        Background::Dots => DOT_WIDTH,
+        _ => LINE_WIDTH,
+    };
+
+    *path = pattern(&view);
+    *stroke = Stroke {
+        color: theme.ink(PATTERN_COLOR).into(),
+        options: StrokeOptions::default()
+            .with_line_width(width * scale)
+            .with_line_cap(LineCap::Round),
+    };
+
+    *drawn = Some(view);
+}
+
+fn pattern(view: &View) -> Path {
+    let mut builder = PathBuilder::new();
+    let View { area, spacing, .. } = *view;
+
+    // From `from`, a multiple of `step`, up to `to` at least
+    let steps = |from: f32, to: f32, step: f32| {
+        let n = ((to - from) / step).ceil() as i32;
+        (0..=n).map(move |i| from + step * i as f32)
+    };
+
+    let mut segment = |from: Vec2, to: Vec2| {
+        builder.move_to(from);
+        builder.line_to(to);
+    };
+
+    match view.background {
+        Background::Plain => {}
+        Background::Grid => {
+            for x in steps(area.min.x, area.max.x, spacing) {
+                segment(Vec2::new(x, area.min.y), Vec2::new(x, area.max.y));
+            }
+            for y in steps(area.min.y, area.max.y, spacing) {
+                segment(Vec2::new(area.min.x, y), Vec2::new(area.max.x, y));
+            }
+        }
+        Background::Dots => {
+            for x in steps(area.min.x, area.max.x, spacing) {
+                for y in steps(area.min.y, area.max.y, spacing) {
+                    // Drawn as a dot, thanks to the round caps
+                    segment(Vec2::new(x, y), Vec2::new(x, y));
+                }
+            }
+        }
+        Background::Ruled => {
+            for y in steps(area.min.y, area.max.y, spacing) {
+                segment(Vec2::new(area.min.x, y), Vec2::new(area.max.x, y));
+            }
+        }
+        Background::Isometric => {
+            // Vertical lines, and lines at 30 degrees either way, through the
+            // corners of triangles with sides `spacing` long
+            let column = spacing * 3f32.sqrt() / 2.0;
+            let first = (area.min.x / column).floor() * column;
+            for x in steps(first, area.max.x + column, column) {
+                segment(Vec2::new(x, area.min.y), Vec2::new(x, area.max.y));
+            }
+
+            let slope = 1.0 / 3f32.sqrt();
+            let (x0, x1) = (area.min.x, area.max.x);
+            let multiple = |y: f32| (y / spacing).floor() * spacing;
+
+            // Going up, then going down, by where they cross x = 0
+            let rising = area.min.y - x1 * slope..area.max.y - x0 * slope;
+            for c in steps(multiple(rising.start), rising.end + spacing, spacing) {
+                segment(Vec2::new(x0, c + x0 * slope), Vec2::new(x1, c + x1 * slope));
+            }
+            let falling = area.min.y + x0 * slope..area.max.y + x1 * slope;
+            for c in steps(multiple(falling.start), falling.end + spacing, spacing) {
+                segment(Vec2::new(x0, c - x0 * slope), Vec2::new(x1, c - x1 * slope));
+            }
+        }
+    }
+
+    builder.build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    // Only multiplied by whole numbers
+    #[allow(clippy::float_cmp)]
+    fn spacing_grows_when_zoomed_out() {
+        assert_eq!(paper_spacing(1.0), SPACING);
+        assert_eq!(paper_spacing(0.5), SPACING);
+        assert_eq!(paper_spacing(3.0), SPACING * SPACING_FACTOR);
+        assert_eq!(paper_spacing(20.0), SPACING * SPACING_FACTOR.powi(2));
+    }
+
+    #[test]
+    fn grid_and_dots_snap_to_the_corners() {
+        for background in [Background::Grid, Background::Dots] {
+            let snap = |x, y| snap_to_paper(background, SPACING, Vec2::new(x, y));
+            assert_eq!(snap(0.0, 0.0), Vec2::ZERO);
+            assert_eq!(snap(55.0, -25.0), Vec2::new(SPACING, -SPACING));
+            assert_eq!(snap(-15.0, 19.0), Vec2::ZERO);
+            assert_eq!(snap(121.0, 79.0), Vec2::new(3.0 * SPACING, 2.0 * SPACING));
+        }
+    }
+
+    #[test]
+    fn ruled_paper_snaps_to_the_lines() {
+        let snap = |x, y| snap_to_paper(Background::Ruled, SPACING, Vec2::new(x, y));
+        assert_eq!(snap(55.0, -25.0), Vec2::new(55.0, -SPACING));
+        assert_eq!(snap(-15.0, 19.0), Vec2::new(-15.0, 0.0));
+    }
+
+    #[test]
+    fn plain_paper_does_not_snap() {
+        let p = Vec2::new(55.0, -25.0);
+        assert_eq!(snap_to_paper(Background::Plain, SPACING, p), p);
+    }
+
+    #[test]
+    fn zoomed_out_paper_snaps_to_the_lines_shown() {
+        let spacing = paper_spacing(3.0);
+        let snap = |x, y| snap_to_paper(Background::Grid, spacing, Vec2::new(x, y));
+        assert_eq!(snap(55.0, -25.0), Vec2::ZERO);
+        assert_eq!(snap(180.0, -130.0), Vec2::new(spacing, -spacing));
+    }
+
+    #[test]
+    fn isometric_paper_snaps_to_the_triangles() {
+        let column = SPACING * 3f32.sqrt() / 2.0;
+        let snap = |x, y| snap_to_paper(Background::Isometric, SPACING, Vec2::new(x, y));
+
+        assert_eq!(snap(2.0, -3.0), Vec2::ZERO);
+        assert_eq!(snap(3.0, 35.0), Vec2::new(0.0, SPACING));
+
+        // Odd columns are shifted by half a triangle
+        assert_eq!(snap(column - 2.0, 22.0), Vec2::new(column, SPACING / 2.0));
+        assert_eq!(snap(column + 1.0, -17.0), Vec2::new(column, -SPACING / 2.0));
+        assert_eq!(snap(-2.0 * column, 3.0), Vec2::new(-2.0 * column, 0.0));
+    }
+
+    #[test]
+    fn corners_stay_put() {
+        for spacing in [SPACING, paper_spacing(3.0)] {
+            let column = spacing * 3f32.sqrt() / 2.0;
+            for i in -3..=3i8 {
+                for j in -3..=3i8 {
+                    let corner = Vec2::new(
+                        f32::from(i) * column,
+                        (f32::from(j) + f32::from(i) / 2.0) * spacing,
+                    );
+                    let snapped = snap_to_paper(Background::Isometric, spacing, corner);
+                    assert!(snapped.abs_diff_eq(corner, 0.001), "{corner} -> {snapped}");
+                }
+            }
+        }
+    }
+}
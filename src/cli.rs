@@ -1,6 +1,15 @@
+use crate::palette::parse_color;
+use crate::presets::parse_pen;
+use crate::Background;
 use crate::CollabOpt;
 use crate::Opt;
+use crate::Pen;
+use crate::Smoothing;
+use crate::Theme;
+use bevy::color::Srgba;
 use clap::Parser;
+use lavagna_collab::CollabId;
+use rand::Rng;
 
 /// The uncluttered blackboard
 #[derive(Parser, Debug)]
@@ -16,6 +25,24 @@ struct Args {
     verbose: bool,
     #[clap(long)]
     ui: bool,
+    /// Stroke smoothing, from 0 (none) to 1
+    #[clap(long, default_value_t = 1.0)]
+    smoothing: f32,
+    /// Paper under the drawing: plain, grid, dots, ruled or isometric
+    #[clap(long)]
+    background: Option<Background>,
+    /// Colors of the board: blackboard, whiteboard or high-contrast
+    #[clap(long, default_value_t)]
+    theme: Theme,
+    /// Colors of the chalk, in hex and separated by commas, like
+    /// "#ffffff,#ff8800". Keys 1 to 9 pick the first nine.
+    #[clap(long, value_delimiter = ',', value_parser = parse_color)]
+    palette: Vec<Srgba>,
+    /// Pens picked with alt and keys 1 to 9, separated by commas. Each one is
+    /// a hex color and a width, then optionally dashed or dotted, start-arrow
+    /// and end-arrow, separated by colons, like "#ff0000:4:dashed:end-arrow".
+    #[clap(long, value_delimiter = ',', value_parser = parse_pen)]
+    presets: Vec<Pen>,
     #[clap(long)]
     width: Option<String>,
     #[clap(long)]
@@ -28,7 +55,9 @@ pub(crate) fn options_from_args() -> Opt {
 
     // If collab-url is set, then collab-id must be set too. Randomize it if not.
     let collab = if let Some(collab_url) = args.collab_url {
-        let collab_id = args.collab_id.unwrap_or_else(rand::random);
+        let collab_id = args
+            .collab_id
+            .unwrap_or_else(|| rand::thread_rng().gen_range(CollabId::PEERS));
         Some(CollabOpt {
             url: collab_url,
             collab_id,
@@ -42,5 +71,10 @@ pub(crate) fn options_from_args() -> Opt {
         show_debug_pane: args.show_debug_pane,
         verbose: args.verbose,
         ui: args.ui,
+        smoothing: Smoothing::new(args.smoothing),
+        background: args.background,
+        theme: args.theme,
+        palette: args.palette,
+        presets: args.presets,
     }
 }
@@ -0,0 +1,264 @@
+#![allow(clippy::needless_pass_by_value)]
+
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{Mutex, PoisonError};
+
+use bevy::prelude::*;
+use lavagna_collab::{Board, Label, Line, Placement, Shape};
+
+use crate::drawing::{AddLabelEvent, AddLineEvent, AddShapeEvent, Drawn, Item, ItemIds};
+use crate::layers::Layers;
+use crate::local_chalk::cursor_to_world_position;
+use crate::pages::Pages;
+use crate::select_tool::{DeleteSelectionEvent, Selection};
+use crate::MainCamera;
+
+/// Copy, cut and paste the selection through the system clipboard, as SVG
+/// carrying the items, see [`Board::to_clipboard`]
+pub(crate) struct ClipboardPlugin;
+
+impl Plugin for ClipboardPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Clipboard>()
+            .add_event::<CopySelectionEvent>()
+            .add_event::<CutSelectionEvent>()
+            .add_event::<PasteEvent>()
+            .add_systems(Update, handle_copy_selection_event)
+            .add_systems(Update, handle_paste_event)
+            .add_systems(Update, paste);
+    }
+}
+
+#[derive(Resource)]
+struct Clipboard {
+    /// `None` if the system clipboard is not available
+    #[cfg(not(target_arch = "wasm32"))]
+    system: Mutex<Option<arboard::Clipboard>>,
+    /// Text read from the clipboard, which arrives later on the web
+    pasted: Mutex<Receiver<String>>,
+    pasted_tx: Sender<String>,
+}
+
+impl Default for Clipboard {
+    fn default() -> Self {
+        let (pasted_tx, pasted) = std::sync::mpsc::channel();
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let system = arboard::Clipboard::new()
+            .map_err(|e| log::info!("clipboard not available: {e}"))
+            .ok();
+
+        Self {
+            #[cfg(not(target_arch = "wasm32"))]
+            system: Mutex::new(system),
+            pasted: Mutex::new(pasted),
+            pasted_tx,
+        }
+    }
+}
+
+impl Clipboard {
+    fn pasted(&mut self) -> Vec<String> {
+        self.pasted
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .try_iter()
+            .collect()
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Clipboard {
+    fn write(&mut self, text: String) {
+        let system = self
+            .system
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some(system) = system {
+            if let Err(e) = system.set_text(text) {
+                log::error!("cannot copy: {e}");
+            }
+        }
+    }
+
+    fn request_paste(&mut self) {
+        let system = self
+            .system
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner);
+        match system.as_mut().map(arboard::Clipboard::get_text) {
+            Some(Ok(text)) => {
+                let _ = self.pasted_tx.send(text);
+            }
+            Some(Err(e)) => log::info!("cannot paste: {e}"),
+            None => {}
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Clipboard {
+    #[allow(clippy::unused_self)]
+    fn write(&mut self, text: String) {
+        wasm_bindgen_futures::spawn_local(async move {
+            let promise = web_clipboard::write_text(&text);
+            if let Err(e) = wasm_bindgen_futures::JsFuture::from(promise).await {
+                log::error!("cannot copy: {e:?}");
+            }
+        });
+    }
+
+    fn request_paste(&mut self) {
+        let pasted_tx = self.pasted_tx.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            let promise = web_clipboard::read_text();
+            match wasm_bindgen_futures::JsFuture::from(promise).await {
+                Ok(text) => {
+                    if let Some(text) = text.as_string() {
+                        let _ = pasted_tx.send(text);
+                    }
+                }
+                Err(e) => log::info!("cannot paste: {e:?}"),
+            }
+        });
+    }
+}
+
+/// The async clipboard API, not available in `web-sys` without unstable APIs
+#[cfg(target_arch = "wasm32")]
+mod web_clipboard {
+    use wasm_bindgen::prelude::wasm_bindgen;
+
+    #[wasm_bindgen]
+    extern "C" {
+        #[wasm_bindgen(js_namespace = ["navigator", "clipboard"], js_name = writeText)]
+        pub(super) fn write_text(text: &str) -> js_sys::Promise;
+
+        #[wasm_bindgen(js_namespace = ["navigator", "clipboard"], js_name = readText)]
+        pub(super) fn read_text() -> js_sys::Promise;
+    }
+}
+
+#[derive(Event)]
+pub(crate) struct CopySelectionEvent;
+
+#[derive(Event)]
+pub(crate) struct CutSelectionEvent;
+
+#[derive(Event)]
+pub(crate) struct PasteEvent;
+
+fn handle_copy_selection_event(
+    mut copy_events: EventReader<CopySelectionEvent>,
+    mut cut_events: EventReader<CutSelectionEvent>,
+    selection: Res<Selection>,
+    item_q: Query<(&Item, &Drawn)>,
+    mut clipboard: ResMut<Clipboard>,
+    mut delete_selection_event: EventWriter<DeleteSelectionEvent>,
+) {
+    let copy = copy_events.read().count() > 0;
+    let cut = cut_events.read().count() > 0;
+
+    if !(copy || cut) || selection.items().is_empty() {
+        return;
+    }
+
+    let mut board = Board::default();
+
+    let selected = item_q
+        .iter()
+        .filter(|(item, _)| selection.items().contains(&item.id));
+
+    for (item, drawn) in selected {
+        let (id, placement) = (item.id, item.placement);
+        match drawn {
+            Drawn::Line(line) => board.lines.push(Line {
+                id,
+                placement,
+                ..Line::from(line)
+            }),
+            Drawn::Shape(shape) => board.shapes.push(Shape {
+                id,
+                placement,
+                ..Shape::from(shape)
+            }),
+            Drawn::Label(label) => board.labels.push(Label {
+                id,
+                placement,
+                ..Label::from(label)
+            }),
+        }
+    }
+
+    clipboard.write(board.to_clipboard());
+
+    if cut {
+        delete_selection_event.send(DeleteSelectionEvent);
+    }
+}
+
+fn handle_paste_event(mut events: EventReader<PasteEvent>, mut clipboard: ResMut<Clipboard>) {
+    if events.read().count() > 0 {
+        clipboard.request_paste();
+    }
+}
+
+/// Pasted items are centered on the cursor, on the active layer of the page
+/// shown, and selected
+#[allow(clippy::too_many_arguments)]
+fn paste(
+    mut clipboard: ResMut<Clipboard>,
+    window_q: Query<&Window>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    mut ids: ResMut<ItemIds>,
+    layers: Res<Layers>,
+    pages: Res<Pages>,
+    mut selection: ResMut<Selection>,
+    mut add_line_event: EventWriter<AddLineEvent>,
+    mut add_shape_event: EventWriter<AddShapeEvent>,
+    mut add_label_event: EventWriter<AddLabelEvent>,
+) {
+    for text in clipboard.pasted() {
+        let Some(mut board) = Board::from_clipboard(&text) else {
+            log::info!("nothing to paste");
+            continue;
+        };
+
+        let Some(((x0, y0), (x1, y1))) = board.bounds() else {
+            continue;
+        };
+
+        let center = Vec2::new(x0 + x1, y0 + y1) / 2.0;
+
+        let cursor = window_q
+            .get_single()
+            .ok()
+            .and_then(Window::cursor_position)
+            .and_then(|position| {
+                let (camera, camera_transform) = camera_q.get_single().ok()?;
+                cursor_to_world_position(position, camera, camera_transform)
+            });
+
+        // Without a cursor, e.g. outside of the window, items are pasted in place
+        let offset = cursor.map_or(Vec2::ZERO, |cursor| cursor - center);
+
+        board.transform(&Placement {
+            translation: offset.into(),
+            ..default()
+        });
+        board.reassign_ids(|| ids.allocate());
+        board.move_to_layer(layers.active());
+        board.move_to_page(pages.current());
+        selection.select(board.ids());
+
+        for line in &board.lines {
+            add_line_event.send(AddLineEvent::new(line.into()));
+        }
+        for shape in &board.shapes {
+            add_shape_event.send(AddShapeEvent::new(shape.into()));
+        }
+        for label in &board.labels {
+            add_label_event.send(AddLabelEvent::new(label.into()));
+        }
+    }
+}
@@ -2,16 +2,25 @@
 #![allow(clippy::cast_possible_truncation)]
 #![allow(clippy::module_name_repetitions)]
 
-use crate::drawing::{make_chalk, ClearEvent};
-use crate::{Chalk, Stats};
+use crate::background::SetBackgroundEvent;
+use crate::drawing::{
+    make_chalk, AddLabelEvent, AddLineEvent, AddShapeEvent, ClearEvent, DeleteItemsEvent,
+    DiscardStrokeEvent, DrawnLabel, DrawnLine, DrawnShape, DuplicateItemsEvent, FillItemsEvent,
+    ItemIds, RemoveChalkEvent, TransformItemsEvent,
+};
+use crate::relay::{self, RelaySocket};
+use crate::{Chalk, PeerStats, Pressure, Stats, Traffic};
 use bevy::prelude::*;
-use bevy::utils::{Duration, HashMap, Instant};
+use bevy::utils::{Duration, HashMap, HashSet, Instant};
 use bevy_matchbox::prelude::*;
 use bevy_prototype_lyon::prelude::{GeometryBuilder, ShapeBundle, Stroke};
 use bevy_prototype_lyon::shapes;
-use serde::{Deserialize, Serialize};
+use lavagna_collab::{AddressedEvent, CollabId, Event, ItemId, Label, Line, MoveEvent, Shape};
 
+use crate::layers::SetLayersEvent;
 use crate::local_chalk::LocalChalk;
+use crate::pages::SetPagesEvent;
+use crate::theme::Theme;
 
 pub(crate) struct CollabPlugin {
     opt: CollabPluginOpt,
@@ -31,25 +40,39 @@ impl CollabPlugin {
 
 impl Plugin for CollabPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
-        let socket = MatchboxSocket::new_reliable(&self.opt.url);
         let collab_id = CollabId(self.opt.collab_id);
-        let room = Room::new(socket, collab_id);
+        let room = Room::new(&self.opt.url, collab_id);
         app.insert_resource(room);
+        // Items drawn here must have ids no other peer uses
+        app.insert_resource(ItemIds::new(collab_id));
 
         app.add_systems(Update, room_system);
+        app.add_systems(Update, ping_system);
         app.add_systems(Update, emit_events);
         app.add_systems(Update, receive_events);
         app.add_systems(Update, handle_clear_event);
+        app.add_systems(Update, handle_add_line_event);
+        app.add_systems(Update, handle_add_shape_event);
+        app.add_systems(Update, handle_add_label_event);
+        app.add_systems(Update, handle_transform_items_event);
+        app.add_systems(Update, handle_delete_items_event);
+        app.add_systems(Update, handle_duplicate_items_event);
+        app.add_systems(Update, handle_fill_items_event);
+        app.add_systems(Update, handle_set_layers_event);
+        app.add_systems(Update, handle_set_background_event);
+        app.add_systems(Update, handle_set_pages_event);
+        app.add_systems(Update, handle_disconnected_peers);
         app.add_systems(Update, update_peer_cursor_visibility);
+        app.add_systems(Update, update_peer_cursor_color);
         app.add_systems(Update, update_stats);
     }
 }
 
 fn emit_events(chalk: ResMut<LocalChalk>, mut room: ResMut<Room>) {
-    let chalk = chalk.get();
+    let chalk = chalk.freehand();
 
     if chalk.updated {
-        room.send(Event::Move(chalk.into()));
+        room.send(Event::Move((&chalk).into()));
     }
 
     if chalk.just_released {
@@ -57,12 +80,24 @@ fn emit_events(chalk: ResMut<LocalChalk>, mut room: ResMut<Room>) {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn receive_events(
     mut commands: Commands,
     mut room: ResMut<Room>,
     mut chalk_q: Query<&mut Chalk>,
-    mut cursor_q: Query<(&mut Transform, &mut Stroke, &mut PeerCursor), With<PeerCursor>>,
+    mut cursor_q: Query<(&mut Transform, &mut PeerCursor)>,
     mut clear_event: EventWriter<ClearEvent>,
+    mut add_line_event: EventWriter<AddLineEvent>,
+    mut add_shape_event: EventWriter<AddShapeEvent>,
+    mut discard_stroke_event: EventWriter<DiscardStrokeEvent>,
+    mut add_label_event: EventWriter<AddLabelEvent>,
+    mut transform_items_event: EventWriter<TransformItemsEvent>,
+    mut delete_items_event: EventWriter<DeleteItemsEvent>,
+    mut duplicate_items_event: EventWriter<DuplicateItemsEvent>,
+    mut fill_items_event: EventWriter<FillItemsEvent>,
+    mut set_layers_event: EventWriter<SetLayersEvent>,
+    mut set_background_event: EventWriter<SetBackgroundEvent>,
+    mut set_pages_event: EventWriter<SetPagesEvent>,
 ) {
     // This is needed, otherwise it can hangs forever when the connection is not established
     if !room.is_ok() {
@@ -71,7 +106,7 @@ fn receive_events(
 
     let my_id = room.collab_id;
 
-    for &AddressedEvent { src, event } in room.receive().iter().filter(|e| e.src != my_id) {
+    for AddressedEvent { src, event } in room.receive().into_iter().filter(|e| e.src != my_id) {
         match event {
             Event::Move(e) => handle_draw(
                 &mut commands,
@@ -85,6 +120,56 @@ fn receive_events(
             Event::Clear => {
                 clear_event.send(ClearEvent::local_only());
             }
+            Event::Line(line) => {
+                let n = room.count_received_item();
+                add_line_event.send(AddLineEvent::received((&line).into(), n));
+            }
+            Event::Shape(shape) => {
+                let n = room.count_received_item();
+                add_shape_event.send(AddShapeEvent::received((&shape).into(), n));
+            }
+            Event::Recognized(shape) => {
+                if let Some(peer) = room.peers.0.get(&src) {
+                    discard_stroke_event.send(DiscardStrokeEvent(peer.chalk));
+                }
+                let n = room.count_received_item();
+                add_shape_event.send(AddShapeEvent::received((&shape).into(), n));
+            }
+            Event::Label(label) => {
+                let n = room.count_received_item();
+                add_label_event.send(AddLabelEvent::received((&label).into(), n));
+            }
+            Event::Transform { items, placement } => {
+                transform_items_event.send(TransformItemsEvent::local_only(items, placement));
+            }
+            Event::Delete(items) => {
+                delete_items_event.send(DeleteItemsEvent::local_only(items));
+            }
+            Event::Duplicate {
+                items,
+                first,
+                placement,
+            } => {
+                let first = ItemId { src, n: first };
+                duplicate_items_event
+                    .send(DuplicateItemsEvent::local_only(items, first, placement));
+            }
+            Event::Fill { items, color } => {
+                let fill = color.map(color_from_u32);
+                fill_items_event.send(FillItemsEvent::local_only(items, fill));
+            }
+            Event::Layers(layers) => {
+                set_layers_event.send(SetLayersEvent::local_only(layers));
+            }
+            Event::Background(background) => {
+                set_background_event.send(SetBackgroundEvent::local_only(background));
+            }
+            Event::Pages { pages, current } => {
+                set_pages_event.send(SetPagesEvent::local_only(pages, current));
+            }
+            Event::Ping(nonce) => room.send(Event::Pong { dst: src, nonce }),
+            Event::Pong { dst, nonce } if dst == my_id => room.handle_pong(src, nonce),
+            Event::Pong { .. } => {}
         }
     }
 }
@@ -104,28 +189,42 @@ fn handle_draw(
     event: &MoveEvent,
     room: &mut Room,
     chalk_q: &mut Query<&mut Chalk>,
-    cursor_q: &mut Query<(&mut Transform, &mut Stroke, &mut PeerCursor), With<PeerCursor>>,
+    cursor_q: &mut Query<(&mut Transform, &mut PeerCursor)>,
 ) {
+    let mut remote_chalk = Chalk::from(event);
+    remote_chalk.stroke.src = src;
+
     let peer: &Peer = room.peers.0.entry(src).or_insert_with(|| {
         let peer_cursor = make_peer_cursor(color_from_u32(event.color), src);
         let cursor_id = commands.spawn(peer_cursor).id();
-        let chalk_id = commands.spawn(make_chalk(event.into())).id();
+        let chalk_id = commands.spawn(make_chalk(remote_chalk)).id();
 
         Peer::new(chalk_id, cursor_id)
     });
 
     if let Ok(mut chalk) = chalk_q.get_mut(peer.chalk) {
-        *chalk = event.into();
+        *chalk = remote_chalk;
     }
 
-    if let Ok((mut t, mut stroke, mut peer_cursor)) = cursor_q.get_mut(peer.cursor) {
+    if let Ok((mut t, mut peer_cursor)) = cursor_q.get_mut(peer.cursor) {
         t.translation.x = event.x.into();
         t.translation.y = event.y.into();
-        stroke.color = color_from_u32(event.color).into();
+        peer_cursor.color = color_from_u32(event.color);
         peer_cursor.touch();
     }
 }
 
+fn update_peer_cursor_color(
+    theme: Res<Theme>,
+    mut cursor_q: Query<(Ref<PeerCursor>, &mut Stroke)>,
+) {
+    for (peer_cursor, mut stroke) in &mut cursor_q {
+        if theme.is_changed() || peer_cursor.is_changed() {
+            stroke.color = theme.ink(peer_cursor.color).into();
+        }
+    }
+}
+
 fn update_peer_cursor_visibility(
     mut cursor_q: Query<(&mut Visibility, &PeerCursor), With<PeerCursor>>,
 ) {
@@ -146,6 +245,13 @@ impl From<&Chalk> for MoveEvent {
             y: chalk.y as i16,
             line_width: chalk.line_width as u8,
             pressed: chalk.pressed,
+            pressure: pressure_to_u8(chalk.pressure),
+            stroke: chalk.stroke.n,
+            laser: chalk.laser,
+            style: chalk.style,
+            layer: chalk.layer,
+            page: chalk.page,
+            scale: chalk.scale,
         }
     }
 }
@@ -160,10 +266,148 @@ impl From<&MoveEvent> for Chalk {
             color: color_from_u32(event.color),
             line_width: event.line_width.into(),
             just_released: false,
+            pressure: pressure_from_u8(event.pressure),
+            // The sender is not known here
+            stroke: ItemId {
+                n: event.stroke,
+                ..default()
+            },
+            laser: event.laser,
+            style: event.style,
+            layer: event.layer,
+            page: event.page,
+            scale: event.scale,
+        }
+    }
+}
+
+impl From<&Line> for DrawnLine {
+    fn from(line: &Line) -> Self {
+        Self {
+            points: line
+                .points
+                .iter()
+                .map(|&(x, y)| Vec2::new(x.into(), y.into()))
+                .collect(),
+            color: color_from_u32(line.color),
+            line_width: line.line_width.into(),
+            pressures: line
+                .pressures
+                .iter()
+                .copied()
+                .map(pressure_from_u8)
+                .collect(),
+            style: line.style,
+            fill: line.fill.map(color_from_u32),
+            id: line.id,
+            placement: line.placement,
+            layer: line.layer,
+            page: line.page,
+        }
+    }
+}
+
+impl From<&DrawnLine> for Line {
+    fn from(line: &DrawnLine) -> Self {
+        Self {
+            color: color_to_u32(line.color),
+            line_width: line.line_width as u8,
+            points: line
+                .points
+                .iter()
+                .map(|p| (p.x as i16, p.y as i16))
+                .collect(),
+            pressures: line.pressures.iter().copied().map(pressure_to_u8).collect(),
+            style: line.style,
+            fill: line.fill.map(color_to_u32),
+            id: line.id,
+            placement: line.placement,
+            layer: line.layer,
+            page: line.page,
+        }
+    }
+}
+
+impl From<&Shape> for DrawnShape {
+    fn from(shape: &Shape) -> Self {
+        let point = |(x, y): (i16, i16)| Vec2::new(x.into(), y.into());
+        Self {
+            kind: shape.kind,
+            from: point(shape.from),
+            to: point(shape.to),
+            color: color_from_u32(shape.color),
+            line_width: shape.line_width.into(),
+            vertices: shape.vertices.iter().copied().map(point).collect(),
+            style: shape.style,
+            fill: shape.fill.map(color_from_u32),
+            id: shape.id,
+            placement: shape.placement,
+            layer: shape.layer,
+            page: shape.page,
         }
     }
 }
 
+impl From<&DrawnShape> for Shape {
+    fn from(shape: &DrawnShape) -> Self {
+        let point = |p: Vec2| (p.x as i16, p.y as i16);
+        Self {
+            kind: shape.kind,
+            color: color_to_u32(shape.color),
+            line_width: shape.line_width as u8,
+            from: point(shape.from),
+            to: point(shape.to),
+            vertices: shape.vertices.iter().copied().map(point).collect(),
+            style: shape.style,
+            fill: shape.fill.map(color_to_u32),
+            id: shape.id,
+            placement: shape.placement,
+            layer: shape.layer,
+            page: shape.page,
+        }
+    }
+}
+
+impl From<&Label> for DrawnLabel {
+    fn from(label: &Label) -> Self {
+        Self {
+            content: label.content.clone(),
+            position: Vec2::new(label.position.0.into(), label.position.1.into()),
+            size: label.size.into(),
+            color: color_from_u32(label.color),
+            id: label.id,
+            placement: label.placement,
+            layer: label.layer,
+            page: label.page,
+        }
+    }
+}
+
+#[allow(clippy::cast_sign_loss)]
+impl From<&DrawnLabel> for Label {
+    fn from(label: &DrawnLabel) -> Self {
+        Self {
+            content: label.content.clone(),
+            color: color_to_u32(label.color),
+            size: label.size as u8,
+            position: (label.position.x as i16, label.position.y as i16),
+            id: label.id,
+            placement: label.placement,
+            layer: label.layer,
+            page: label.page,
+        }
+    }
+}
+
+fn pressure_from_u8(pressure: u8) -> Pressure {
+    Pressure(f32::from(pressure) / f32::from(u8::MAX))
+}
+
+#[allow(clippy::cast_sign_loss)]
+fn pressure_to_u8(pressure: Pressure) -> u8 {
+    (pressure.0 * f32::from(u8::MAX)).round() as u8
+}
+
 #[allow(clippy::many_single_char_names)]
 fn color_from_u32(n: u32) -> Srgba {
     let r = ((n) & 0xFF) as u8;
@@ -196,90 +440,349 @@ impl Peer {
     }
 }
 
+/// How long to wait for WebRTC connections before falling back to the relay
+const RELAY_FALLBACK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often to measure the round trip time, and to update the traffic rates
+const STATS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Peers answer pings every `STATS_INTERVAL`, if nothing is received for this
+/// long the peer is gone. Needed with the relay, which does not tell who left.
+const PEER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Counts the traffic of the current interval
+#[derive(Default)]
+struct TrafficCounter {
+    bytes: usize,
+    messages: usize,
+    /// Rate computed at the end of the previous interval
+    rate: Traffic,
+}
+
+impl TrafficCounter {
+    fn count(&mut self, bytes: usize) {
+        self.bytes += bytes;
+        self.messages += 1;
+    }
+
+    #[allow(clippy::cast_precision_loss, clippy::cast_sign_loss)]
+    fn update_rate(&mut self, elapsed: Duration) {
+        let per_sec = |n: usize| (n as f32 / elapsed.as_secs_f32()).round() as usize;
+        self.rate = Traffic {
+            bytes_per_sec: per_sec(self.bytes),
+            messages_per_sec: per_sec(self.messages),
+        };
+        self.bytes = 0;
+        self.messages = 0;
+    }
+}
+
+#[derive(Default)]
+struct NetStats {
+    sent: TrafficCounter,
+    received: TrafficCounter,
+    decode_failures: usize,
+    dropped: usize,
+    round_trips: HashMap<CollabId, Duration>,
+    /// Nonce of the last ping, and when it was sent
+    last_ping: Option<(u32, Instant)>,
+}
+
 #[derive(Resource)]
 struct Room {
-    socket: MatchboxSocket<SingleChannel>,
+    /// `None` when using the relay
+    socket: Option<MatchboxSocket<SingleChannel>>,
+    relay: Option<RelaySocket>,
     collab_id: CollabId,
     peers: Peers,
+    /// Since when some peers are in the room but not connected via WebRTC
+    unreachable_since: Option<Instant>,
+    stats: NetStats,
+    /// Collab id of each WebRTC peer, known after its first event
+    collab_ids: HashMap<PeerId, CollabId>,
+    /// When each online peer has been heard of the last time
+    last_seen: HashMap<CollabId, Instant>,
+    offline: HashSet<CollabId>,
+    /// Peers gone offline, whose chalk and cursor must be removed
+    disconnected: Vec<CollabId>,
+    /// Items received from the keeper
+    replayed: HashSet<ItemId>,
+    /// Items received so far, which are stacked in that order
+    received_items: u64,
 }
 
 impl Room {
-    fn new(socket: MatchboxSocket<SingleChannel>, collab_id: CollabId) -> Self {
+    fn new(url: &str, collab_id: CollabId) -> Self {
+        let (socket, relay) = if let Some(relay_url) = relay::forced_relay_url(url) {
+            (None, Some(RelaySocket::new(&relay_url, true)))
+        } else {
+            // Keep the relay ready, in case WebRTC does not work
+            let relay = relay::relay_url(url).map(|url| RelaySocket::new(&url, false));
+            (Some(MatchboxSocket::new_reliable(url)), relay)
+        };
+
         Self {
             socket,
+            relay,
             collab_id,
             peers: Peers::default(),
+            unreachable_since: None,
+            stats: NetStats::default(),
+            collab_ids: HashMap::default(),
+            last_seen: HashMap::default(),
+            offline: HashSet::default(),
+            disconnected: Vec::new(),
+            replayed: HashSet::default(),
+            received_items: 0,
         }
     }
 
+    /// Number of the item just received
+    fn count_received_item(&mut self) -> u64 {
+        self.received_items += 1;
+        self.received_items
+    }
+
     fn send(&mut self, event: Event) {
         let event = AddressedEvent {
             src: self.collab_id,
             event,
         };
-        let peers: Vec<_> = self.socket.connected_peers().collect();
-        let mut payload = Vec::new();
-        ciborium::ser::into_writer(&event, &mut payload).unwrap();
-        for peer in peers {
-            self.socket.send(payload.clone().into(), peer);
+        let payload = event.encode();
+
+        if let Some(socket) = &mut self.socket {
+            let peers: Vec<_> = socket.connected_peers().collect();
+            for peer in peers {
+                self.stats.sent.count(payload.len());
+                if socket.try_send(payload.clone().into(), peer).is_err() {
+                    self.stats.dropped += 1;
+                }
+            }
+        } else if let Some(relay) = &self.relay {
+            self.stats.sent.count(payload.len());
+            if !relay.send(payload) {
+                self.stats.dropped += 1;
+            }
         }
     }
 
     fn receive(&mut self) -> Vec<AddressedEvent> {
-        self.socket
-            .receive()
+        let payloads: Vec<(Option<PeerId>, Vec<u8>)> = if let Some(socket) = &mut self.socket {
+            socket
+                .receive()
+                .into_iter()
+                .map(|(peer, payload)| (Some(peer), payload.into()))
+                .collect()
+        } else if let Some(relay) = &mut self.relay {
+            relay.receive().into_iter().map(|p| (None, p)).collect()
+        } else {
+            Vec::new()
+        };
+
+        let mut events = Vec::with_capacity(payloads.len());
+
+        for (peer, payload) in &payloads {
+            self.stats.received.count(payload.len());
+
+            let Some(event) = AddressedEvent::decode(payload) else {
+                self.stats.decode_failures += 1;
+                continue;
+            };
+
+            if self.is_replayed_again(&event) {
+                continue;
+            }
+
+            if event.src != self.collab_id {
+                if let Some(peer) = peer {
+                    self.collab_ids.insert(*peer, event.src);
+                }
+                self.last_seen.insert(event.src, Instant::now());
+                self.offline.remove(&event.src);
+            }
+
+            events.push(event);
+        }
+
+        events
+    }
+
+    /// The keeper sends the whole board over WebRTC and again over the relay,
+    /// after switching to it. Items already received are on the board.
+    fn is_replayed_again(&mut self, event: &AddressedEvent) -> bool {
+        if event.src != CollabId::KEEPER {
+            return false;
+        }
+
+        let id = match &event.event {
+            Event::Line(line) => line.id,
+            Event::Shape(shape) => shape.id,
+            Event::Label(label) => label.id,
+            _ => return false,
+        };
+
+        !self.replayed.insert(id)
+    }
+
+    fn set_offline(&mut self, id: CollabId) {
+        info!("peer {id:?} is offline");
+        self.last_seen.remove(&id);
+        self.stats.round_trips.remove(&id);
+        self.offline.insert(id);
+        self.disconnected.push(id);
+    }
+
+    fn handle_webrtc_disconnection(&mut self, peer: PeerId) {
+        if let Some(id) = self.collab_ids.remove(&peer) {
+            self.set_offline(id);
+        }
+    }
+
+    fn check_peer_timeouts(&mut self) {
+        let timed_out: Vec<_> = self
+            .last_seen
             .iter()
-            .map(|(_, payload)| payload)
-            .filter_map(|payload| ciborium::de::from_reader(&payload[..]).ok())
-            .collect()
+            .filter(|(_, last_seen)| last_seen.elapsed() > PEER_TIMEOUT)
+            .map(|(&id, _)| id)
+            .collect();
+
+        for id in timed_out {
+            self.set_offline(id);
+        }
+    }
+
+    fn handle_pong(&mut self, src: CollabId, nonce: u32) {
+        if let Some((last_nonce, sent_at)) = self.stats.last_ping {
+            if nonce == last_nonce {
+                self.stats.round_trips.insert(src, sent_at.elapsed());
+            }
+        }
+    }
+
+    fn connected_peers(&self) -> usize {
+        if let Some(socket) = &self.socket {
+            socket.connected_peers().count()
+        } else {
+            // Do not count ourselves
+            self.relay
+                .as_ref()
+                .and_then(RelaySocket::status)
+                .map_or(0, |status| status.relayed.saturating_sub(1))
+        }
     }
 
     fn is_ok(&self) -> bool {
-        self.socket.connected_peers().count() > 0
+        self.connected_peers() > 0
     }
-}
 
-#[derive(Debug, Serialize, Deserialize, Copy, Clone)]
-enum Event {
-    Move(MoveEvent),
-    Release,
-    Clear,
-}
+    /// Switch to the relay when someone is already using it, or when WebRTC
+    /// cannot connect to the peers in the room
+    fn check_relay_fallback(&mut self) {
+        let (Some(socket), Some(relay)) = (&self.socket, &mut self.relay) else {
+            return;
+        };
 
-#[derive(Debug, Serialize, Deserialize, Copy, Clone)]
-struct MoveEvent {
-    color: u32,
-    line_width: u8,
-    x: i16,
-    y: i16,
-    pressed: bool,
-}
+        relay.update();
 
-#[derive(Debug, Serialize, Deserialize, Copy, Clone)]
-struct AddressedEvent {
-    src: CollabId,
-    event: Event,
-}
+        let Some(status) = relay.status() else {
+            return;
+        };
 
-#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Hash)]
-struct CollabId(u16);
+        // Do not count ourselves, nor the keeper: the server does not count
+        // it either. It is known once it has sent something, at least a pong.
+        let others = status.peers.saturating_sub(1);
+        let connected = socket
+            .connected_peers()
+            .filter(|peer| self.collab_ids.get(peer) != Some(&CollabId::KEEPER))
+            .count();
+        let unreachable = connected < others;
+
+        if !unreachable {
+            self.unreachable_since = None;
+        }
 
-impl From<u16> for CollabId {
-    fn from(value: u16) -> Self {
-        Self(value)
+        let timeout = unreachable
+            && self
+                .unreachable_since
+                .get_or_insert_with(Instant::now)
+                .elapsed()
+                > RELAY_FALLBACK_TIMEOUT;
+
+        if status.relayed > 0 || timeout {
+            info!("switching to relay");
+            relay.activate();
+            // Leave the WebRTC room, everything goes through the relay now
+            self.socket = None;
+        }
     }
 }
 
 // regularly call update_peers to update the list of connected peers
 fn room_system(mut room: ResMut<Room>) {
-    let Ok(peers) = room.socket.try_update_peers() else {
+    room.check_relay_fallback();
+    room.check_peer_timeouts();
+
+    let Some(socket) = &mut room.socket else {
+        return;
+    };
+
+    let Ok(peers) = socket.try_update_peers() else {
         log::error!("failed to update peers");
         return;
     };
     for (peer, new_state) in peers {
         match new_state {
             PeerState::Connected => info!("peer {peer:?} connected"),
-            PeerState::Disconnected => info!("peer {peer:?} disconnected"),
+            PeerState::Disconnected => {
+                info!("peer {peer:?} disconnected");
+                room.handle_webrtc_disconnection(peer);
+            }
+        }
+    }
+}
+
+/// Ping the peers and update the traffic rates, every `STATS_INTERVAL`
+fn ping_system(mut room: ResMut<Room>) {
+    let elapsed = room
+        .stats
+        .last_ping
+        .map_or(STATS_INTERVAL, |(_, sent_at)| sent_at.elapsed());
+
+    if elapsed < STATS_INTERVAL {
+        return;
+    }
+
+    let nonce = room
+        .stats
+        .last_ping
+        .map_or(0, |(nonce, _)| nonce.wrapping_add(1));
+
+    room.stats.sent.update_rate(elapsed);
+    room.stats.received.update_rate(elapsed);
+    room.stats.last_ping = Some((nonce, Instant::now()));
+
+    if room.is_ok() {
+        room.send(Event::Ping(nonce));
+    }
+}
+
+/// Keep what disconnected peers were drawing, but remove their cursors
+fn handle_disconnected_peers(
+    mut commands: Commands,
+    mut room: ResMut<Room>,
+    mut remove_chalk_event: EventWriter<RemoveChalkEvent>,
+) {
+    let disconnected = std::mem::take(&mut room.disconnected);
+
+    for id in disconnected {
+        let Some(peer) = room.peers.0.remove(&id) else {
+            continue;
+        };
+
+        remove_chalk_event.send(RemoveChalkEvent(peer.chalk));
+
+        if let Some(mut cursor) = commands.get_entity(peer.cursor) {
+            cursor.despawn();
         }
     }
 }
@@ -292,9 +795,116 @@ fn handle_clear_event(mut events: EventReader<ClearEvent>, mut room: ResMut<Room
     }
 }
 
+fn handle_add_line_event(mut events: EventReader<AddLineEvent>, mut room: ResMut<Room>) {
+    for event in events.read().filter(|e| e.must_be_forwarded()) {
+        room.send(Event::Line((&event.line).into()));
+    }
+}
+
+fn handle_add_shape_event(mut events: EventReader<AddShapeEvent>, mut room: ResMut<Room>) {
+    for event in events.read().filter(|e| e.must_be_forwarded()) {
+        let shape = (&event.shape).into();
+        if event.replaces_stroke() {
+            room.send(Event::Recognized(shape));
+        } else {
+            room.send(Event::Shape(shape));
+        }
+    }
+}
+
+fn handle_add_label_event(mut events: EventReader<AddLabelEvent>, mut room: ResMut<Room>) {
+    for event in events.read().filter(|e| e.must_be_forwarded()) {
+        room.send(Event::Label((&event.label).into()));
+    }
+}
+
+fn handle_transform_items_event(
+    mut events: EventReader<TransformItemsEvent>,
+    mut room: ResMut<Room>,
+) {
+    for event in events.read().filter(|e| e.must_be_forwarded()) {
+        room.send(Event::Transform {
+            items: event.items.clone(),
+            placement: event.placement,
+        });
+    }
+}
+
+fn handle_delete_items_event(mut events: EventReader<DeleteItemsEvent>, mut room: ResMut<Room>) {
+    for event in events.read().filter(|e| e.must_be_forwarded()) {
+        room.send(Event::Delete(event.items.clone()));
+    }
+}
+
+fn handle_duplicate_items_event(
+    mut events: EventReader<DuplicateItemsEvent>,
+    mut room: ResMut<Room>,
+) {
+    for event in events.read().filter(|e| e.must_be_forwarded()) {
+        room.send(Event::Duplicate {
+            items: event.items.clone(),
+            first: event.first.n,
+            placement: event.placement,
+        });
+    }
+}
+
+fn handle_fill_items_event(mut events: EventReader<FillItemsEvent>, mut room: ResMut<Room>) {
+    for event in events.read().filter(|e| e.must_be_forwarded()) {
+        room.send(Event::Fill {
+            items: event.items.clone(),
+            color: event.fill.map(color_to_u32),
+        });
+    }
+}
+
+fn handle_set_layers_event(mut events: EventReader<SetLayersEvent>, mut room: ResMut<Room>) {
+    for event in events.read().filter(|e| e.must_be_forwarded()) {
+        room.send(Event::Layers(event.layers.clone()));
+    }
+}
+
+fn handle_set_background_event(
+    mut events: EventReader<SetBackgroundEvent>,
+    mut room: ResMut<Room>,
+) {
+    for event in events.read().filter(|e| e.must_be_forwarded()) {
+        room.send(Event::Background(event.background));
+    }
+}
+
+fn handle_set_pages_event(mut events: EventReader<SetPagesEvent>, mut room: ResMut<Room>) {
+    for event in events.read().filter(|e| e.must_be_forwarded()) {
+        room.send(Event::Pages {
+            pages: event.pages.clone(),
+            current: event.current,
+        });
+    }
+}
+
 fn update_stats(room: Res<Room>, mut stats: ResMut<Stats>) {
     stats.collab.active = true;
-    stats.collab.peers = room.socket.connected_peers().count();
+    stats.collab.peers = room.connected_peers();
+    stats.collab.relay = room.socket.is_none();
+
+    let online = room.last_seen.keys().map(|id| (id, true));
+    let offline = room.offline.iter().map(|id| (id, false));
+
+    let mut peer_stats: Vec<_> = online
+        .chain(offline)
+        .map(|(id, online)| PeerStats {
+            id: id.0,
+            round_trip: room.stats.round_trips.get(id).copied(),
+            online,
+        })
+        .collect();
+    peer_stats.sort_unstable_by_key(|peer| peer.id);
+
+    stats.collab.peer_stats = peer_stats;
+    stats.collab.sent = room.stats.sent.rate;
+    stats.collab.received = room.stats.received.rate;
+    stats.collab.decode_failures = room.stats.decode_failures;
+    stats.collab.dropped = room.stats.dropped;
 }
 
 #[derive(Component)]
@@ -302,15 +912,18 @@ struct PeerCursor {
     #[allow(dead_code)]
     id: CollabId,
     last_seen: Instant,
+    /// Ink of the peer, shown with the local theme
+    color: Srgba,
 }
 
 impl PeerCursor {
-    fn new(id: CollabId) -> Self {
+    fn new(id: CollabId, color: Srgba) -> Self {
         log::info!("new peer cursor {:?}", id);
 
         Self {
             id,
             last_seen: Instant::now(),
+            color,
         }
     }
 
@@ -342,7 +955,7 @@ fn make_peer_cursor(color: Srgba, id: CollabId) -> (ShapeBundle, Stroke, PeerCur
     };
 
     let stroke = Stroke::new(color, 10.0);
-    let peer_cursor = PeerCursor::new(id);
+    let peer_cursor = PeerCursor::new(id, color);
 
     (shape, stroke, peer_cursor)
 }
@@ -4,8 +4,11 @@ use bevy::prelude::*;
 
 use bevy::diagnostic::{Diagnostic, DiagnosticsStore, FrameTimeDiagnosticsPlugin};
 
+use std::fmt::Write;
+
 use crate::local_chalk::LocalChalk;
-use crate::Stats;
+use crate::theme::Theme;
+use crate::{CollabStats, Stats};
 
 pub(crate) struct DebugPlugin;
 
@@ -44,8 +47,13 @@ fn update(
     mut text: Query<&mut Text, With<DebugText>>,
     chalk: Res<LocalChalk>,
     stats: Res<Stats>,
+    theme: Res<Theme>,
 ) {
     let mut text = text.single_mut();
+
+    if theme.is_changed() {
+        text.sections[0].style.color = theme.ui_text().into();
+    }
     let chalk = chalk.get();
 
     let fps = diagnostics
@@ -65,7 +73,7 @@ fn update(
         format!("{x:+05}:{y:+05} {pressed}")
     };
 
-    let collab = { format!("{:?}", stats.collab) };
+    let collab = collab_text(&stats.collab);
 
     let text_value = &mut text.sections[0].value;
 
@@ -73,7 +81,44 @@ fn update(
         r#"{fps}
 {frame_time}
 {chalk}
-{collab}
-"#
+{collab}"#
+    );
+}
+
+fn collab_text(collab: &CollabStats) -> String {
+    if !collab.active {
+        return "collab off\n".to_owned();
+    }
+
+    let mut text = format!(
+        "{} peers{}\n",
+        collab.peers,
+        if collab.relay { " (relay)" } else { "" }
     );
+
+    for peer in &collab.peer_stats {
+        let id = peer.id;
+        let _ = match peer.round_trip {
+            _ if !peer.online => writeln!(text, "  {id:05} offline"),
+            Some(rtt) => writeln!(text, "  {id:05} {:.0} ms", rtt.as_secs_f32() * 1000.0),
+            None => writeln!(text, "  {id:05} -- ms"),
+        };
+    }
+
+    let traffic = [("tx", collab.sent), ("rx", collab.received)];
+    for (label, traffic) in traffic {
+        let _ = writeln!(
+            text,
+            "{label} {} B/s {} msg/s",
+            traffic.bytes_per_sec, traffic.messages_per_sec
+        );
+    }
+
+    let _ = writeln!(
+        text,
+        "decode failures {} dropped {}",
+        collab.decode_failures, collab.dropped
+    );
+
+    text
 }
@@ -1,34 +1,96 @@
 #![allow(clippy::needless_pass_by_value)]
 #![allow(clippy::cast_precision_loss)]
 
-use crate::Chalk;
-use bevy::{prelude::*, render::view::NoFrustumCulling};
+use crate::layers::Layers;
+use crate::pages::Pages;
+use crate::theme::Theme;
+use crate::ui::default_font;
+use crate::{Chalk, Pressure};
+use bevy::{prelude::*, render::view::NoFrustumCulling, sprite::Anchor};
+use lavagna_collab::{
+    simplify, simplify_tolerance, CollabId, ItemId, Placement, ShapeKind, StrokeStyle,
+};
 
+use bevy_prototype_lyon::prelude::tess::path::{iterator::PathIterator, PathEvent};
 use bevy_prototype_lyon::prelude::*;
 
 const POINTS_CHUNK_THRESHOLD: usize = 100;
 
-pub(crate) struct DrawingPlugin;
+/// Width of the lightest stroke, relative to the line width
+const MIN_PRESSURE_WIDTH: f32 = 0.2;
+
+/// Curves are split in segments this close to them, to be dashed
+const DASH_TOLERANCE: f32 = 0.1;
+
+/// Completed items are stacked between 0 and this
+const MAX_ITEM_Z: f32 = 900.0;
+
+/// While drawing, strokes are at maximum before the cursor plane
+pub(crate) const PENDING_Z: f32 = 998.0;
+
+pub(crate) struct DrawingPlugin {
+    smoothing: Smoothing,
+}
+
+impl DrawingPlugin {
+    pub(crate) fn new(smoothing: Smoothing) -> Self {
+        Self { smoothing }
+    }
+}
 
 impl Plugin for DrawingPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
         app.add_plugins(ShapePlugin)
+            .insert_resource(self.smoothing)
+            .init_resource::<ItemIds>()
+            .init_resource::<Stack>()
             .add_event::<ClearEvent>()
+            .add_event::<AddLineEvent>()
+            .add_event::<RemoveChalkEvent>()
+            .add_event::<AddShapeEvent>()
+            .add_event::<DiscardStrokeEvent>()
+            .add_event::<AddLabelEvent>()
+            .add_event::<TransformItemsEvent>()
+            .add_event::<DeleteItemsEvent>()
+            .add_event::<DuplicateItemsEvent>()
+            .add_event::<FillItemsEvent>()
             .add_systems(Update, handle_clear_event)
+            .add_systems(Update, handle_add_item_events)
+            .add_systems(Update, handle_remove_chalk_event)
+            .add_systems(Update, handle_discard_stroke_event)
+            .add_systems(Update, handle_transform_items_event)
+            .add_systems(Update, handle_delete_items_event)
+            .add_systems(Update, handle_duplicate_items_event)
+            .add_systems(Update, handle_fill_items_event)
+            .add_systems(Update, draw_fills.after(handle_fill_items_event))
+            .add_systems(Update, recolor.before(draw_fills))
+            .add_systems(Update, place_items)
+            .add_systems(Update, restack.after(draw_fills))
+            .add_systems(Update, init_polyline_smoothing)
             .add_systems(Update, update);
     }
 }
 
+/// What a chalk is drawing
+type PendingStroke<'a> = (
+    &'a mut Chalk,
+    &'a mut Path,
+    &'a mut Stroke,
+    &'a mut Fill,
+    &'a mut Polyline,
+    &'a mut Transform,
+);
+
 fn update(
     mut commands: Commands,
-    mut chalk_q: Query<(&mut Chalk, &mut Path, &mut Stroke, &mut Polyline), With<Pending>>,
-    time: Res<Time>,
+    mut chalk_q: Query<PendingStroke, With<Pending>>,
+    mut stack: ResMut<Stack>,
+    theme: Res<Theme>,
 ) {
-    for (chalk, mut path, mut stroke, mut polyline) in &mut chalk_q {
+    for (chalk, mut path, mut stroke, mut fill, mut polyline, mut transform) in &mut chalk_q {
         let updated = chalk.pressed && chalk.updated;
 
-        stroke.color = chalk.color.into();
-        stroke.options.line_width = chalk.line_width as f32;
+        polyline.style = chalk.style;
 
         if updated {
             add_point(&mut polyline, &chalk);
@@ -38,48 +100,348 @@ fn update(
         let just_released = chalk.just_released && !polyline.points.is_empty();
         let completed = just_released || chunk_completed;
 
+        if chunk_completed {
+            let points = polyline.points.clone();
+            polyline.previous.extend(points);
+        }
+
         if completed {
-            complete_pending_path(&mut polyline, &mut commands, &chalk, &time);
+            complete_pending_path(&mut polyline, &mut commands, &chalk, &mut stack, *theme);
 
+            // The rest of the stroke goes on in a new chunk
             if chunk_completed {
                 add_point(&mut polyline, &chalk);
+            } else {
+                polyline.previous.clear();
             }
         }
 
         // Regenerate mesh from list of points
         *path = Path::from(&*polyline);
+        (*stroke, *fill) = paint(&polyline, theme.ink(chalk.color), chalk.line_width);
+        transform.translation.z = stack.pending_z(chalk.color);
     }
 }
 
+/// Styled strokes ignore the pen pressure, they are dashed along the middle
 fn add_point(polyline: &mut Polyline, chalk: &Chalk) {
     let new_point = Vec2::new(chalk.x as f32, chalk.y as f32);
+    let width = if chalk.style.is_plain() {
+        pressure_width(chalk.line_width, chalk.pressure)
+    } else {
+        chalk.line_width as f32
+    };
     polyline.points.push(new_point);
+    polyline.widths.push(width);
 }
 
-fn z_from_time(time: &Time) -> f32 {
-    const MAX_Z: f32 = 500.0;
-    const MAX_TIME: f32 = 10_000.0;
+fn pressure_width(line_width: u32, pressure: Pressure) -> f32 {
+    line_width as f32 * (MIN_PRESSURE_WIDTH + (1.0 - MIN_PRESSURE_WIDTH) * pressure.0)
+}
+
+/// Strokes with constant width are stroked, the others are filled outlines
+fn paint(polyline: &Polyline, color: Srgba, line_width: u32) -> (Stroke, Fill) {
+    if polyline.has_constant_width() {
+        let width = polyline
+            .widths
+            .first()
+            .copied()
+            .unwrap_or(line_width as f32);
+        (
+            styled_stroke(color, width, polyline.style),
+            Fill::color(Color::NONE),
+        )
+    } else {
+        // The outline overlaps itself on sharp turns
+        let fill = Fill {
+            options: FillOptions::non_zero(),
+            color: color.into(),
+        };
+        (Stroke::new(Color::NONE, 1.0), fill)
+    }
+}
+
+/// Round caps make dots out of dashes of zero length
+pub(crate) fn styled_stroke(color: Srgba, width: f32, style: StrokeStyle) -> Stroke {
+    if style.is_plain() {
+        return Stroke::new(color, width);
+    }
+
+    Stroke {
+        color: color.into(),
+        options: StrokeOptions::default()
+            .with_line_width(width)
+            .with_line_cap(LineCap::Round)
+            .with_line_join(LineJoin::Round),
+    }
+}
+
+/// Dash `path` and add arrowheads at the ends of the line through `points`
+fn styled_path(path: Path, style: StrokeStyle, line_width: f32, points: &[Vec2]) -> Path {
+    if style.is_plain() {
+        return path;
+    }
+
+    // Solid lines are a single endless dash
+    let (dash, gap) = style
+        .dash_pattern(line_width)
+        .unwrap_or((f32::INFINITY, 0.0));
 
-    let t = time.elapsed_seconds();
-    let step = MAX_Z / MAX_TIME;
-    t * step
+    let mut dasher = Dasher {
+        path_builder: PathBuilder::new(),
+        dash,
+        gap,
+        drawing: true,
+        left: dash,
+    };
+
+    let vec2 = |p: tess::math::Point| Vec2::new(p.x, p.y);
+
+    for event in path.0.iter().flattened(DASH_TOLERANCE) {
+        match event {
+            PathEvent::Begin { at } => dasher.begin(vec2(at)),
+            PathEvent::Line { from, to } => dasher.segment(vec2(from), vec2(to)),
+            PathEvent::End {
+                last,
+                first,
+                close: true,
+            } => dasher.segment(vec2(last), vec2(first)),
+            _ => {}
+        }
+    }
+
+    let mut path_builder = dasher.path_builder;
+    let points: Vec<_> = points.iter().map(|&p| p.into()).collect();
+
+    for head in style.arrow_heads(&points, line_width) {
+        let [side, tip, other_side] = head.map(Vec2::from);
+        path_builder.move_to(side);
+        path_builder.line_to(tip);
+        path_builder.line_to(other_side);
+    }
+
+    path_builder.build()
+}
+
+/// Walks along straight segments, alternating dashes and gaps
+struct Dasher {
+    path_builder: PathBuilder,
+    dash: f32,
+    gap: f32,
+    drawing: bool,
+    /// Length left of the current dash or gap
+    left: f32,
+}
+
+impl Dasher {
+    /// Every subpath starts with a dash
+    fn begin(&mut self, at: Vec2) {
+        self.drawing = true;
+        self.left = self.dash;
+        self.path_builder.move_to(at);
+    }
+
+    fn segment(&mut self, from: Vec2, to: Vec2) {
+        let length = from.distance(to);
+        let mut done = 0.0;
+
+        loop {
+            let step = self.left.min(length - done);
+            done += step;
+            self.left -= step;
+
+            let p = from.lerp(to, done / length.max(f32::EPSILON));
+
+            if self.drawing {
+                self.path_builder.line_to(p);
+            }
+
+            if self.left > 0.0 {
+                break;
+            }
+
+            self.drawing = !self.drawing;
+            if self.drawing {
+                self.left = self.dash;
+                self.path_builder.move_to(p);
+            } else {
+                self.left = self.gap;
+            }
+        }
+    }
+}
+
+/// In each layer, translucent ink, like highlighter strokes, goes under the
+/// opaque ink
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Tier {
+    Highlight,
+    Ink,
+}
+
+impl Tier {
+    fn of(color: Srgba) -> Self {
+        if color.alpha < 1.0 {
+            Self::Highlight
+        } else {
+            Self::Ink
+        }
+    }
+}
+
+/// Completed items are stacked by layer, then by tier, then in the order they
+/// have been added. Their z is recomputed from their rank, so the stack never
+/// runs out of room however many items are added.
+#[derive(Debug, Resource)]
+struct Stack {
+    /// Stacking index of the next item
+    next: u64,
+    /// Distance between consecutive items
+    step: f32,
+    /// Where translucent strokes are drawn, over the translucent ink of the
+    /// active layer and under its opaque ink
+    highlight_z: f32,
+}
+
+impl Default for Stack {
+    fn default() -> Self {
+        Self {
+            next: 0,
+            step: MAX_ITEM_Z,
+            highlight_z: 0.0,
+        }
+    }
+}
+
+impl Stack {
+    /// A new item, on top of its layer and tier
+    fn item(
+        &mut self,
+        id: ItemId,
+        layer: ItemId,
+        page: ItemId,
+        placement: Placement,
+        origin: Vec2,
+    ) -> Item {
+        let index = self.next;
+        self.next += 1;
+        Item {
+            id,
+            layer,
+            page,
+            index,
+            placement,
+            preview: Placement::default(),
+            origin,
+        }
+    }
+
+    fn pending_z(&self, color: Srgba) -> f32 {
+        match Tier::of(color) {
+            Tier::Highlight => self.highlight_z,
+            Tier::Ink => PENDING_Z,
+        }
+    }
+}
+
+/// Items are added on top until restacked
+fn restack(
+    mut stack: ResMut<Stack>,
+    layers: Res<Layers>,
+    added_q: Query<(), Added<Item>>,
+    mut item_q: Query<(&Item, &Drawn, &mut Transform, Option<&Children>)>,
+    mut fill_q: Query<&mut Transform, (With<ItemFill>, Without<Item>)>,
+) {
+    if added_q.is_empty() && !layers.is_changed() {
+        return;
+    }
+
+    let mut items: Vec<_> = item_q
+        .iter_mut()
+        .map(|(item, drawn, transform, children)| {
+            let key = (layers.rank(item.layer), Tier::of(drawn.color()), item.index);
+            (key, transform, children)
+        })
+        .collect();
+    items.sort_unstable_by_key(|(key, ..)| *key);
+
+    let step = MAX_ITEM_Z / (items.len() + 1) as f32;
+    let active = (layers.rank(layers.active()), Tier::Ink, 0);
+
+    stack.step = step;
+    stack.highlight_z = step / 2.0;
+
+    for (i, (key, mut transform, children)) in items.into_iter().enumerate() {
+        let z = step * (i + 1) as f32;
+
+        if key < active {
+            stack.highlight_z = z + step / 2.0;
+        }
+
+        transform.translation.z = z;
+
+        for &child in children.iter().flat_map(|children| children.iter()) {
+            if let Ok(mut transform) = fill_q.get_mut(child) {
+                transform.translation.z = -step / 2.0;
+            }
+        }
+    }
 }
 
 fn complete_pending_path(
     polyline: &mut Polyline,
     commands: &mut Commands,
     chalk: &Chalk,
-    time: &Time,
+    stack: &mut Stack,
+    theme: Theme,
 ) {
-    let path = Path::from(&*polyline);
+    let tolerance = simplify_tolerance(chalk.line_width as f32, chalk.scale);
 
-    let z = z_from_time(time);
+    let points: Vec<_> = polyline
+        .points
+        .iter()
+        .copied()
+        .zip(polyline.widths.iter().copied())
+        .collect();
 
-    let transform = Transform {
-        translation: Vec3::new(0., 0., z),
-        ..default()
+    let (points, widths) = simplify(&points, tolerance, |(p, _)| (p.x, p.y))
+        .into_iter()
+        .unzip();
+
+    let simplified = Polyline {
+        points,
+        widths,
+        smoothing: polyline.smoothing,
+        previous: Vec::new(),
+        style: polyline.style,
     };
 
+    spawn_completed_line(
+        commands,
+        DrawnLine::new(&simplified, chalk),
+        polyline.smoothing,
+        stack,
+        theme,
+    );
+
+    polyline.points.clear();
+    polyline.widths.clear();
+}
+
+fn spawn_completed_line(
+    commands: &mut Commands,
+    line: DrawnLine,
+    smoothing: Smoothing,
+    stack: &mut Stack,
+    theme: Theme,
+) {
+    let polyline = Polyline::from_line(&line, smoothing);
+    let path = Path::from(&polyline);
+    let (stroke, fill) = paint(&polyline, theme.ink(line.color), line.line_width);
+
+    let item = stack.item(line.id, line.layer, line.page, line.placement, Vec2::ZERO);
+    let transform = item.transform(MAX_ITEM_Z);
+
     // The line is complete, we spawn a fresh mesh, which will persist
     commands.spawn((
         ShapeBundle {
@@ -87,21 +449,20 @@ fn complete_pending_path(
             spatial: transform.into(),
             ..default()
         },
-        Stroke::new(chalk.color, chalk.line_width as f32),
-        Fill::color(Color::NONE),
+        stroke,
+        fill,
         Completed,
+        item,
+        Drawn::Line(line),
     ));
-
-    polyline.points.clear();
 }
 
 pub(crate) fn make_chalk(chalk: Chalk) -> impl Bundle {
     // An empty path
     let path = PathBuilder::new().build();
 
-    // z-index at maximum before cursor plane
     let transform = Transform {
-        translation: Vec3::new(0., 0., 998.0),
+        translation: Vec3::new(0., 0., PENDING_Z),
         ..default()
     };
 
@@ -121,37 +482,234 @@ pub(crate) fn make_chalk(chalk: Chalk) -> impl Bundle {
 }
 
 #[derive(Debug, Component, Default)]
-struct Completed;
+pub(crate) struct Completed;
+
+/// Hands out ids for the items drawn here
+#[derive(Debug, Resource, Default)]
+pub(crate) struct ItemIds {
+    src: CollabId,
+    last: u32,
+}
+
+impl ItemIds {
+    pub(crate) fn new(src: CollabId) -> Self {
+        Self { src, last: 0 }
+    }
+
+    pub(crate) fn allocate(&mut self) -> ItemId {
+        self.reserve(1)
+    }
+
+    /// The first of `count` consecutive ids
+    #[allow(clippy::cast_possible_truncation)]
+    pub(crate) fn reserve(&mut self, count: usize) -> ItemId {
+        let first = ItemId {
+            src: self.src,
+            n: self.last + 1,
+        };
+        self.last += count as u32;
+        first
+    }
+}
+
+/// What a completed item is, e.g. to copy it to the clipboard. Its current id
+/// and placement are the ones of its `Item`.
+#[derive(Debug, Component, Clone)]
+pub(crate) enum Drawn {
+    Line(DrawnLine),
+    Shape(DrawnShape),
+    Label(DrawnLabel),
+}
+
+impl Drawn {
+    fn color(&self) -> Srgba {
+        match self {
+            Self::Line(line) => line.color,
+            Self::Shape(shape) => shape.color,
+            Self::Label(label) => label.color,
+        }
+    }
+}
+
+/// A completed line, shape or label, which can be selected and moved. Long
+/// strokes are split in chunks sharing the same id.
+#[derive(Debug, Component, Clone, Copy)]
+pub(crate) struct Item {
+    pub(crate) id: ItemId,
+    pub(crate) layer: ItemId,
+    pub(crate) page: ItemId,
+    /// Position in the stack, see `Stack`
+    index: u64,
+    pub(crate) placement: Placement,
+    /// Applied after `placement` while the selection is being dragged
+    pub(crate) preview: Placement,
+    /// Where the item is, before being placed, e.g. the start of a label
+    origin: Vec2,
+}
+
+impl Item {
+    fn transform(&self, z: f32) -> Transform {
+        let placement = self.placement.then(&self.preview);
+        let (x, y) = placement.apply(self.origin.into());
+        Transform {
+            translation: Vec3::new(x, y, z),
+            rotation: Quat::from_rotation_z(placement.rotation),
+            scale: Vec3::new(placement.scale, placement.scale, 1.0),
+        }
+    }
+}
+
+fn place_items(mut item_q: Query<(&Item, &mut Transform), Changed<Item>>) {
+    for (item, mut transform) in &mut item_q {
+        *transform = item.transform(transform.translation.z);
+    }
+}
 
 #[derive(Debug, Component, Default)]
 pub(crate) struct Pending;
 
+/// How much strokes are smoothed, from 0 (straight segments between sampled
+/// points) to 1 (Catmull-Rom spline through the sampled points)
+#[derive(Debug, Clone, Copy, Resource, PartialEq)]
+pub struct Smoothing(f32);
+
+impl Smoothing {
+    #[must_use]
+    pub fn new(level: f32) -> Self {
+        Self(level.clamp(0.0, 1.0))
+    }
+}
+
+impl Default for Smoothing {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
 #[derive(Debug, Clone, Component, Default)]
 pub(crate) struct Polyline {
     points: Vec<Vec2>,
+    /// Width of each point, depending on the pen pressure
+    widths: Vec<f32>,
+    smoothing: Smoothing,
+    /// Points of the chunks of the same stroke which have been completed
+    /// already, to recognize the whole stroke
+    previous: Vec<Vec2>,
+    style: StrokeStyle,
+}
+
+impl Polyline {
+    fn from_line(line: &DrawnLine, smoothing: Smoothing) -> Self {
+        let widths = if line.pressures.len() == line.points.len() && line.style.is_plain() {
+            line.pressures
+                .iter()
+                .map(|&pressure| pressure_width(line.line_width, pressure))
+                .collect()
+        } else {
+            vec![line.line_width as f32; line.points.len()]
+        };
+
+        Self {
+            points: line.points.clone(),
+            widths,
+            smoothing,
+            previous: Vec::new(),
+            style: line.style,
+        }
+    }
+
+    /// The points of the whole stroke, including the completed chunks
+    pub(crate) fn stroke(&self) -> Vec<Vec2> {
+        [self.previous.as_slice(), &self.points].concat()
+    }
+
+    fn has_constant_width(&self) -> bool {
+        self.widths.windows(2).all(|w| (w[0] - w[1]).abs() < 0.01)
+    }
+
+    /// Left and right sides of a variable width stroke
+    fn outline(&self) -> (Vec<Vec2>, Vec<Vec2>) {
+        let last = self.points.len().saturating_sub(1);
+
+        self.points
+            .iter()
+            .zip(&self.widths)
+            .enumerate()
+            .map(|(i, (&p, &width))| {
+                let direction = self.points[(i + 1).min(last)] - self.points[i.saturating_sub(1)];
+                let offset = direction.normalize_or_zero().perp() * width / 2.0;
+                (p + offset, p - offset)
+            })
+            .unzip()
+    }
+}
+
+/// Chalks are spawned without access to resources, smoothing is set here
+fn init_polyline_smoothing(
+    mut polyline_q: Query<&mut Polyline, Added<Polyline>>,
+    smoothing: Res<Smoothing>,
+) {
+    for mut polyline in &mut polyline_q {
+        polyline.smoothing = *smoothing;
+    }
 }
 
 impl From<&Polyline> for Path {
     fn from(polyline: &Polyline) -> Self {
         let mut path_builder = PathBuilder::new();
 
-        let mut iter = polyline.points.iter();
+        let Some(&first) = polyline.points.first() else {
+            return path_builder.build();
+        };
 
-        if let Some(first) = iter.next() {
-            path_builder.move_to(*first);
-        }
+        let smoothing = polyline.smoothing;
 
-        for point in iter {
-            path_builder.line_to(*point);
-        }
+        if polyline.has_constant_width() {
+            path_builder.move_to(first);
+            spline_to(&mut path_builder, &polyline.points, smoothing);
 
-        path_builder.build()
+            let width = polyline.widths[0];
+            styled_path(
+                path_builder.build(),
+                polyline.style,
+                width,
+                &polyline.points,
+            )
+        } else {
+            let (left, mut right) = polyline.outline();
+            right.reverse();
+
+            path_builder.move_to(left[0]);
+            spline_to(&mut path_builder, &left, smoothing);
+            path_builder.line_to(right[0]);
+            spline_to(&mut path_builder, &right, smoothing);
+            path_builder.close();
+
+            path_builder.build()
+        }
     }
 }
 
-fn despawn_all_completed_lines(commands: &mut Commands, lines: &Query<Entity, With<Completed>>) {
-    for line in lines.iter() {
-        commands.entity(line).despawn();
+/// Catmull-Rom spline from the first point, where the path currently is, to
+/// the last one, as cubic Bézier curves. Endpoints are repeated, so the curve
+/// passes through every point.
+fn spline_to(path_builder: &mut PathBuilder, points: &[Vec2], smoothing: Smoothing) {
+    let k = smoothing.0 / 6.0;
+    let last = points.len().saturating_sub(1);
+
+    for i in 1..=last {
+        let p0 = points[i.saturating_sub(2)];
+        let p1 = points[i - 1];
+        let p2 = points[i];
+        let p3 = points[(i + 1).min(last)];
+
+        if k > 0.0 {
+            let ctrl1 = p1 + (p2 - p0) * k;
+            let ctrl2 = p2 - (p3 - p1) * k;
+            path_builder.cubic_bezier_to(ctrl1, ctrl2, p2);
+        } else {
+            path_builder.line_to(p2);
+        }
     }
 }
 
@@ -170,13 +728,776 @@ impl ClearEvent {
     }
 }
 
+/// Only the page shown is cleared, items on hidden or locked layers are kept
 fn handle_clear_event(
     mut events: EventReader<ClearEvent>,
-    lines: Query<Entity, With<Completed>>,
+    pages: Res<Pages>,
+    layers: Res<Layers>,
+    item_q: Query<(Entity, &Item), With<Completed>>,
     mut commands: Commands,
 ) {
     let clear = events.read().count() > 0;
-    if clear {
-        despawn_all_completed_lines(&mut commands, &lines);
+    if !clear {
+        return;
+    }
+
+    for (entity, item) in &item_q {
+        if pages.is_shown(item.page) && layers.is_editable(item.layer) {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+/// A complete freehand line
+#[derive(Debug, Clone)]
+pub(crate) struct DrawnLine {
+    pub(crate) points: Vec<Vec2>,
+    pub(crate) color: Srgba,
+    pub(crate) line_width: u32,
+    /// Pressure of each point, empty when always at full pressure
+    pub(crate) pressures: Vec<Pressure>,
+    pub(crate) style: StrokeStyle,
+    pub(crate) fill: Option<Srgba>,
+    pub(crate) id: ItemId,
+    pub(crate) layer: ItemId,
+    pub(crate) page: ItemId,
+    pub(crate) placement: Placement,
+}
+
+impl DrawnLine {
+    fn new(polyline: &Polyline, chalk: &Chalk) -> Self {
+        let pressures = if polyline.has_constant_width() {
+            Vec::new()
+        } else {
+            polyline
+                .widths
+                .iter()
+                .map(|&width| width_pressure(chalk.line_width, width))
+                .collect()
+        };
+
+        Self {
+            points: polyline.points.clone(),
+            color: chalk.color,
+            line_width: chalk.line_width,
+            pressures,
+            style: chalk.style,
+            fill: None,
+            id: chalk.stroke,
+            layer: chalk.layer,
+            page: chalk.page,
+            placement: Placement::default(),
+        }
+    }
+}
+
+/// Inverse of `pressure_width`
+fn width_pressure(line_width: u32, width: f32) -> Pressure {
+    let relative = width / (line_width as f32).max(1.0);
+    Pressure(((relative - MIN_PRESSURE_WIDTH) / (1.0 - MIN_PRESSURE_WIDTH)).clamp(0.0, 1.0))
+}
+
+/// A complete line, e.g. drawn by a peer before joining the room, or pasted
+#[derive(Event)]
+pub(crate) struct AddLineEvent {
+    pub(crate) line: DrawnLine,
+    forward: bool,
+    received: Option<u64>,
+}
+
+impl AddLineEvent {
+    pub(crate) fn new(line: DrawnLine) -> Self {
+        Self {
+            line,
+            forward: true,
+            received: None,
+        }
+    }
+    pub(crate) fn local_only(line: DrawnLine) -> Self {
+        Self {
+            line,
+            forward: false,
+            received: None,
+        }
+    }
+    /// Received from a peer, as the `n`th item, see `handle_add_item_events`
+    pub(crate) fn received(line: DrawnLine, n: u64) -> Self {
+        Self {
+            received: Some(n),
+            ..Self::local_only(line)
+        }
+    }
+    pub(crate) fn must_be_forwarded(&self) -> bool {
+        self.forward
+    }
+}
+
+/// An item added by one of the events below
+enum NewItem<'a> {
+    Line(&'a DrawnLine),
+    Shape(&'a DrawnShape),
+    Label(&'a DrawnLabel),
+}
+
+/// Items of every kind are added by the same system, because items received
+/// together, e.g. when joining a room, are stacked in the order they were
+/// received. Items added locally go first.
+#[allow(clippy::too_many_arguments)]
+fn handle_add_item_events(
+    mut line_events: EventReader<AddLineEvent>,
+    mut shape_events: EventReader<AddShapeEvent>,
+    mut label_events: EventReader<AddLabelEvent>,
+    mut commands: Commands,
+    mut stack: ResMut<Stack>,
+    smoothing: Res<Smoothing>,
+    asset_server: Res<AssetServer>,
+    theme: Res<Theme>,
+) {
+    let lines = line_events
+        .read()
+        .map(|event| (event.received, NewItem::Line(&event.line)));
+    let shapes = shape_events
+        .read()
+        .map(|event| (event.received, NewItem::Shape(&event.shape)));
+    let labels = label_events
+        .read()
+        .map(|event| (event.received, NewItem::Label(&event.label)));
+
+    let mut added: Vec<_> = lines.chain(shapes).chain(labels).collect();
+    added.sort_by_key(|(received, _)| *received);
+
+    for (_, item) in added {
+        match item {
+            NewItem::Line(line) => {
+                spawn_completed_line(&mut commands, line.clone(), *smoothing, &mut stack, *theme);
+            }
+            NewItem::Shape(shape) => spawn_shape(&mut commands, shape, &mut stack, *theme),
+            NewItem::Label(label) => {
+                let font = default_font(&asset_server);
+                spawn_label(&mut commands, label, font, &mut stack, *theme);
+            }
+        }
+    }
+}
+
+/// A chalk is not used anymore, e.g. because its peer disconnected. What it
+/// was drawing is kept as a completed line.
+#[derive(Event)]
+pub(crate) struct RemoveChalkEvent(pub(crate) Entity);
+
+fn handle_remove_chalk_event(
+    mut events: EventReader<RemoveChalkEvent>,
+    mut commands: Commands,
+    chalk_q: Query<(&Chalk, &Polyline), With<Pending>>,
+    mut stack: ResMut<Stack>,
+    theme: Res<Theme>,
+) {
+    for RemoveChalkEvent(entity) in events.read() {
+        if let Ok((chalk, polyline)) = chalk_q.get(*entity) {
+            if !polyline.points.is_empty() {
+                spawn_completed_line(
+                    &mut commands,
+                    DrawnLine::new(polyline, chalk),
+                    polyline.smoothing,
+                    &mut stack,
+                    *theme,
+                );
+            }
+        }
+
+        if let Some(mut entity) = commands.get_entity(*entity) {
+            entity.despawn();
+        }
+    }
+}
+
+/// A shape drawn with one of the shape tools, or recognized from a stroke
+#[derive(Debug, Clone)]
+pub(crate) struct DrawnShape {
+    pub(crate) kind: ShapeKind,
+    pub(crate) from: Vec2,
+    pub(crate) to: Vec2,
+    pub(crate) color: Srgba,
+    pub(crate) line_width: u32,
+    /// Only for `ShapeKind::Polygon`
+    pub(crate) vertices: Vec<Vec2>,
+    pub(crate) style: StrokeStyle,
+    /// Only for closed shapes
+    pub(crate) fill: Option<Srgba>,
+    pub(crate) id: ItemId,
+    pub(crate) layer: ItemId,
+    pub(crate) page: ItemId,
+    pub(crate) placement: Placement,
+}
+
+impl DrawnShape {
+    pub(crate) fn stroke(&self, theme: Theme) -> Stroke {
+        let style = self.kind.stroke_style(self.style);
+        styled_stroke(theme.ink(self.color), self.line_width as f32, style)
+    }
+
+    /// The shape, without dashes and arrowheads
+    fn geometry(&self) -> Path {
+        let (from, to) = (self.from, self.to);
+        let center = (from + to) / 2.0;
+        let size = (to - from).abs();
+
+        match self.kind {
+            ShapeKind::Line | ShapeKind::Arrow => {
+                GeometryBuilder::build_as(&shapes::Line(from, to))
+            }
+            ShapeKind::Rectangle => GeometryBuilder::build_as(&shapes::Rectangle {
+                extents: size,
+                origin: RectangleOrigin::CustomCenter(center),
+            }),
+            ShapeKind::Ellipse => GeometryBuilder::build_as(&shapes::Ellipse {
+                radii: size / 2.0,
+                center,
+            }),
+            ShapeKind::Polygon => GeometryBuilder::build_as(&shapes::Polygon {
+                points: self.vertices.clone(),
+                closed: true,
+            }),
+        }
+    }
+}
+
+impl From<&DrawnShape> for Path {
+    fn from(shape: &DrawnShape) -> Self {
+        let style = shape.kind.stroke_style(shape.style);
+        let ends = [shape.from, shape.to];
+        styled_path(shape.geometry(), style, shape.line_width as f32, &ends)
+    }
+}
+
+#[derive(Event)]
+pub(crate) struct AddShapeEvent {
+    pub(crate) shape: DrawnShape,
+    forward: bool,
+    replaces_stroke: bool,
+    received: Option<u64>,
+}
+
+impl AddShapeEvent {
+    pub(crate) fn new(shape: DrawnShape) -> Self {
+        Self {
+            shape,
+            forward: true,
+            replaces_stroke: false,
+            received: None,
+        }
+    }
+    pub(crate) fn local_only(shape: DrawnShape) -> Self {
+        Self {
+            shape,
+            forward: false,
+            replaces_stroke: false,
+            received: None,
+        }
+    }
+    /// Received from a peer, as the `n`th item, see `handle_add_item_events`
+    pub(crate) fn received(shape: DrawnShape, n: u64) -> Self {
+        Self {
+            received: Some(n),
+            ..Self::local_only(shape)
+        }
+    }
+    /// The shape has been recognized from the stroke being drawn
+    pub(crate) fn replacing_stroke(shape: DrawnShape) -> Self {
+        Self {
+            shape,
+            forward: true,
+            replaces_stroke: true,
+            received: None,
+        }
+    }
+    pub(crate) fn must_be_forwarded(&self) -> bool {
+        self.forward
+    }
+    pub(crate) fn replaces_stroke(&self) -> bool {
+        self.replaces_stroke
+    }
+}
+
+fn spawn_shape(commands: &mut Commands, shape: &DrawnShape, stack: &mut Stack, theme: Theme) {
+    let item = stack.item(
+        shape.id,
+        shape.layer,
+        shape.page,
+        shape.placement,
+        Vec2::ZERO,
+    );
+    let transform = item.transform(MAX_ITEM_Z);
+
+    commands.spawn((
+        ShapeBundle {
+            path: shape.into(),
+            spatial: transform.into(),
+            ..default()
+        },
+        shape.stroke(theme),
+        Fill::color(Color::NONE),
+        Completed,
+        item,
+        Drawn::Shape(shape.clone()),
+    ));
+}
+
+/// Throw away what a chalk is drawing, without completing it, along with
+/// the chunks of the same stroke completed already
+#[derive(Event)]
+pub(crate) struct DiscardStrokeEvent(pub(crate) Entity);
+
+fn handle_discard_stroke_event(
+    mut events: EventReader<DiscardStrokeEvent>,
+    mut commands: Commands,
+    mut polyline_q: Query<(&mut Polyline, &Chalk), With<Pending>>,
+    item_q: Query<(Entity, &Item), With<Completed>>,
+) {
+    for DiscardStrokeEvent(entity) in events.read() {
+        let Ok((mut polyline, chalk)) = polyline_q.get_mut(*entity) else {
+            continue;
+        };
+
+        if !polyline.previous.is_empty() {
+            for (entity, _) in item_q.iter().filter(|(_, item)| item.id == chalk.stroke) {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+
+        polyline.points.clear();
+        polyline.widths.clear();
+        polyline.previous.clear();
+    }
+}
+
+/// Some text typed with the text tool
+#[derive(Debug, Clone)]
+pub(crate) struct DrawnLabel {
+    pub(crate) content: String,
+    pub(crate) position: Vec2,
+    pub(crate) size: f32,
+    pub(crate) color: Srgba,
+    pub(crate) id: ItemId,
+    pub(crate) layer: ItemId,
+    pub(crate) page: ItemId,
+    pub(crate) placement: Placement,
+}
+
+pub(crate) fn label_bundle(
+    label: &DrawnLabel,
+    font: Handle<Font>,
+    z: f32,
+    theme: Theme,
+) -> Text2dBundle {
+    Text2dBundle {
+        text: Text::from_section(
+            label.content.clone(),
+            TextStyle {
+                font,
+                font_size: label.size,
+                color: theme.ink(label.color).into(),
+            },
+        ),
+        text_anchor: Anchor::CenterLeft,
+        transform: Transform::from_translation(label.position.extend(z)),
+        ..default()
+    }
+}
+
+#[derive(Event)]
+pub(crate) struct AddLabelEvent {
+    pub(crate) label: DrawnLabel,
+    forward: bool,
+    received: Option<u64>,
+}
+
+impl AddLabelEvent {
+    pub(crate) fn new(label: DrawnLabel) -> Self {
+        Self {
+            label,
+            forward: true,
+            received: None,
+        }
+    }
+    pub(crate) fn local_only(label: DrawnLabel) -> Self {
+        Self {
+            label,
+            forward: false,
+            received: None,
+        }
+    }
+    /// Received from a peer, as the `n`th item, see `handle_add_item_events`
+    pub(crate) fn received(label: DrawnLabel, n: u64) -> Self {
+        Self {
+            received: Some(n),
+            ..Self::local_only(label)
+        }
+    }
+    pub(crate) fn must_be_forwarded(&self) -> bool {
+        self.forward
+    }
+}
+
+fn spawn_label(
+    commands: &mut Commands,
+    label: &DrawnLabel,
+    font: Handle<Font>,
+    stack: &mut Stack,
+    theme: Theme,
+) {
+    let item = stack.item(
+        label.id,
+        label.layer,
+        label.page,
+        label.placement,
+        label.position,
+    );
+    let z = MAX_ITEM_Z;
+    let bundle = Text2dBundle {
+        transform: item.transform(z),
+        ..label_bundle(label, font, z, theme)
+    };
+    commands.spawn((bundle, Completed, item, Drawn::Label(label.clone())));
+}
+
+/// Move, scale and rotate some items
+#[derive(Event)]
+pub(crate) struct TransformItemsEvent {
+    pub(crate) items: Vec<ItemId>,
+    pub(crate) placement: Placement,
+    forward: bool,
+}
+
+impl TransformItemsEvent {
+    pub(crate) fn new(items: Vec<ItemId>, placement: Placement) -> Self {
+        Self {
+            items,
+            placement,
+            forward: true,
+        }
+    }
+    pub(crate) fn local_only(items: Vec<ItemId>, placement: Placement) -> Self {
+        Self {
+            items,
+            placement,
+            forward: false,
+        }
+    }
+    pub(crate) fn must_be_forwarded(&self) -> bool {
+        self.forward
+    }
+}
+
+fn handle_transform_items_event(
+    mut events: EventReader<TransformItemsEvent>,
+    mut item_q: Query<&mut Item>,
+) {
+    for event in events.read() {
+        for mut item in &mut item_q {
+            if event.items.contains(&item.id) {
+                item.placement = item.placement.then(&event.placement);
+            }
+        }
+    }
+}
+
+#[derive(Event)]
+pub(crate) struct DeleteItemsEvent {
+    pub(crate) items: Vec<ItemId>,
+    forward: bool,
+}
+
+impl DeleteItemsEvent {
+    pub(crate) fn new(items: Vec<ItemId>) -> Self {
+        Self {
+            items,
+            forward: true,
+        }
+    }
+    pub(crate) fn local_only(items: Vec<ItemId>) -> Self {
+        Self {
+            items,
+            forward: false,
+        }
+    }
+    pub(crate) fn must_be_forwarded(&self) -> bool {
+        self.forward
+    }
+}
+
+fn handle_delete_items_event(
+    mut events: EventReader<DeleteItemsEvent>,
+    mut commands: Commands,
+    item_q: Query<(Entity, &Item)>,
+) {
+    for event in events.read() {
+        for (entity, item) in &item_q {
+            if event.items.contains(&item.id) {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+    }
+}
+
+/// Copy some items, then move the copies. Copies get consecutive ids from
+/// `first`, in the same order of `items`.
+#[derive(Event)]
+pub(crate) struct DuplicateItemsEvent {
+    pub(crate) items: Vec<ItemId>,
+    pub(crate) first: ItemId,
+    pub(crate) placement: Placement,
+    forward: bool,
+}
+
+impl DuplicateItemsEvent {
+    pub(crate) fn new(items: Vec<ItemId>, first: ItemId, placement: Placement) -> Self {
+        Self {
+            items,
+            first,
+            placement,
+            forward: true,
+        }
+    }
+    pub(crate) fn local_only(items: Vec<ItemId>, first: ItemId, placement: Placement) -> Self {
+        Self {
+            items,
+            first,
+            placement,
+            forward: false,
+        }
+    }
+    pub(crate) fn must_be_forwarded(&self) -> bool {
+        self.forward
+    }
+    /// Ids of the copies
+    pub(crate) fn copies(&self) -> impl Iterator<Item = ItemId> + '_ {
+        let ItemId { src, n } = self.first;
+        (n..).take(self.items.len()).map(move |n| ItemId { src, n })
+    }
+}
+
+/// Lines and shapes are meshes, labels are text
+type ItemContent<'a> = (
+    &'a Item,
+    &'a Drawn,
+    Option<&'a Path>,
+    Option<&'a Stroke>,
+    Option<&'a Fill>,
+    Option<&'a Text>,
+    Option<&'a Anchor>,
+);
+
+fn handle_duplicate_items_event(
+    mut events: EventReader<DuplicateItemsEvent>,
+    mut commands: Commands,
+    item_q: Query<ItemContent, With<Completed>>,
+    mut stack: ResMut<Stack>,
+) {
+    for event in events.read() {
+        for (id, copy_id) in event.items.iter().zip(event.copies()) {
+            let originals = item_q.iter().filter(|(item, ..)| item.id == *id);
+
+            for (item, drawn, path, stroke, fill, text, anchor) in originals {
+                let placement = item.placement.then(&event.placement);
+                let copy = stack.item(copy_id, item.layer, item.page, placement, item.origin);
+                let transform = copy.transform(MAX_ITEM_Z);
+
+                if let (Some(path), Some(stroke), Some(fill)) = (path, stroke, fill) {
+                    commands.spawn((
+                        ShapeBundle {
+                            path: Path(path.0.clone()),
+                            spatial: transform.into(),
+                            ..default()
+                        },
+                        *stroke,
+                        *fill,
+                        Completed,
+                        copy,
+                        drawn.clone(),
+                    ));
+                } else if let (Some(text), Some(anchor)) = (text, anchor) {
+                    commands.spawn((
+                        Text2dBundle {
+                            text: text.clone(),
+                            text_anchor: *anchor,
+                            transform,
+                            ..default()
+                        },
+                        Completed,
+                        copy,
+                        drawn.clone(),
+                    ));
+                }
+            }
+        }
+    }
+}
+
+/// Fill closed lines and shapes with a color, or empty them with `None`
+#[derive(Event)]
+pub(crate) struct FillItemsEvent {
+    pub(crate) items: Vec<ItemId>,
+    pub(crate) fill: Option<Srgba>,
+    forward: bool,
+}
+
+impl FillItemsEvent {
+    pub(crate) fn new(items: Vec<ItemId>, fill: Option<Srgba>) -> Self {
+        Self {
+            items,
+            fill,
+            forward: true,
+        }
+    }
+    pub(crate) fn local_only(items: Vec<ItemId>, fill: Option<Srgba>) -> Self {
+        Self {
+            items,
+            fill,
+            forward: false,
+        }
+    }
+    pub(crate) fn must_be_forwarded(&self) -> bool {
+        self.forward
+    }
+}
+
+fn handle_fill_items_event(
+    mut events: EventReader<FillItemsEvent>,
+    mut item_q: Query<(&Item, &mut Drawn)>,
+) {
+    for event in events.read() {
+        for (item, mut drawn) in &mut item_q {
+            if !event.items.contains(&item.id) {
+                continue;
+            }
+            match drawn.as_mut() {
+                Drawn::Line(line) => line.fill = event.fill,
+                Drawn::Shape(shape) if shape.kind.is_closed() => shape.fill = event.fill,
+                Drawn::Shape(_) | Drawn::Label(_) => {}
+            }
+        }
+    }
+}
+
+/// The inside of a filled item, a child of the item so it follows it
+#[derive(Component)]
+struct ItemFill;
+
+/// Chunks of a long stroke are filled together, as a single closed line,
+/// under the first chunk
+fn draw_fills(
+    mut commands: Commands,
+    changed_q: Query<&Item, Changed<Drawn>>,
+    item_q: Query<(Entity, &Item, &Drawn, &Transform, Option<&Children>)>,
+    fill_q: Query<(), With<ItemFill>>,
+    smoothing: Res<Smoothing>,
+    stack: Res<Stack>,
+    theme: Res<Theme>,
+) {
+    let mut changed: Vec<_> = changed_q.iter().map(|item| item.id).collect();
+    changed.sort_unstable_by_key(|id| (id.src.0, id.n));
+    changed.dedup();
+
+    for id in changed {
+        let mut parts: Vec<_> = item_q
+            .iter()
+            .filter(|(_, item, ..)| item.id == id)
+            .collect();
+
+        for (.., children) in &parts {
+            for &child in children.iter().flat_map(|children| children.iter()) {
+                if fill_q.contains(child) {
+                    commands.entity(child).despawn();
+                }
+            }
+        }
+
+        parts.sort_by(|(.., a, _), (.., b, _)| a.translation.z.total_cmp(&b.translation.z));
+
+        let Some(&(owner, _, drawn, ..)) = parts.first() else {
+            continue;
+        };
+
+        let (path, color) = match drawn {
+            Drawn::Line(DrawnLine {
+                fill: Some(color), ..
+            }) => {
+                let points: Vec<_> = parts
+                    .iter()
+                    .filter_map(|(_, _, drawn, ..)| match drawn {
+                        Drawn::Line(line) => Some(line.points.iter().copied()),
+                        _ => None,
+                    })
+                    .flatten()
+                    .collect();
+
+                // Fills come from peers too, their lines may be empty
+                let Some(&first) = points.first() else {
+                    continue;
+                };
+
+                let mut path_builder = PathBuilder::new();
+                path_builder.move_to(first);
+                spline_to(&mut path_builder, &points, *smoothing);
+                path_builder.close();
+                (path_builder.build(), *color)
+            }
+            Drawn::Shape(
+                shape @ DrawnShape {
+                    fill: Some(color), ..
+                },
+            ) => (shape.geometry(), *color),
+            _ => continue,
+        };
+
+        commands.entity(owner).with_children(|parent| {
+            parent.spawn((
+                ItemFill,
+                ShapeBundle {
+                    path,
+                    spatial: Transform::from_xyz(0.0, 0.0, -stack.step / 2.0).into(),
+                    ..default()
+                },
+                Fill::color(theme.ink(color)),
+            ));
+        });
+    }
+}
+
+/// What carries the colors of an item
+type Paint<'a> = (
+    &'a mut Drawn,
+    Option<&'a mut Stroke>,
+    Option<&'a mut Fill>,
+    Option<&'a mut Text>,
+);
+
+/// Items are painted again with the colors of the new theme
+fn recolor(theme: Res<Theme>, mut item_q: Query<Paint>) {
+    if !theme.is_changed() {
+        return;
+    }
+
+    for (mut drawn, stroke, fill, mut text) in &mut item_q {
+        let color = theme.ink(drawn.color());
+
+        // Strokes drawn as outlines are filled, the other ones are not
+        if let Some(mut stroke) = stroke.filter(|x| x.color.alpha() > 0.0) {
+            stroke.color = color.into();
+        }
+        if let Some(mut fill) = fill.filter(|x| x.color.alpha() > 0.0) {
+            fill.color = color.into();
+        }
+        for section in text.iter_mut().flat_map(|text| text.sections.iter_mut()) {
+            section.style.color = color.into();
+        }
+
+        // Their fills are drawn again
+        if let Drawn::Line(DrawnLine { fill: Some(_), .. })
+        | Drawn::Shape(DrawnShape { fill: Some(_), .. }) = *drawn
+        {
+            drawn.set_changed();
+        }
     }
 }
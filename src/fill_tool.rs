@@ -0,0 +1,145 @@
+#![allow(clippy::needless_pass_by_value)]
+#![allow(clippy::cast_precision_loss)]
+
+use bevy::prelude::*;
+use lavagna_collab::{ItemId, ShapeKind};
+
+use crate::drawing::{Drawn, DrawnShape, FillItemsEvent, Item};
+use crate::layers::Layers;
+use crate::local_chalk::{LocalChalk, Tool};
+use crate::pages::Pages;
+
+/// A freehand line is closed when its ends are closer than this, relative to
+/// its length
+const CLOSED_THRESHOLD: f32 = 0.2;
+
+/// Click inside a closed line or shape to fill it with the chalk color.
+/// Clicking again with the same color empties it. Items on hidden or locked
+/// layers are left alone.
+pub(crate) struct FillToolPlugin;
+
+impl Plugin for FillToolPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, update);
+    }
+}
+
+fn update(
+    chalk: Res<LocalChalk>,
+    layers: Res<Layers>,
+    pages: Res<Pages>,
+    mut was_pressed: Local<bool>,
+    item_q: Query<(&Item, &Drawn, &Transform)>,
+    mut fill_items_event: EventWriter<FillItemsEvent>,
+) {
+    let local = chalk.get();
+    let just_pressed = local.pressed && !*was_pressed;
+    *was_pressed = local.pressed;
+
+    if chalk.tool() != Tool::Fill || !just_pressed {
+        return;
+    }
+
+    let position = Vec3::new(local.x as f32, local.y as f32, 0.0);
+
+    // Chunks of long strokes share the same id, and are filled together
+    let mut items: Vec<(ItemId, Vec<(&Drawn, &Transform)>)> = Vec::new();
+    let editable = item_q
+        .iter()
+        .filter(|(item, ..)| layers.is_editable(item.layer) && pages.is_shown(item.page));
+    for (item, drawn, transform) in editable {
+        match items.iter_mut().find(|(id, _)| *id == item.id) {
+            Some((_, parts)) => parts.push((drawn, transform)),
+            None => items.push((item.id, vec![(drawn, transform)])),
+        }
+    }
+
+    let topmost = items
+        .iter_mut()
+        .filter_map(|(id, parts)| {
+            parts.sort_by(|(_, a), (_, b)| a.translation.z.total_cmp(&b.translation.z));
+            let (drawn, transform) = parts.first()?;
+            // Every chunk has the same placement
+            let inverse = transform.compute_matrix().inverse();
+            let p = inverse.transform_point3(position).truncate();
+            let top = parts.last()?.1.translation.z;
+            contains(drawn, parts, p).then_some((*id, top, fill(drawn)))
+        })
+        .max_by(|(_, a, _), (_, b, _)| a.total_cmp(b));
+
+    if let Some((id, _, fill)) = topmost {
+        let color = chalk.color();
+        let fill = (fill != Some(color)).then_some(color);
+        fill_items_event.send(FillItemsEvent::new(vec![id], fill));
+    }
+}
+
+fn fill(drawn: &Drawn) -> Option<Srgba> {
+    match drawn {
+        Drawn::Line(line) => line.fill,
+        Drawn::Shape(shape) => shape.fill,
+        Drawn::Label(_) => None,
+    }
+}
+
+/// Whether `p`, in the coordinates of the item, is inside it
+fn contains(drawn: &Drawn, parts: &[(&Drawn, &Transform)], p: Vec2) -> bool {
+    match drawn {
+        Drawn::Line(_) => {
+            let points: Vec<_> = parts
+                .iter()
+                .filter_map(|(drawn, _)| match drawn {
+                    Drawn::Line(line) => Some(line.points.iter().copied()),
+                    _ => None,
+                })
+                .flatten()
+                .collect();
+            is_closed(&points) && polygon_contains(&points, p)
+        }
+        Drawn::Shape(shape) => shape_contains(shape, p),
+        Drawn::Label(_) => false,
+    }
+}
+
+fn is_closed(points: &[Vec2]) -> bool {
+    let (Some(&first), Some(&last)) = (points.first(), points.last()) else {
+        return false;
+    };
+    let length: f32 = points.windows(2).map(|w| w[0].distance(w[1])).sum();
+    points.len() > 2 && first.distance(last) <= length * CLOSED_THRESHOLD
+}
+
+fn shape_contains(shape: &DrawnShape, p: Vec2) -> bool {
+    let rect = Rect::from_corners(shape.from, shape.to);
+
+    match shape.kind {
+        ShapeKind::Rectangle => rect.contains(p),
+        ShapeKind::Ellipse => {
+            let radii = rect.half_size().max(Vec2::splat(f32::EPSILON));
+            ((p - rect.center()) / radii).length_squared() <= 1.0
+        }
+        ShapeKind::Polygon => polygon_contains(&shape.vertices, p),
+        ShapeKind::Line | ShapeKind::Arrow => false,
+    }
+}
+
+/// Even-odd rule, the polygon is implicitly closed
+fn polygon_contains(vertices: &[Vec2], p: Vec2) -> bool {
+    let Some(&last) = vertices.last() else {
+        return false;
+    };
+
+    let mut inside = false;
+    let mut prev = last;
+
+    for &v in vertices {
+        if (v.y > p.y) != (prev.y > p.y)
+            && p.x < prev.x + (p.y - prev.y) / (v.y - prev.y) * (v.x - prev.x)
+        {
+            inside = !inside;
+        }
+        prev = v;
+    }
+
+    inside
+}
@@ -1,9 +1,22 @@
 #![allow(clippy::needless_pass_by_value)]
 
 use crate::{
+    background::NextBackgroundEvent,
+    clipboard::{CopySelectionEvent, CutSelectionEvent, PasteEvent},
     drawing::ClearEvent,
-    local_chalk::{ChangeColorEvent, GrowEvent, ShrinkEvent},
-    ui::ToggleUiEvent,
+    layers::ToggleLayerPanelEvent,
+    local_chalk::{
+        ChangeColorEvent, ChangeDashEvent, GrowEvent, SelectToolEvent, ShrinkEvent,
+        ToggleEndArrowEvent, ToggleStartArrowEvent, Tool,
+    },
+    minimap::{GoToOriginEvent, ResetViewEvent, ToggleMinimapEvent, ZoomToFitEvent},
+    pages::{NextPageEvent, PreviousPageEvent, TogglePagePanelEvent},
+    palette::SelectSlotEvent,
+    presets::{SavePresetEvent, UsePresetEvent},
+    select_tool::{DeleteSelectionEvent, DuplicateSelectionEvent},
+    text_tool::TextEditor,
+    theme::NextThemeEvent,
+    ui::{ColorPicker, TogglePickerEvent, ToggleUiEvent},
 };
 use bevy::prelude::*;
 
@@ -11,26 +24,95 @@ pub(crate) struct KeybindingPlugin;
 
 impl Plugin for KeybindingPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
-        app.add_systems(Update, update);
+        app.add_systems(
+            Update,
+            (
+                update_clipboard,
+                update_color,
+                update_pen,
+                update_tools,
+                update_selection,
+                update_board,
+                update_panels,
+                update_digits,
+                update_view,
+            )
+                .run_if(not_typing),
+        );
     }
 }
 
-fn update(
+/// Keys are typed into the text, or into the color picker
+fn not_typing(text_editor: Res<TextEditor>, picker: Res<ColorPicker>) -> bool {
+    !(text_editor.is_editing() || picker.is_typing())
+}
+
+/// Command on macOS
+fn command_pressed(keyboard_input: &ButtonInput<KeyCode>) -> bool {
+    keyboard_input.any_pressed([
+        KeyCode::ControlLeft,
+        KeyCode::ControlRight,
+        KeyCode::SuperLeft,
+        KeyCode::SuperRight,
+    ])
+}
+
+/// Copy, cut and paste, with the command key
+fn update_clipboard(
     keyboard_input: Res<ButtonInput<KeyCode>>,
-    mut clear_event: EventWriter<ClearEvent>,
-    mut change_color_event: EventWriter<ChangeColorEvent>,
-    mut shrink_event: EventWriter<ShrinkEvent>,
-    mut grow_event: EventWriter<GrowEvent>,
-    mut toggle_ui_event: EventWriter<ToggleUiEvent>,
+    mut copy_selection_event: EventWriter<CopySelectionEvent>,
+    mut cut_selection_event: EventWriter<CutSelectionEvent>,
+    mut paste_event: EventWriter<PasteEvent>,
 ) {
+    if !command_pressed(&keyboard_input) {
+        return;
+    }
+
+    if keyboard_input.just_pressed(KeyCode::KeyC) {
+        copy_selection_event.send(CopySelectionEvent);
+    }
+
     if keyboard_input.just_pressed(KeyCode::KeyX) {
-        clear_event.send(ClearEvent::new());
+        cut_selection_event.send(CutSelectionEvent);
+    }
+
+    if keyboard_input.just_pressed(KeyCode::KeyV) {
+        paste_event.send(PasteEvent);
+    }
+}
+
+fn update_color(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut change_color_event: EventWriter<ChangeColorEvent>,
+    mut toggle_picker_event: EventWriter<TogglePickerEvent>,
+) {
+    // Shortcuts must not trigger the bindings of the same keys, like copy
+    if command_pressed(&keyboard_input) {
+        return;
     }
 
     if keyboard_input.just_pressed(KeyCode::KeyC) {
         change_color_event.send(ChangeColorEvent);
     }
 
+    if keyboard_input.just_pressed(KeyCode::KeyK) {
+        toggle_picker_event.send(TogglePickerEvent);
+    }
+}
+
+/// Keys changing how the chalk draws
+fn update_pen(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut grow_event: EventWriter<GrowEvent>,
+    mut shrink_event: EventWriter<ShrinkEvent>,
+    mut change_dash_event: EventWriter<ChangeDashEvent>,
+    mut toggle_start_arrow_event: EventWriter<ToggleStartArrowEvent>,
+    mut toggle_end_arrow_event: EventWriter<ToggleEndArrowEvent>,
+) {
+    if command_pressed(&keyboard_input) {
+        return;
+    }
+
     if keyboard_input.just_pressed(KeyCode::KeyM) {
         grow_event.send(GrowEvent);
     }
@@ -39,7 +121,192 @@ fn update(
         shrink_event.send(ShrinkEvent);
     }
 
+    if keyboard_input.just_pressed(KeyCode::Minus) {
+        change_dash_event.send(ChangeDashEvent);
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Comma) {
+        toggle_start_arrow_event.send(ToggleStartArrowEvent);
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Period) {
+        toggle_end_arrow_event.send(ToggleEndArrowEvent);
+    }
+}
+
+fn update_tools(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut select_tool_event: EventWriter<SelectToolEvent>,
+) {
+    if command_pressed(&keyboard_input) {
+        return;
+    }
+
+    let tools = [
+        (KeyCode::KeyP, Tool::Chalk),
+        (KeyCode::KeyH, Tool::Highlighter),
+        (KeyCode::KeyL, Tool::Line),
+        (KeyCode::KeyR, Tool::Rectangle),
+        (KeyCode::KeyE, Tool::Ellipse),
+        (KeyCode::KeyA, Tool::Arrow),
+        (KeyCode::KeyT, Tool::Text),
+        (KeyCode::KeyS, Tool::Select),
+        (KeyCode::KeyZ, Tool::Laser),
+        (KeyCode::KeyF, Tool::Fill),
+    ];
+
+    for (key, tool) in tools {
+        if keyboard_input.just_pressed(key) {
+            select_tool_event.send(SelectToolEvent(tool));
+        }
+    }
+}
+
+fn update_selection(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut delete_selection_event: EventWriter<DeleteSelectionEvent>,
+    mut duplicate_selection_event: EventWriter<DuplicateSelectionEvent>,
+) {
+    if command_pressed(&keyboard_input) {
+        return;
+    }
+
+    if keyboard_input.any_just_pressed([KeyCode::Delete, KeyCode::Backspace]) {
+        delete_selection_event.send(DeleteSelectionEvent);
+    }
+
+    if keyboard_input.just_pressed(KeyCode::KeyD) {
+        duplicate_selection_event.send(DuplicateSelectionEvent);
+    }
+}
+
+/// Keys changing the whole board: clearing it, its look and its pages
+fn update_board(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut clear_event: EventWriter<ClearEvent>,
+    mut next_background_event: EventWriter<NextBackgroundEvent>,
+    mut next_theme_event: EventWriter<NextThemeEvent>,
+    mut next_page_event: EventWriter<NextPageEvent>,
+    mut previous_page_event: EventWriter<PreviousPageEvent>,
+) {
+    // Ctrl+X cuts instead
+    if command_pressed(&keyboard_input) {
+        return;
+    }
+
+    if keyboard_input.just_pressed(KeyCode::KeyX) {
+        clear_event.send(ClearEvent::new());
+    }
+
+    if keyboard_input.just_pressed(KeyCode::KeyG) {
+        next_background_event.send(NextBackgroundEvent);
+    }
+
+    if keyboard_input.just_pressed(KeyCode::KeyB) {
+        next_theme_event.send(NextThemeEvent);
+    }
+
+    if keyboard_input.any_just_pressed([KeyCode::BracketRight, KeyCode::PageDown]) {
+        next_page_event.send(NextPageEvent);
+    }
+
+    if keyboard_input.any_just_pressed([KeyCode::BracketLeft, KeyCode::PageUp]) {
+        previous_page_event.send(PreviousPageEvent);
+    }
+}
+
+fn update_panels(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut toggle_ui_event: EventWriter<ToggleUiEvent>,
+    mut toggle_layer_panel_event: EventWriter<ToggleLayerPanelEvent>,
+    mut toggle_page_panel_event: EventWriter<TogglePagePanelEvent>,
+) {
+    if command_pressed(&keyboard_input) {
+        return;
+    }
+
     if keyboard_input.just_pressed(KeyCode::KeyU) {
         toggle_ui_event.send(ToggleUiEvent);
     }
+
+    if keyboard_input.just_pressed(KeyCode::KeyY) {
+        toggle_layer_panel_event.send(ToggleLayerPanelEvent);
+    }
+
+    if keyboard_input.just_pressed(KeyCode::KeyO) {
+        toggle_page_panel_event.send(TogglePagePanelEvent);
+    }
+}
+
+/// Keys 1 to 9 pick a color of the palette, or a pen preset with alt
+fn update_digits(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut select_slot_event: EventWriter<SelectSlotEvent>,
+    mut use_preset_event: EventWriter<UsePresetEvent>,
+    mut save_preset_event: EventWriter<SavePresetEvent>,
+) {
+    if command_pressed(&keyboard_input) {
+        return;
+    }
+
+    let slots = [
+        KeyCode::Digit1,
+        KeyCode::Digit2,
+        KeyCode::Digit3,
+        KeyCode::Digit4,
+        KeyCode::Digit5,
+        KeyCode::Digit6,
+        KeyCode::Digit7,
+        KeyCode::Digit8,
+        KeyCode::Digit9,
+    ];
+
+    // With alt, the same keys pick pen presets, or save them with shift too
+    let alt = keyboard_input.any_pressed([KeyCode::AltLeft, KeyCode::AltRight]);
+    let shift = keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+
+    for (slot, key) in slots.into_iter().enumerate() {
+        if keyboard_input.just_pressed(key) {
+            match (alt, shift) {
+                (true, true) => {
+                    save_preset_event.send(SavePresetEvent(slot));
+                }
+                (true, false) => {
+                    use_preset_event.send(UsePresetEvent(slot));
+                }
+                _ => {
+                    select_slot_event.send(SelectSlotEvent(slot));
+                }
+            }
+        }
+    }
+}
+
+/// Keys finding the way back to the drawing
+fn update_view(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut toggle_minimap_event: EventWriter<ToggleMinimapEvent>,
+    mut zoom_to_fit_event: EventWriter<ZoomToFitEvent>,
+    mut reset_view_event: EventWriter<ResetViewEvent>,
+    mut go_to_origin_event: EventWriter<GoToOriginEvent>,
+) {
+    if command_pressed(&keyboard_input) {
+        return;
+    }
+
+    if keyboard_input.just_pressed(KeyCode::KeyV) {
+        toggle_minimap_event.send(ToggleMinimapEvent);
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Equal) {
+        zoom_to_fit_event.send(ZoomToFitEvent);
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Digit0) {
+        reset_view_event.send(ResetViewEvent);
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Home) {
+        go_to_origin_event.send(GoToOriginEvent);
+    }
 }
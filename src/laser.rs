@@ -0,0 +1,132 @@
+#![allow(clippy::needless_pass_by_value)]
+#![allow(clippy::cast_precision_loss)]
+
+use std::collections::VecDeque;
+
+use bevy::color::palettes::css::RED;
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+
+use crate::drawing::PENDING_Z;
+use crate::{Chalk, MainCamera};
+
+pub(crate) const LASER_COLOR: Srgba = RED;
+
+/// How long each point of the trail lasts, in seconds
+const LASER_DURATION: f32 = 1.0;
+
+/// Width of the trail, in pixels
+const LASER_WIDTH: f32 = 6.0;
+
+/// Point at something without drawing: the laser leaves a trail behind the
+/// cursor which fades away, and is never completed like a stroke. Peers see
+/// the trail too, remote chalks point with the laser when their move events
+/// say so.
+pub(crate) struct LaserPlugin;
+
+impl Plugin for LaserPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, follow_chalks)
+            .add_systems(Update, fade_trails.after(follow_chalks));
+    }
+}
+
+/// The trail left by the laser of a chalk, local or remote
+#[derive(Component)]
+struct LaserTrail {
+    chalk: Entity,
+    /// Points with the time they were reached, oldest first
+    points: VecDeque<(Vec2, f32)>,
+}
+
+fn follow_chalks(
+    mut commands: Commands,
+    chalk_q: Query<(Entity, &Chalk), Changed<Chalk>>,
+    mut trail_q: Query<&mut LaserTrail>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_seconds();
+
+    for (entity, chalk) in &chalk_q {
+        if !(chalk.laser && chalk.updated) {
+            continue;
+        }
+
+        let point = (Vec2::new(chalk.x as f32, chalk.y as f32), now);
+
+        if let Some(mut trail) = trail_q.iter_mut().find(|trail| trail.chalk == entity) {
+            trail.points.push_back(point);
+        } else {
+            spawn_trail(&mut commands, entity, point);
+        }
+    }
+}
+
+fn spawn_trail(commands: &mut Commands, chalk: Entity, point: (Vec2, f32)) {
+    // Above the strokes being drawn, below the cursors
+    let transform = Transform {
+        translation: Vec3::new(0., 0., PENDING_Z + 0.5),
+        ..default()
+    };
+
+    commands.spawn((
+        LaserTrail {
+            chalk,
+            points: VecDeque::from([point]),
+        },
+        ShapeBundle {
+            path: PathBuilder::new().build(),
+            spatial: transform.into(),
+            ..default()
+        },
+        Stroke::new(LASER_COLOR, LASER_WIDTH),
+    ));
+}
+
+/// Old points are dropped, and the whole trail fades when the laser stops
+fn fade_trails(
+    mut commands: Commands,
+    mut trail_q: Query<(Entity, &mut LaserTrail, &mut Path, &mut Stroke)>,
+    camera_q: Query<&OrthographicProjection, With<MainCamera>>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_seconds();
+    let scale = camera_q
+        .get_single()
+        .map_or(1.0, |projection| projection.scale);
+
+    for (entity, mut trail, mut path, mut stroke) in &mut trail_q {
+        while let Some(&(_, t)) = trail.points.front() {
+            if now - t < LASER_DURATION {
+                break;
+            }
+            trail.points.pop_front();
+        }
+
+        let Some(&(_, newest)) = trail.points.back() else {
+            commands.entity(entity).despawn();
+            continue;
+        };
+
+        let mut builder = PathBuilder::new();
+        let mut points = trail.points.iter().map(|&(p, _)| p);
+        if let Some(first) = points.next() {
+            builder.move_to(first);
+            // A single point is drawn as a dot
+            builder.line_to(first);
+        }
+        for p in points {
+            builder.line_to(p);
+        }
+        *path = builder.build();
+
+        let alpha = 1.0 - (now - newest) / LASER_DURATION;
+        *stroke = Stroke {
+            color: LASER_COLOR.with_alpha(alpha).into(),
+            options: StrokeOptions::default()
+                .with_line_width(LASER_WIDTH * scale)
+                .with_line_cap(LineCap::Round)
+                .with_line_join(LineJoin::Round),
+        };
+    }
+}
@@ -0,0 +1,356 @@
+#![allow(clippy::needless_pass_by_value)]
+#![allow(clippy::type_complexity)]
+
+use bevy::prelude::*;
+use lavagna_collab::{ItemId, Layer};
+
+use crate::drawing::{DeleteItemsEvent, Item, ItemIds};
+use crate::local_chalk::LocalChalk;
+use crate::pages::Pages;
+use crate::theme::Theme;
+use crate::ui::{default_font, toggle_visibility};
+
+const FONT_SIZE: f32 = 20.0;
+
+/// Items are drawn on the active layer. Layers can be reordered, hidden and
+/// locked from the layer panel, and are shared with peers.
+pub(crate) struct LayersPlugin;
+
+impl Plugin for LayersPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Layers>()
+            .add_event::<SetLayersEvent>()
+            .add_event::<ToggleLayerPanelEvent>()
+            .add_systems(Startup, setup)
+            .add_systems(Update, handle_set_layers_event)
+            .add_systems(
+                Update,
+                toggle_visibility::<ToggleLayerPanelEvent, LayerPanel>,
+            )
+            .add_systems(Update, handle_layer_buttons)
+            .add_systems(Update, update_chalk_layer.after(handle_set_layers_event))
+            .add_systems(Update, show_layers.after(handle_set_layers_event))
+            .add_systems(Update, draw_panel.after(handle_set_layers_event));
+    }
+}
+
+#[derive(Resource)]
+pub(crate) struct Layers {
+    /// Bottom first, never empty
+    layers: Vec<Layer>,
+    active: ItemId,
+}
+
+impl Default for Layers {
+    fn default() -> Self {
+        Self {
+            layers: vec![first_layer()],
+            active: ItemId::default(),
+        }
+    }
+}
+
+/// The layer of items saved before layers were introduced
+fn first_layer() -> Layer {
+    Layer::new(ItemId::default(), layer_name(1))
+}
+
+fn layer_name(n: usize) -> String {
+    format!("Layer {n}")
+}
+
+impl Layers {
+    pub(crate) fn active(&self) -> ItemId {
+        self.active
+    }
+
+    /// Position from the bottom. Items on layers not known yet, e.g. created
+    /// by a peer, are stacked on the first one.
+    pub(crate) fn rank(&self, id: ItemId) -> usize {
+        self.layers.iter().position(|x| x.id == id).unwrap_or(0)
+    }
+
+    fn get(&self, id: ItemId) -> Option<&Layer> {
+        self.layers.iter().find(|x| x.id == id)
+    }
+
+    pub(crate) fn is_visible(&self, id: ItemId) -> bool {
+        self.get(id).map_or(true, |x| x.visible)
+    }
+
+    /// Items on hidden or locked layers cannot be selected or changed
+    pub(crate) fn is_editable(&self, id: ItemId) -> bool {
+        self.get(id).map_or(true, |x| x.visible && !x.locked)
+    }
+
+    /// Nothing is drawn while the active layer is locked, i.e. when all the
+    /// layers are
+    pub(crate) fn is_active_locked(&self) -> bool {
+        self.get(self.active).is_some_and(|x| x.locked)
+    }
+
+    fn set(&mut self, layers: Vec<Layer>) {
+        self.layers = if layers.is_empty() {
+            vec![first_layer()]
+        } else {
+            layers
+        };
+
+        // Nothing can be drawn on locked layers, the last one is active
+        // when all of them are
+        if !self.get(self.active).is_some_and(|x| !x.locked) {
+            let unlocked = self.layers.iter().rev().find(|x| !x.locked);
+            self.active = unlocked
+                .or(self.layers.last())
+                .map_or_else(ItemId::default, |x| x.id);
+        }
+    }
+}
+
+/// Replace all the layers, e.g. after changing one of them
+#[derive(Event)]
+pub(crate) struct SetLayersEvent {
+    pub(crate) layers: Vec<Layer>,
+    forward: bool,
+}
+
+impl SetLayersEvent {
+    pub(crate) fn new(layers: Vec<Layer>) -> Self {
+        Self {
+            layers,
+            forward: true,
+        }
+    }
+    pub(crate) fn local_only(layers: Vec<Layer>) -> Self {
+        Self {
+            layers,
+            forward: false,
+        }
+    }
+    pub(crate) fn must_be_forwarded(&self) -> bool {
+        self.forward
+    }
+}
+
+fn handle_set_layers_event(mut events: EventReader<SetLayersEvent>, mut layers: ResMut<Layers>) {
+    for event in events.read() {
+        layers.set(event.layers.clone());
+    }
+}
+
+fn update_chalk_layer(layers: Res<Layers>, mut chalk: ResMut<LocalChalk>) {
+    if layers.is_changed() {
+        chalk.set_layer(layers.active(), layers.is_active_locked());
+    }
+}
+
+/// Items on other pages are hidden too
+fn show_layers(
+    layers: Res<Layers>,
+    pages: Res<Pages>,
+    added_q: Query<(), Added<Item>>,
+    mut item_q: Query<(&Item, &mut Visibility)>,
+) {
+    if added_q.is_empty() && !layers.is_changed() && !pages.is_changed() {
+        return;
+    }
+
+    for (item, mut visibility) in &mut item_q {
+        let shown = if layers.is_visible(item.layer) && pages.is_shown(item.page) {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+        if *visibility != shown {
+            *visibility = shown;
+        }
+    }
+}
+
+#[derive(Event)]
+pub(crate) struct ToggleLayerPanelEvent;
+
+/// Lists the layers, top first
+#[derive(Component)]
+struct LayerPanel;
+
+#[derive(Clone, Copy)]
+enum LayerAction {
+    Add,
+    Activate,
+    ToggleVisible,
+    ToggleLocked,
+    Raise,
+    Lower,
+    Delete,
+}
+
+#[derive(Component)]
+struct LayerButton {
+    layer: ItemId,
+    action: LayerAction,
+}
+
+fn setup(mut commands: Commands) {
+    commands.spawn((
+        LayerPanel,
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                right: Val::Px(0.),
+                top: Val::Px(0.),
+                flex_direction: FlexDirection::Column,
+                ..default()
+            },
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+    ));
+}
+
+fn draw_panel(
+    mut commands: Commands,
+    layers: Res<Layers>,
+    theme: Res<Theme>,
+    panel_q: Query<Entity, With<LayerPanel>>,
+    asset_server: Res<AssetServer>,
+) {
+    if !(layers.is_changed() || theme.is_changed()) {
+        return;
+    }
+
+    let font = default_font(&asset_server);
+    let panel = panel_q.single();
+
+    let button = |parent: &mut ChildBuilder, text: &str, layer, action, color| {
+        parent
+            .spawn((
+                LayerButton { layer, action },
+                ButtonBundle {
+                    style: Style {
+                        padding: UiRect::axes(Val::Px(8.), Val::Px(4.)),
+                        ..default()
+                    },
+                    background_color: BackgroundColor(Color::Srgba(color)),
+                    ..default()
+                },
+            ))
+            .with_children(|parent| {
+                parent.spawn(TextBundle::from_section(
+                    text,
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: FONT_SIZE,
+                        color: theme.ui_text().into(),
+                    },
+                ));
+            });
+    };
+
+    let row = NodeBundle {
+        style: Style {
+            justify_content: JustifyContent::End,
+            ..default()
+        },
+        ..default()
+    };
+
+    commands.entity(panel).despawn_descendants();
+    commands.entity(panel).with_children(|parent| {
+        parent.spawn(row.clone()).with_children(|parent| {
+            let layer = ItemId::default();
+            button(parent, "+", layer, LayerAction::Add, theme.ui_button());
+        });
+
+        for layer in layers.layers.iter().rev() {
+            let id = layer.id;
+            let name_color = if id == layers.active {
+                theme.ui_active()
+            } else {
+                theme.ui_button()
+            };
+            let visible = if layer.visible { "o" } else { "-" };
+            let locked = if layer.locked { "L" } else { "_" };
+
+            parent.spawn(row.clone()).with_children(|parent| {
+                button(parent, &layer.name, id, LayerAction::Activate, name_color);
+                button(
+                    parent,
+                    visible,
+                    id,
+                    LayerAction::ToggleVisible,
+                    theme.ui_button(),
+                );
+                button(
+                    parent,
+                    locked,
+                    id,
+                    LayerAction::ToggleLocked,
+                    theme.ui_button(),
+                );
+                button(parent, "^", id, LayerAction::Raise, theme.ui_button());
+                button(parent, "v", id, LayerAction::Lower, theme.ui_button());
+                button(parent, "x", id, LayerAction::Delete, theme.ui_button());
+            });
+        }
+    });
+}
+
+fn handle_layer_buttons(
+    interaction_q: Query<(&Interaction, &LayerButton), Changed<Interaction>>,
+    mut layers: ResMut<Layers>,
+    mut ids: ResMut<ItemIds>,
+    item_q: Query<&Item>,
+    mut set_layers_event: EventWriter<SetLayersEvent>,
+    mut delete_items_event: EventWriter<DeleteItemsEvent>,
+) {
+    for (interaction, button) in &interaction_q {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        let mut changed = layers.layers.clone();
+        let position = changed.iter().position(|x| x.id == button.layer);
+
+        match (button.action, position) {
+            (LayerAction::Add, _) => {
+                let layer = Layer::new(ids.allocate(), layer_name(changed.len() + 1));
+                layers.active = layer.id;
+                changed.push(layer);
+            }
+            (LayerAction::Activate, Some(i)) => {
+                if !changed[i].locked {
+                    layers.active = button.layer;
+                }
+                continue;
+            }
+            (LayerAction::ToggleVisible, Some(i)) => {
+                changed[i].visible = !changed[i].visible;
+            }
+            (LayerAction::ToggleLocked, Some(i)) => {
+                changed[i].locked = !changed[i].locked;
+            }
+            (LayerAction::Raise, Some(i)) if i + 1 < changed.len() => {
+                changed.swap(i, i + 1);
+            }
+            (LayerAction::Lower, Some(i)) if i > 0 => {
+                changed.swap(i, i - 1);
+            }
+            // The last layer is kept
+            (LayerAction::Delete, Some(i)) if changed.len() > 1 => {
+                let items: Vec<_> = item_q
+                    .iter()
+                    .filter(|item| item.layer == button.layer)
+                    .map(|item| item.id)
+                    .collect();
+                if !items.is_empty() {
+                    delete_items_event.send(DeleteItemsEvent::new(items));
+                }
+                changed.remove(i);
+            }
+            _ => continue,
+        }
+
+        set_layers_event.send(SetLayersEvent::new(changed));
+    }
+}
@@ -1,11 +1,26 @@
 #![deny(unsafe_code)]
 #![warn(clippy::all, clippy::pedantic)]
 
+mod background;
+mod clipboard;
 mod collab;
 mod debug;
 mod drawing;
+mod fill_tool;
 mod keybinding;
+mod laser;
+mod layers;
 mod local_chalk;
+mod minimap;
+mod pages;
+mod palette;
+mod presets;
+mod recognition;
+mod relay;
+mod select_tool;
+mod shape_tool;
+mod text_tool;
+mod theme;
 mod ui;
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -17,16 +32,36 @@ pub mod web;
 use bevy::diagnostic::FrameTimeDiagnosticsPlugin;
 use bevy::log::LogPlugin;
 use bevy::prelude::*;
+use bevy::utils::Duration;
 use bevy_embedded_assets::EmbeddedAssetPlugin;
 use bevy_framepace::{FramepacePlugin, FramepaceSettings, Limiter};
 use bevy_pancam::{PanCam, PanCamPlugin};
+pub use lavagna_collab::Background;
+use lavagna_collab::{ItemId, StrokeStyle};
 
+use crate::background::BackgroundPlugin;
+use crate::clipboard::ClipboardPlugin;
 use crate::collab::CollabPlugin;
 pub use crate::collab::CollabPluginOpt as CollabOpt;
 use crate::debug::DebugPlugin;
 use crate::drawing::DrawingPlugin;
+pub use crate::drawing::Smoothing;
+use crate::fill_tool::FillToolPlugin;
 use crate::keybinding::KeybindingPlugin;
+use crate::laser::LaserPlugin;
+use crate::layers::LayersPlugin;
 use crate::local_chalk::LocalChalkPlugin;
+use crate::minimap::MinimapPlugin;
+use crate::pages::PagesPlugin;
+use crate::palette::PalettePlugin;
+pub use crate::presets::Pen;
+use crate::presets::PresetsPlugin;
+use crate::recognition::RecognitionPlugin;
+use crate::select_tool::SelectToolPlugin;
+use crate::shape_tool::ShapeToolPlugin;
+use crate::text_tool::TextToolPlugin;
+pub use crate::theme::Theme;
+use crate::theme::ThemePlugin;
 use crate::ui::UiPlugin;
 
 #[derive(Debug, Default)]
@@ -35,6 +70,14 @@ pub struct Opt {
     pub show_debug_pane: bool,
     pub verbose: bool,
     pub ui: bool,
+    pub smoothing: Smoothing,
+    /// The paper chosen when starting, sent to the room
+    pub background: Option<Background>,
+    pub theme: Theme,
+    /// Colors of the chalk, instead of the palette of the theme
+    pub palette: Vec<Srgba>,
+    /// Pens picked with alt and keys 1 to 9, instead of the default ones
+    pub presets: Vec<Pen>,
 }
 
 pub fn run(opt: Opt) {
@@ -75,10 +118,24 @@ pub fn run(opt: Opt) {
 
     app.add_systems(Startup, setup);
 
+    app.add_plugins(ThemePlugin::new(opt.theme));
     app.add_plugins(KeybindingPlugin);
     app.add_plugins(FramepacePlugin);
     app.add_plugins(LocalChalkPlugin);
-    app.add_plugins(DrawingPlugin);
+    app.add_plugins(PalettePlugin::new(opt.palette));
+    app.add_plugins(PresetsPlugin::new(opt.presets));
+    app.add_plugins(DrawingPlugin::new(opt.smoothing));
+    app.add_plugins(BackgroundPlugin::new(opt.background));
+    app.add_plugins(ShapeToolPlugin);
+    app.add_plugins(RecognitionPlugin);
+    app.add_plugins(TextToolPlugin);
+    app.add_plugins(SelectToolPlugin);
+    app.add_plugins(ClipboardPlugin);
+    app.add_plugins(FillToolPlugin);
+    app.add_plugins(LaserPlugin);
+    app.add_plugins(LayersPlugin);
+    app.add_plugins(PagesPlugin);
+    app.add_plugins(MinimapPlugin);
     app.add_plugins(PanCamPlugin);
 
     if opt.show_debug_pane {
@@ -98,11 +155,7 @@ pub fn run(opt: Opt) {
 #[derive(Component)]
 struct MainCamera;
 
-fn setup(
-    mut commands: Commands,
-    mut clear_color: ResMut<ClearColor>,
-    mut framepace: ResMut<FramepaceSettings>,
-) {
+fn setup(mut commands: Commands, mut framepace: ResMut<FramepaceSettings>) {
     commands
         .spawn((Camera2dBundle::default(), MainCamera))
         .insert(PanCam {
@@ -117,13 +170,12 @@ fn setup(
             max_y: None,
         });
 
-    clear_color.0 = Color::BLACK;
-
     // Limit frame rate, we dont't want to squeeze that CPU
     framepace.limiter = Limiter::from_framerate(30.0);
 }
 
 #[derive(Component, Debug, Clone, Copy, Default)]
+#[allow(clippy::struct_excessive_bools)]
 struct Chalk {
     pressed: bool,
     updated: bool,
@@ -132,6 +184,29 @@ struct Chalk {
     y: i32,
     color: Srgba,
     line_width: u32,
+    pressure: Pressure,
+    /// The line being drawn
+    stroke: ItemId,
+    /// Pointing with the laser, never pressed at the same time
+    laser: bool,
+    style: StrokeStyle,
+    /// The layer items are drawn on
+    layer: ItemId,
+    /// The page items are drawn on
+    page: ItemId,
+    /// Scale of the view when the stroke started, which decides how much it
+    /// is simplified
+    scale: f32,
+}
+
+/// Pen pressure, from 0 to 1. Devices without pressure are always at 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Pressure(f32);
+
+impl Default for Pressure {
+    fn default() -> Self {
+        Self(1.0)
+    }
 }
 
 #[must_use]
@@ -147,10 +222,31 @@ pub fn options() -> Opt {
     }
 }
 
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Traffic {
+    pub bytes_per_sec: usize,
+    pub messages_per_sec: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PeerStats {
+    pub id: u16,
+    /// `None` until the peer answers a ping
+    pub round_trip: Option<Duration>,
+    pub online: bool,
+}
+
 #[derive(Debug, Default)]
 pub struct CollabStats {
     pub active: bool,
     pub peers: usize,
+    pub relay: bool,
+    /// Every peer seen in this session, sorted by collab id
+    pub peer_stats: Vec<PeerStats>,
+    pub sent: Traffic,
+    pub received: Traffic,
+    pub decode_failures: usize,
+    pub dropped: usize,
 }
 
 #[derive(Debug, Resource, Default)]
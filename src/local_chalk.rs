@@ -1,45 +1,184 @@
 #![allow(clippy::needless_pass_by_value)]
 
-use crate::drawing::make_chalk;
+use crate::drawing::{make_chalk, ItemIds};
+use crate::laser::LASER_COLOR;
+use crate::palette::Palette;
+use crate::theme::Theme;
 use crate::Chalk;
 use crate::MainCamera;
-use bevy::color::palettes::css::{BLUE, GREEN, ORANGE, RED, TURQUOISE, WHITE, YELLOW};
+use crate::Pressure;
+use bevy::color::palettes::css::WHITE;
 use std::cmp::max;
 use std::cmp::min;
 
 use bevy::{
-    input::{mouse::MouseButtonInput, ButtonState},
+    input::{
+        mouse::{MouseButtonInput, MouseScrollUnit, MouseWheel},
+        touch::ForceTouch,
+        ButtonState,
+    },
     prelude::*,
 };
+use bevy_pancam::PanCam;
 use bevy_prototype_lyon::prelude::Fill;
 use bevy_prototype_lyon::prelude::GeometryBuilder;
 use bevy_prototype_lyon::prelude::ShapeBundle;
 use bevy_prototype_lyon::shapes;
+use lavagna_collab::{Dash, ItemId, ShapeKind, StrokeStyle};
 
 pub(crate) struct LocalChalkPlugin;
 
 #[derive(Component)]
-struct LocalChalkFlag;
+pub(crate) struct LocalChalkFlag;
 
 #[derive(Component)]
 struct LocalCursor;
 
 #[derive(Resource)]
-pub(crate) struct LocalChalk(Chalk);
+pub(crate) struct LocalChalk {
+    chalk: Chalk,
+    tool: Tool,
+    /// The stroke has been replaced by a recognized shape, nothing else is
+    /// drawn until the chalk is pressed again
+    stroke_replaced: bool,
+    /// Nothing is drawn on a locked layer
+    layer_locked: bool,
+}
 
 impl LocalChalk {
     pub(crate) fn get(&self) -> &Chalk {
-        &self.0
+        &self.chalk
+    }
+
+    /// The chalk as seen by freehand drawing, never pressed when another
+    /// tool is in use or the layer is locked
+    pub(crate) fn freehand(&self) -> Chalk {
+        let mut chalk = self.chalk;
+        if self.tool == Tool::Highlighter {
+            chalk.color = chalk.color.with_alpha(HIGHLIGHTER_ALPHA);
+            chalk.line_width = (chalk.line_width * HIGHLIGHTER_WIDTH_FACTOR).min(MAX_LINE_WIDTH);
+        }
+        if self.tool == Tool::Laser {
+            // Peers see the cursor with the color of the laser
+            chalk.color = LASER_COLOR;
+            chalk.laser = chalk.pressed;
+        }
+        if !self.tool.is_freehand() || self.stroke_replaced || self.layer_locked {
+            chalk.pressed = false;
+            chalk.just_released = false;
+        }
+        chalk
     }
 }
 
 impl Default for LocalChalk {
     fn default() -> Self {
-        Self(Chalk {
-            color: WHITE,
-            line_width: 8,
-            ..default()
-        })
+        Self {
+            chalk: Chalk {
+                color: WHITE,
+                line_width: 8,
+                scale: 1.0,
+                ..default()
+            },
+            tool: Tool::default(),
+            stroke_replaced: false,
+            layer_locked: false,
+        }
+    }
+}
+
+/// Opacity of highlighter strokes, drawn under the opaque ink
+const HIGHLIGHTER_ALPHA: f32 = 0.4;
+
+/// Highlighter strokes are wider than the chalk
+const HIGHLIGHTER_WIDTH_FACTOR: u32 = 3;
+
+/// Line widths are sent to peers as bytes
+const MAX_LINE_WIDTH: u32 = u8::MAX as u32;
+
+/// Widest chalk, leaving room for the highlighter
+pub(crate) const MAX_CHALK_WIDTH: u32 = 100;
+
+/// Each notch of the wheel, with alt held, grows or shrinks the chalk by this
+/// factor, and by one at least
+const WHEEL_WIDTH_FACTOR: f32 = 1.2;
+
+/// Scrolling this many pixels, e.g. with touchpads, counts as a notch
+const PIXELS_PER_NOTCH: f32 = 50.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum Tool {
+    #[default]
+    Chalk,
+    Highlighter,
+    Line,
+    Rectangle,
+    Ellipse,
+    Arrow,
+    Text,
+    Select,
+    Laser,
+    Fill,
+}
+
+const TOOLS: [Tool; 10] = [
+    Tool::Chalk,
+    Tool::Highlighter,
+    Tool::Line,
+    Tool::Rectangle,
+    Tool::Ellipse,
+    Tool::Arrow,
+    Tool::Text,
+    Tool::Select,
+    Tool::Laser,
+    Tool::Fill,
+];
+
+impl Tool {
+    pub(crate) fn shape(self) -> Option<ShapeKind> {
+        match self {
+            Self::Chalk
+            | Self::Highlighter
+            | Self::Text
+            | Self::Select
+            | Self::Laser
+            | Self::Fill => None,
+            Self::Line => Some(ShapeKind::Line),
+            Self::Rectangle => Some(ShapeKind::Rectangle),
+            Self::Ellipse => Some(ShapeKind::Ellipse),
+            Self::Arrow => Some(ShapeKind::Arrow),
+        }
+    }
+
+    /// Draws freehand strokes
+    pub(crate) fn is_freehand(self) -> bool {
+        matches!(self, Self::Chalk | Self::Highlighter)
+    }
+
+    /// Shown in the toolbar
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            Self::Chalk => "~",
+            Self::Highlighter => "=",
+            Self::Line => "/",
+            Self::Rectangle => "#",
+            Self::Ellipse => "O",
+            Self::Arrow => ">",
+            Self::Text => "T",
+            Self::Select => "[]",
+            Self::Laser => "*",
+            Self::Fill => "F",
+        }
+    }
+
+    fn next(self) -> Self {
+        TOOLS
+            .iter()
+            .cycle()
+            .skip_while(|&&x| x != self)
+            .nth(1)
+            .copied()
+            .unwrap_or(self)
     }
 }
 
@@ -49,11 +188,19 @@ impl Plugin for LocalChalkPlugin {
             .add_event::<ChangeColorEvent>()
             .add_event::<GrowEvent>()
             .add_event::<ShrinkEvent>()
+            .add_event::<SelectToolEvent>()
+            .add_event::<ChangeDashEvent>()
+            .add_event::<ToggleStartArrowEvent>()
+            .add_event::<ToggleEndArrowEvent>()
             .add_systems(Startup, startup)
             .add_systems(Update, handle_user_input)
+            .add_systems(Update, update_scale)
             .add_systems(Update, handle_change_color_event)
             .add_systems(Update, handle_incr_size_event)
             .add_systems(Update, handle_decr_size_event)
+            .add_systems(Update, handle_select_tool_event)
+            .add_systems(Update, handle_change_style_events)
+            .add_systems(Update, wheel_width)
             .add_systems(Update, mouse_events)
             .add_systems(Update, update_chalk)
             .add_systems(Update, touch_events)
@@ -64,10 +211,10 @@ impl Plugin for LocalChalkPlugin {
 fn startup(mut commands: Commands, mut chalk: ResMut<LocalChalk>) {
     commands.spawn((
         LocalChalkFlag, //
-        make_chalk(chalk.0),
+        make_chalk(chalk.freehand()),
     ));
 
-    let chalk = &mut chalk.0;
+    let chalk = &mut chalk.chalk;
 
     let shape = shapes::Circle {
         radius: 1.0,
@@ -84,7 +231,7 @@ fn startup(mut commands: Commands, mut chalk: ResMut<LocalChalk>) {
     ));
 }
 
-fn cursor_to_world_position(
+pub(crate) fn cursor_to_world_position(
     cursor_pos: Vec2,
     camera: &Camera,
     camera_t: &GlobalTransform,
@@ -101,7 +248,7 @@ fn handle_user_input(
     mut chalk: ResMut<LocalChalk>,
 ) {
     let (camera, camera_transform) = camera_q.single();
-    let chalk = &mut chalk.0;
+    let chalk = &mut chalk.chalk;
     let window = window_q.single();
 
     let prev_chalk = *chalk;
@@ -117,11 +264,30 @@ fn handle_user_input(
     }
 }
 
+/// Zooming in the middle of a stroke does not change how it is simplified
+fn update_scale(
+    camera_q: Query<&OrthographicProjection, With<MainCamera>>,
+    mut chalk: ResMut<LocalChalk>,
+) {
+    let Ok(projection) = camera_q.get_single() else {
+        return;
+    };
+
+    if !chalk.chalk.pressed {
+        chalk.chalk.scale = projection.scale;
+    }
+}
+
 fn mouse_events(
     mut mouse_button_input_events: EventReader<MouseButtonInput>,
     mut chalk: ResMut<LocalChalk>,
+    mut ids: ResMut<ItemIds>,
 ) {
-    let chalk = &mut chalk.0;
+    let LocalChalk {
+        chalk,
+        stroke_replaced,
+        ..
+    } = chalk.as_mut();
     let was_pressed = chalk.pressed;
 
     // This is needed to avoid interference with touch (see touch_events)
@@ -136,7 +302,10 @@ fn mouse_events(
             } => {
                 chalk.just_released = false;
                 chalk.pressed = true;
+                chalk.pressure = Pressure::default();
+                chalk.stroke = ids.allocate();
                 press_changed = true;
+                *stroke_replaced = false;
             }
             MouseButtonInput {
                 button: MouseButton::Left,
@@ -162,10 +331,15 @@ fn touch_events(
     mut touch_evr: EventReader<TouchInput>,
     mut chalk: ResMut<LocalChalk>,
     camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    mut ids: ResMut<ItemIds>,
 ) {
     use bevy::input::touch::TouchPhase;
 
-    let chalk = &mut chalk.0;
+    let LocalChalk {
+        chalk,
+        stroke_replaced,
+        ..
+    } = chalk.as_mut();
     let was_pressed = chalk.pressed;
 
     // This is needed to avoid interference with mouse (see mouse_events)
@@ -176,11 +350,16 @@ fn touch_events(
 
     for event in &mut touch_evr.read() {
         cursor_position = Some(event.position);
+        chalk.pressure = event
+            .force
+            .map_or_else(Pressure::default, pressure_from_force);
         match event.phase {
             TouchPhase::Started => {
                 chalk.just_released = false;
                 chalk.pressed = true;
+                chalk.stroke = ids.allocate();
                 press_changed = true;
+                *stroke_replaced = false;
             }
             TouchPhase::Moved => {}
             TouchPhase::Ended | TouchPhase::Canceled => {
@@ -205,6 +384,21 @@ fn touch_events(
     }
 }
 
+#[allow(clippy::cast_possible_truncation)]
+fn pressure_from_force(force: ForceTouch) -> Pressure {
+    let pressure = match force {
+        ForceTouch::Calibrated {
+            force,
+            max_possible_force,
+            ..
+        } if max_possible_force > 0.0 => force / max_possible_force,
+        ForceTouch::Calibrated { .. } => 1.0,
+        ForceTouch::Normalized(force) => force,
+    };
+
+    Pressure(pressure.clamp(0.0, 1.0) as f32)
+}
+
 fn is_updated(old_chalk: &Chalk, new_chalk: &Chalk) -> bool {
     old_chalk.x != new_chalk.x
         || old_chalk.y != new_chalk.y
@@ -214,13 +408,15 @@ fn is_updated(old_chalk: &Chalk, new_chalk: &Chalk) -> bool {
 
 #[allow(clippy::cast_precision_loss)]
 fn update_cursor(
-    mut chalk: ResMut<LocalChalk>,
+    chalk: Res<LocalChalk>,
+    theme: Res<Theme>,
     mut cursor_q: Query<(&mut Fill, &mut Transform), With<LocalCursor>>,
 ) {
-    let chalk = &mut chalk.0;
+    // Same size and color of the strokes drawn by the current tool
+    let chalk = chalk.freehand();
     let (mut fill, mut transform) = cursor_q.single_mut();
 
-    *fill = Fill::color(chalk.color);
+    *fill = Fill::color(theme.ink(chalk.color));
 
     let scale = chalk.line_width as f32 / 2.0;
     transform.scale = Vec3::new(scale, scale, scale);
@@ -233,48 +429,116 @@ fn update_chalk(
     mut chalk_q: Query<&mut Chalk, With<LocalChalkFlag>>,
 ) {
     if let Ok(mut chalk) = chalk_q.get_single_mut() {
-        *chalk = local_chalk.0;
+        *chalk = local_chalk.freehand();
     }
 }
 
-const COLORS: [Srgba; 7] = [WHITE, BLUE, TURQUOISE, GREEN, YELLOW, ORANGE, RED];
-
-fn next_color(curr_color: Srgba) -> Srgba {
-    if let Some(next_color) = COLORS
+/// The color after `color` in `palette`, or the first one
+fn next_color(color: Srgba, palette: &[Srgba]) -> Srgba {
+    let next = palette
         .iter()
-        .cycle()
-        .skip_while(|&&x| x != curr_color)
-        .nth(1)
-    {
-        *next_color
-    } else {
-        curr_color
+        .position(|&x| x == color)
+        .map_or(0, |i| (i + 1) % palette.len());
+    palette[next]
+}
+
+fn next_dash(dash: Dash) -> Dash {
+    match dash {
+        Dash::Solid => Dash::Dashed,
+        Dash::Dashed => Dash::Dotted,
+        Dash::Dotted => Dash::Solid,
     }
 }
 
 fn incr_size(size: u32) -> u32 {
-    min(100, size * 2)
+    min(MAX_CHALK_WIDTH, size * 2)
 }
 
 fn decr_size(size: u32) -> u32 {
     max(1, size / 2)
 }
 
+/// `size` grown by `notches` of the wheel, shrunk when negative
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_precision_loss)]
+#[allow(clippy::cast_sign_loss)]
+fn wheel_size(size: u32, notches: f32) -> u32 {
+    let scaled = (size as f32 * WHEEL_WIDTH_FACTOR.powf(notches)).round() as u32;
+    let size = if notches > 0.0 {
+        max(scaled, size + 1)
+    } else {
+        min(scaled, size.saturating_sub(1))
+    };
+    size.clamp(1, MAX_CHALK_WIDTH)
+}
+
 impl LocalChalk {
     pub(crate) fn color(&self) -> Srgba {
-        self.0.color
+        self.chalk.color
+    }
+    /// `palette` is as shown on the board with `theme`
+    pub(crate) fn next_color(&mut self, palette: &[Srgba], theme: Theme) -> Srgba {
+        let shown = next_color(theme.ink(self.chalk.color), palette);
+        self.chalk.color = theme.ink(shown);
+        self.chalk.color
     }
-    pub(crate) fn next_color(&mut self) -> Srgba {
-        self.0.color = next_color(self.0.color);
-        self.0.color
+    pub(crate) fn set_color(&mut self, color: Srgba) {
+        self.chalk.color = color;
     }
     pub(crate) fn grow(&mut self) -> u32 {
-        self.0.line_width = incr_size(self.0.line_width);
-        self.0.line_width
+        self.chalk.line_width = incr_size(self.chalk.line_width);
+        self.chalk.line_width
     }
     pub(crate) fn shrink(&mut self) -> u32 {
-        self.0.line_width = decr_size(self.0.line_width);
-        self.0.line_width
+        self.chalk.line_width = decr_size(self.chalk.line_width);
+        self.chalk.line_width
+    }
+    pub(crate) fn line_width(&self) -> u32 {
+        self.chalk.line_width
+    }
+    pub(crate) fn set_line_width(&mut self, line_width: u32) {
+        self.chalk.line_width = line_width.clamp(1, MAX_CHALK_WIDTH);
+    }
+    pub(crate) fn style(&self) -> StrokeStyle {
+        self.chalk.style
+    }
+    pub(crate) fn set_style(&mut self, style: StrokeStyle) {
+        self.chalk.style = style;
+    }
+    pub(crate) fn next_dash(&mut self) -> Dash {
+        self.chalk.style.dash = next_dash(self.chalk.style.dash);
+        self.chalk.style.dash
+    }
+    pub(crate) fn toggle_start_arrow(&mut self) -> bool {
+        self.chalk.style.start_arrow = !self.chalk.style.start_arrow;
+        self.chalk.style.start_arrow
+    }
+    pub(crate) fn toggle_end_arrow(&mut self) -> bool {
+        self.chalk.style.end_arrow = !self.chalk.style.end_arrow;
+        self.chalk.style.end_arrow
+    }
+    pub(crate) fn set_layer(&mut self, layer: ItemId, locked: bool) {
+        self.chalk.layer = layer;
+        self.layer_locked = locked;
+    }
+    pub(crate) fn layer_locked(&self) -> bool {
+        self.layer_locked
+    }
+    pub(crate) fn set_page(&mut self, page: ItemId) {
+        self.chalk.page = page;
+    }
+    pub(crate) fn tool(&self) -> Tool {
+        self.tool
+    }
+    pub(crate) fn is_stroke_replaced(&self) -> bool {
+        self.stroke_replaced
+    }
+    pub(crate) fn replace_stroke(&mut self) {
+        self.stroke_replaced = true;
+    }
+    pub(crate) fn next_tool(&mut self) -> Tool {
+        self.tool = self.tool.next();
+        self.tool
     }
 }
 
@@ -287,12 +551,26 @@ pub(crate) struct GrowEvent;
 #[derive(Event)]
 pub(crate) struct ShrinkEvent;
 
+#[derive(Event)]
+pub(crate) struct SelectToolEvent(pub(crate) Tool);
+
+#[derive(Event)]
+pub(crate) struct ChangeDashEvent;
+
+#[derive(Event)]
+pub(crate) struct ToggleStartArrowEvent;
+
+#[derive(Event)]
+pub(crate) struct ToggleEndArrowEvent;
+
 fn handle_change_color_event(
     mut events: EventReader<ChangeColorEvent>,
     mut chalk: ResMut<LocalChalk>,
+    theme: Res<Theme>,
+    palette: Res<Palette>,
 ) {
     for _ in &mut events.read() {
-        chalk.next_color();
+        chalk.next_color(palette.colors(*theme), *theme);
     }
 }
 
@@ -307,3 +585,69 @@ fn handle_decr_size_event(mut events: EventReader<ShrinkEvent>, mut chalk: ResMu
         chalk.shrink();
     }
 }
+
+fn handle_select_tool_event(
+    mut events: EventReader<SelectToolEvent>,
+    mut chalk: ResMut<LocalChalk>,
+) {
+    for SelectToolEvent(tool) in events.read() {
+        chalk.tool = *tool;
+    }
+}
+
+fn handle_change_style_events(
+    mut change_dash_events: EventReader<ChangeDashEvent>,
+    mut toggle_start_arrow_events: EventReader<ToggleStartArrowEvent>,
+    mut toggle_end_arrow_events: EventReader<ToggleEndArrowEvent>,
+    mut chalk: ResMut<LocalChalk>,
+) {
+    for _ in change_dash_events.read() {
+        chalk.next_dash();
+    }
+    for _ in toggle_start_arrow_events.read() {
+        chalk.toggle_start_arrow();
+    }
+    for _ in toggle_end_arrow_events.read() {
+        chalk.toggle_end_arrow();
+    }
+}
+
+/// The wheel changes the width of the chalk while alt is held, instead of
+/// zooming
+fn wheel_width(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut wheel_events: EventReader<MouseWheel>,
+    mut chalk: ResMut<LocalChalk>,
+    mut pancam_q: Query<&mut PanCam>,
+    mut pending_notches: Local<f32>,
+) {
+    let alt = keyboard_input.any_pressed([KeyCode::AltLeft, KeyCode::AltRight]);
+
+    for mut pancam in &mut pancam_q {
+        if pancam.enabled == alt {
+            pancam.enabled = !alt;
+        }
+    }
+
+    if !alt {
+        wheel_events.clear();
+        *pending_notches = 0.0;
+        return;
+    }
+
+    // Scrolling a few pixels at a time adds up to whole notches
+    *pending_notches += wheel_events
+        .read()
+        .map(|event| match event.unit {
+            MouseScrollUnit::Line => event.y,
+            MouseScrollUnit::Pixel => event.y / PIXELS_PER_NOTCH,
+        })
+        .sum::<f32>();
+    let notches = pending_notches.trunc();
+    *pending_notches -= notches;
+
+    if notches != 0.0 {
+        let line_width = wheel_size(chalk.line_width(), notches);
+        chalk.set_line_width(line_width);
+    }
+}
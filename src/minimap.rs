@@ -0,0 +1,279 @@
+#![allow(clippy::needless_pass_by_value)]
+#![allow(clippy::type_complexity)]
+
+use bevy::prelude::*;
+use bevy::render::primitives::Aabb;
+use bevy::ui::RelativeCursorPosition;
+use bevy_pancam::PanCam;
+
+use crate::drawing::{Completed, Item};
+use crate::select_tool::world_rect;
+use crate::theme::Theme;
+use crate::ui::toggle_visibility;
+use crate::MainCamera;
+
+const MINIMAP_SIZE: Vec2 = Vec2::new(200.0, 150.0);
+
+/// Room around the drawing and the view, relative to their size
+const MINIMAP_MARGIN: f32 = 0.05;
+
+/// Room around the drawing when zooming to fit it, relative to the window
+const FIT_MARGIN: f32 = 0.1;
+
+/// Ways back to the drawing on the infinite board: a minimap of the items
+/// and of the view, where clicking moves the view, and jumps to fit the
+/// drawing, to the default view or to the origin
+pub(crate) struct MinimapPlugin;
+
+impl Plugin for MinimapPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ToggleMinimapEvent>()
+            .add_event::<ZoomToFitEvent>()
+            .add_event::<ResetViewEvent>()
+            .add_event::<GoToOriginEvent>()
+            .add_systems(Startup, setup)
+            .add_systems(Update, toggle_visibility::<ToggleMinimapEvent, Minimap>)
+            .add_systems(Update, handle_navigation_events)
+            .add_systems(Update, navigate)
+            .add_systems(Update, draw.after(navigate));
+    }
+}
+
+#[derive(Event)]
+pub(crate) struct ToggleMinimapEvent;
+
+/// Show every item on the page, as large as possible
+#[derive(Event)]
+pub(crate) struct ZoomToFitEvent;
+
+/// Back to the origin, without zoom
+#[derive(Event)]
+pub(crate) struct ResetViewEvent;
+
+/// Back to the origin, keeping the zoom
+#[derive(Event)]
+pub(crate) struct GoToOriginEvent;
+
+/// The part of the board shown in the minimap
+#[derive(Component, Default)]
+struct Minimap {
+    area: Rect,
+    /// Pixels of the minimap for each unit of the board
+    scale: f32,
+    /// Where `area` starts in the minimap, centered on it
+    offset: Vec2,
+}
+
+impl Minimap {
+    fn new(area: Rect) -> Self {
+        let size = area.size().max(Vec2::ONE);
+        let scale = (MINIMAP_SIZE / size).min_element();
+        let offset = (MINIMAP_SIZE - size * scale) / 2.0;
+        Self {
+            area,
+            scale,
+            offset,
+        }
+    }
+
+    /// Position and size in the minimap, y pointing down
+    fn style(&self, rect: Rect) -> Style {
+        Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(self.offset.x + (rect.min.x - self.area.min.x) * self.scale),
+            top: Val::Px(self.offset.y + (self.area.max.y - rect.max.y) * self.scale),
+            width: Val::Px(rect.width() * self.scale),
+            height: Val::Px(rect.height() * self.scale),
+            border: UiRect::all(Val::Px(1.)),
+            ..default()
+        }
+    }
+
+    /// Point of the board at `position`, from (0, 0) at the top left of the
+    /// minimap to (1, 1) at the bottom right
+    fn world_position(&self, position: Vec2) -> Vec2 {
+        let p = (position * MINIMAP_SIZE - self.offset) / self.scale;
+        Vec2::new(self.area.min.x + p.x, self.area.max.y - p.y)
+    }
+}
+
+/// Everything drawn on the page shown
+#[derive(Component)]
+struct ContentFrame;
+
+/// What the window shows
+#[derive(Component)]
+struct ViewFrame;
+
+fn setup(mut commands: Commands) {
+    commands
+        .spawn((
+            Minimap::default(),
+            RelativeCursorPosition::default(),
+            ButtonBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    right: Val::Px(0.),
+                    bottom: Val::Px(50.),
+                    width: Val::Px(MINIMAP_SIZE.x),
+                    height: Val::Px(MINIMAP_SIZE.y),
+                    overflow: Overflow::clip(),
+                    ..default()
+                },
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn((ContentFrame, NodeBundle::default()));
+            parent.spawn((ViewFrame, NodeBundle::default()));
+        });
+}
+
+/// Bounding box of the items shown, hidden layers and other pages are
+/// left out
+fn content_rect<'a>(
+    items: impl Iterator<Item = (&'a Transform, &'a Aabb, &'a Visibility)>,
+) -> Option<Rect> {
+    items
+        .filter(|(.., visibility)| **visibility != Visibility::Hidden)
+        .map(|(transform, aabb, _)| world_rect(transform, aabb))
+        .reduce(|a, b| a.union(b))
+}
+
+fn view_rect(transform: &Transform, projection: &OrthographicProjection) -> Rect {
+    let center = transform.translation.truncate();
+    Rect {
+        min: projection.area.min + center,
+        max: projection.area.max + center,
+    }
+}
+
+fn handle_navigation_events(
+    mut zoom_to_fit_events: EventReader<ZoomToFitEvent>,
+    mut reset_view_events: EventReader<ResetViewEvent>,
+    mut go_to_origin_events: EventReader<GoToOriginEvent>,
+    item_q: Query<(&Transform, &Aabb, &Visibility), (With<Item>, With<Completed>)>,
+    mut camera_q: Query<
+        (&mut Transform, &mut OrthographicProjection, &PanCam),
+        (With<MainCamera>, Without<Item>),
+    >,
+) {
+    let zoom_to_fit = zoom_to_fit_events.read().count() > 0;
+    let reset_view = reset_view_events.read().count() > 0;
+    let go_to_origin = go_to_origin_events.read().count() > 0;
+
+    let Ok((mut transform, mut projection, pancam)) = camera_q.get_single_mut() else {
+        return;
+    };
+
+    let z = transform.translation.z;
+
+    if go_to_origin {
+        transform.translation = Vec3::new(0.0, 0.0, z);
+    }
+
+    if reset_view {
+        transform.translation = Vec3::new(0.0, 0.0, z);
+        projection.scale = 1.0;
+    }
+
+    if zoom_to_fit {
+        // An empty page is shown as it is by default
+        let Some(content) = content_rect(item_q.iter()) else {
+            transform.translation = Vec3::new(0.0, 0.0, z);
+            projection.scale = 1.0;
+            return;
+        };
+
+        // The area of the projection grows with its scale
+        let window = projection.area.size() / projection.scale;
+        let needed = content.size() * (1.0 + 2.0 * FIT_MARGIN) / window;
+        let max_scale = pancam.max_scale.unwrap_or(f32::INFINITY);
+        projection.scale = needed.max_element().clamp(pancam.min_scale, max_scale);
+        transform.translation = content.center().extend(z);
+    }
+}
+
+/// Clicking or dragging on the minimap centers the view there
+fn navigate(
+    minimap_q: Query<(&Minimap, &Interaction, &RelativeCursorPosition, &Visibility)>,
+    mut camera_q: Query<&mut Transform, With<MainCamera>>,
+) {
+    let Ok((minimap, interaction, cursor, visibility)) = minimap_q.get_single() else {
+        return;
+    };
+
+    if *interaction != Interaction::Pressed || *visibility == Visibility::Hidden {
+        return;
+    }
+
+    let (Some(position), Ok(mut transform)) = (cursor.normalized, camera_q.get_single_mut()) else {
+        return;
+    };
+
+    let target = minimap.world_position(position.clamp(Vec2::ZERO, Vec2::ONE));
+    transform.translation = target.extend(transform.translation.z);
+}
+
+fn draw(
+    theme: Res<Theme>,
+    mut minimap_q: Query<(
+        &mut Minimap,
+        &Interaction,
+        &mut BackgroundColor,
+        &Visibility,
+    )>,
+    item_q: Query<(&Transform, &Aabb, &Visibility), (With<Item>, With<Completed>)>,
+    camera_q: Query<(&Transform, &OrthographicProjection), With<MainCamera>>,
+    mut content_q: Query<
+        (&mut Style, &mut BackgroundColor),
+        (With<ContentFrame>, Without<Minimap>),
+    >,
+    mut view_q: Query<
+        (&mut Style, &mut BorderColor),
+        (With<ViewFrame>, Without<ContentFrame>, Without<Minimap>),
+    >,
+) {
+    let Ok((mut minimap, interaction, mut background, visibility)) = minimap_q.get_single_mut()
+    else {
+        return;
+    };
+
+    if *visibility == Visibility::Hidden {
+        return;
+    }
+
+    let Ok((camera, projection)) = camera_q.get_single() else {
+        return;
+    };
+
+    let content = content_rect(item_q.iter());
+    let view = view_rect(camera, projection);
+
+    // While dragging, the minimap stays put under the cursor
+    if *interaction != Interaction::Pressed {
+        let area = content.map_or(view, |content| content.union(view));
+        let margin = area.size().max_element() * MINIMAP_MARGIN;
+        *minimap = Minimap::new(area.inflate(margin));
+    }
+
+    background.set_if_neq(theme.ui_button().into());
+
+    if let Ok((mut style, mut color)) = content_q.get_single_mut() {
+        let shown = match content {
+            Some(content) => minimap.style(content),
+            None => Style {
+                display: Display::None,
+                ..default()
+            },
+        };
+        style.set_if_neq(shown);
+        color.set_if_neq(theme.ui_active().into());
+    }
+
+    if let Ok((mut style, mut color)) = view_q.get_single_mut() {
+        style.set_if_neq(minimap.style(view));
+        color.set_if_neq(theme.ui_text().into());
+    }
+}
@@ -0,0 +1,338 @@
+#![allow(clippy::needless_pass_by_value)]
+#![allow(clippy::type_complexity)]
+
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use lavagna_collab::{ItemId, Page};
+
+use crate::drawing::{DeleteItemsEvent, Item, ItemIds};
+use crate::local_chalk::LocalChalk;
+use crate::theme::Theme;
+use crate::ui::{default_font, toggle_visibility};
+use crate::MainCamera;
+
+const FONT_SIZE: f32 = 20.0;
+
+/// The board is a stack of pages, like a notebook, shown one at a time. Pages
+/// can be added, reordered and deleted from the page panel. Turning pages is
+/// shared with peers, while each peer remembers where they were looking on
+/// every page.
+pub(crate) struct PagesPlugin;
+
+impl Plugin for PagesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Pages>()
+            .add_event::<SetPagesEvent>()
+            .add_event::<NextPageEvent>()
+            .add_event::<PreviousPageEvent>()
+            .add_event::<TogglePagePanelEvent>()
+            .add_systems(Startup, setup)
+            .add_systems(Update, handle_set_pages_event)
+            .add_systems(Update, handle_turn_page_events)
+            .add_systems(Update, toggle_visibility::<TogglePagePanelEvent, PagePanel>)
+            .add_systems(Update, handle_page_buttons)
+            .add_systems(Update, update_chalk_page.after(handle_set_pages_event))
+            .add_systems(Update, switch_camera.after(handle_set_pages_event))
+            .add_systems(Update, draw_panel.after(handle_set_pages_event));
+    }
+}
+
+#[derive(Resource)]
+pub(crate) struct Pages {
+    /// In order, never empty
+    all: Vec<Page>,
+    current: ItemId,
+    /// Where the camera was, and its zoom, when leaving each page
+    cameras: HashMap<ItemId, (Vec2, f32)>,
+}
+
+impl Default for Pages {
+    fn default() -> Self {
+        Self {
+            // The page of items saved before pages were introduced
+            all: vec![Page::new(ItemId::default())],
+            current: ItemId::default(),
+            cameras: HashMap::default(),
+        }
+    }
+}
+
+impl Pages {
+    pub(crate) fn current(&self) -> ItemId {
+        self.current
+    }
+
+    /// Position of `id`. Items on pages not known yet, e.g. created by a
+    /// peer, are on the first one.
+    fn index(&self, id: ItemId) -> usize {
+        self.all.iter().position(|x| x.id == id).unwrap_or(0)
+    }
+
+    /// Whether items on `page` are on the page shown
+    pub(crate) fn is_shown(&self, page: ItemId) -> bool {
+        self.index(page) == self.index(self.current)
+    }
+
+    fn set(&mut self, pages: Vec<Page>, current: ItemId) {
+        self.all = if pages.is_empty() {
+            vec![Page::new(ItemId::default())]
+        } else {
+            pages
+        };
+        self.current = self.all[self.index(current)].id;
+    }
+}
+
+/// Replace all the pages, and show one of them
+#[derive(Event)]
+pub(crate) struct SetPagesEvent {
+    pub(crate) pages: Vec<Page>,
+    pub(crate) current: ItemId,
+    forward: bool,
+}
+
+impl SetPagesEvent {
+    pub(crate) fn new(pages: Vec<Page>, current: ItemId) -> Self {
+        Self {
+            pages,
+            current,
+            forward: true,
+        }
+    }
+    pub(crate) fn local_only(pages: Vec<Page>, current: ItemId) -> Self {
+        Self {
+            pages,
+            current,
+            forward: false,
+        }
+    }
+    pub(crate) fn must_be_forwarded(&self) -> bool {
+        self.forward
+    }
+}
+
+#[derive(Event)]
+pub(crate) struct NextPageEvent;
+
+#[derive(Event)]
+pub(crate) struct PreviousPageEvent;
+
+fn handle_set_pages_event(mut events: EventReader<SetPagesEvent>, mut pages: ResMut<Pages>) {
+    for event in events.read() {
+        pages.set(event.pages.clone(), event.current);
+    }
+}
+
+fn handle_turn_page_events(
+    mut next_events: EventReader<NextPageEvent>,
+    mut previous_events: EventReader<PreviousPageEvent>,
+    pages: Res<Pages>,
+    mut set_pages_event: EventWriter<SetPagesEvent>,
+) {
+    let next = next_events.read().count();
+    let previous = previous_events.read().count();
+
+    if next == previous {
+        return;
+    }
+
+    // Nothing happens past the first or last page
+    let turned = (pages.index(pages.current) + next).checked_sub(previous);
+    if let Some(page) = turned.and_then(|i| pages.all.get(i)) {
+        set_pages_event.send(SetPagesEvent::new(pages.all.clone(), page.id));
+    }
+}
+
+fn update_chalk_page(pages: Res<Pages>, mut chalk: ResMut<LocalChalk>) {
+    if pages.is_changed() {
+        chalk.set_page(pages.current());
+    }
+}
+
+/// Each page is shown from where it was left, new pages from the origin
+fn switch_camera(
+    mut pages: ResMut<Pages>,
+    mut camera_q: Query<(&mut Transform, &mut OrthographicProjection), With<MainCamera>>,
+    mut shown: Local<Option<ItemId>>,
+) {
+    let current = pages.current;
+    if *shown == Some(current) {
+        return;
+    }
+
+    let Ok((mut transform, mut projection)) = camera_q.get_single_mut() else {
+        return;
+    };
+
+    let Some(previous) = shown.replace(current) else {
+        return;
+    };
+
+    let camera = (transform.translation.truncate(), projection.scale);
+    pages.cameras.insert(previous, camera);
+
+    let (translation, scale) = pages
+        .cameras
+        .get(&current)
+        .copied()
+        .unwrap_or((Vec2::ZERO, 1.0));
+    transform.translation = translation.extend(transform.translation.z);
+    projection.scale = scale;
+}
+
+#[derive(Event)]
+pub(crate) struct TogglePagePanelEvent;
+
+/// Lists the pages, first at the top
+#[derive(Component)]
+struct PagePanel;
+
+#[derive(Clone, Copy)]
+enum PageAction {
+    Add,
+    Show,
+    Earlier,
+    Later,
+    Delete,
+}
+
+#[derive(Component)]
+struct PageButton {
+    page: ItemId,
+    action: PageAction,
+}
+
+fn setup(mut commands: Commands) {
+    commands.spawn((
+        PagePanel,
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                left: Val::Px(0.),
+                top: Val::Px(0.),
+                flex_direction: FlexDirection::Column,
+                ..default()
+            },
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+    ));
+}
+
+fn draw_panel(
+    mut commands: Commands,
+    pages: Res<Pages>,
+    theme: Res<Theme>,
+    panel_q: Query<Entity, With<PagePanel>>,
+    asset_server: Res<AssetServer>,
+) {
+    if !(pages.is_changed() || theme.is_changed()) {
+        return;
+    }
+
+    let font = default_font(&asset_server);
+    let panel = panel_q.single();
+
+    let button = |parent: &mut ChildBuilder, text: &str, page, action, color| {
+        parent
+            .spawn((
+                PageButton { page, action },
+                ButtonBundle {
+                    style: Style {
+                        padding: UiRect::axes(Val::Px(8.), Val::Px(4.)),
+                        ..default()
+                    },
+                    background_color: BackgroundColor(Color::Srgba(color)),
+                    ..default()
+                },
+            ))
+            .with_children(|parent| {
+                parent.spawn(TextBundle::from_section(
+                    text,
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: FONT_SIZE,
+                        color: theme.ui_text().into(),
+                    },
+                ));
+            });
+    };
+
+    let row = NodeBundle::default();
+
+    commands.entity(panel).despawn_descendants();
+    commands.entity(panel).with_children(|parent| {
+        for (i, page) in pages.all.iter().enumerate() {
+            let id = page.id;
+            let name_color = if pages.is_shown(id) {
+                theme.ui_active()
+            } else {
+                theme.ui_button()
+            };
+
+            parent.spawn(row.clone()).with_children(|parent| {
+                let name = format!("Page {}", i + 1);
+                button(parent, &name, id, PageAction::Show, name_color);
+                button(parent, "^", id, PageAction::Earlier, theme.ui_button());
+                button(parent, "v", id, PageAction::Later, theme.ui_button());
+                button(parent, "x", id, PageAction::Delete, theme.ui_button());
+            });
+        }
+
+        parent.spawn(row.clone()).with_children(|parent| {
+            let page = ItemId::default();
+            button(parent, "+", page, PageAction::Add, theme.ui_button());
+        });
+    });
+}
+
+fn handle_page_buttons(
+    interaction_q: Query<(&Interaction, &PageButton), Changed<Interaction>>,
+    pages: Res<Pages>,
+    mut ids: ResMut<ItemIds>,
+    item_q: Query<&Item>,
+    mut set_pages_event: EventWriter<SetPagesEvent>,
+    mut delete_items_event: EventWriter<DeleteItemsEvent>,
+) {
+    for (interaction, button) in &interaction_q {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        let mut changed = pages.all.clone();
+        let mut current = pages.current;
+        let i = pages.index(button.page);
+
+        match button.action {
+            // After the page shown, and shown instead of it
+            PageAction::Add => {
+                let page = Page::new(ids.allocate());
+                current = page.id;
+                changed.insert(pages.index(pages.current) + 1, page);
+            }
+            PageAction::Show => current = button.page,
+            PageAction::Earlier if i > 0 => changed.swap(i, i - 1),
+            PageAction::Later if i + 1 < changed.len() => changed.swap(i, i + 1),
+            // The last page is kept
+            PageAction::Delete if changed.len() > 1 => {
+                let items: Vec<_> = item_q
+                    .iter()
+                    .filter(|item| pages.index(item.page) == i)
+                    .map(|item| item.id)
+                    .collect();
+                if !items.is_empty() {
+                    delete_items_event.send(DeleteItemsEvent::new(items));
+                }
+                changed.remove(i);
+
+                // The next page is shown instead, or the previous one
+                if pages.is_shown(button.page) {
+                    current = changed[i.min(changed.len() - 1)].id;
+                }
+            }
+            _ => continue,
+        }
+
+        set_pages_event.send(SetPagesEvent::new(changed, current));
+    }
+}
@@ -0,0 +1,136 @@
+#![allow(clippy::needless_pass_by_value)]
+
+use bevy::prelude::*;
+
+use crate::local_chalk::LocalChalk;
+use crate::theme::Theme;
+
+/// How many recently picked colors are kept
+const MAX_RECENT: usize = 8;
+
+/// The colors at hand: the palette, from the options or else the one of the
+/// theme, and the colors picked recently
+pub(crate) struct PalettePlugin {
+    colors: Vec<Srgba>,
+}
+
+impl PalettePlugin {
+    /// Without `colors`, the palette is the one of the theme
+    pub(crate) fn new(colors: Vec<Srgba>) -> Self {
+        Self { colors }
+    }
+}
+
+impl Plugin for PalettePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Palette {
+            custom: self.colors.clone(),
+            recent: Vec::new(),
+        })
+        .add_event::<PickColorEvent>()
+        .add_event::<SelectSlotEvent>()
+        .add_systems(Update, handle_pick_color_event)
+        .add_systems(Update, handle_select_slot_event)
+        .add_systems(Update, follow_theme);
+    }
+}
+
+#[derive(Resource)]
+pub(crate) struct Palette {
+    /// As shown on the board, empty for the palette of the theme
+    custom: Vec<Srgba>,
+    /// Ink, most recent first
+    recent: Vec<Srgba>,
+}
+
+impl Palette {
+    /// Colors as shown on the board
+    pub(crate) fn colors(&self, theme: Theme) -> &[Srgba] {
+        if self.custom.is_empty() {
+            theme.palette()
+        } else {
+            &self.custom
+        }
+    }
+
+    /// Ink of the colors picked recently, most recent first
+    pub(crate) fn recent(&self) -> &[Srgba] {
+        &self.recent
+    }
+}
+
+/// Colors separated by commas, in hex like `#ff8800` or `f80`
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn parse_palette(s: &str) -> Result<Vec<Srgba>, String> {
+    s.split(',')
+        .map(str::trim)
+        .filter(|x| !x.is_empty())
+        .map(parse_color)
+        .collect()
+}
+
+pub(crate) fn parse_color(s: &str) -> Result<Srgba, String> {
+    Srgba::hex(s).map_err(|e| format!("invalid color {s}: {e}"))
+}
+
+/// Use a color picked on the board, as shown with the current theme
+#[derive(Event)]
+pub(crate) struct PickColorEvent(pub(crate) Srgba);
+
+/// Use the color in a slot of the palette, counting from 0
+#[derive(Event)]
+pub(crate) struct SelectSlotEvent(pub(crate) usize);
+
+fn handle_pick_color_event(
+    mut events: EventReader<PickColorEvent>,
+    theme: Res<Theme>,
+    mut palette: ResMut<Palette>,
+    mut chalk: ResMut<LocalChalk>,
+) {
+    for PickColorEvent(color) in events.read() {
+        let ink = theme.ink(*color);
+        chalk.set_color(ink);
+
+        palette.recent.retain(|x| *x != ink);
+        palette.recent.insert(0, ink);
+        palette.recent.truncate(MAX_RECENT);
+    }
+}
+
+fn handle_select_slot_event(
+    mut events: EventReader<SelectSlotEvent>,
+    theme: Res<Theme>,
+    palette: Res<Palette>,
+    mut chalk: ResMut<LocalChalk>,
+) {
+    for SelectSlotEvent(slot) in events.read() {
+        if let Some(color) = palette.colors(*theme).get(*slot) {
+            chalk.set_color(theme.ink(*color));
+        }
+    }
+}
+
+/// When the theme changes, a chalk with a color of the palette takes the
+/// color in the same slot of the new palette. Other colors are kept.
+fn follow_theme(
+    theme: Res<Theme>,
+    palette: Res<Palette>,
+    mut chalk: ResMut<LocalChalk>,
+    mut previous: Local<Option<Theme>>,
+) {
+    let Some(old) = previous.replace(*theme) else {
+        return;
+    };
+
+    if old == *theme {
+        return;
+    }
+
+    let old_colors = palette.colors(old);
+    let new_colors = palette.colors(*theme);
+
+    if let Some(slot) = old_colors.iter().position(|x| *x == old.ink(chalk.color())) {
+        let slot = slot.min(new_colors.len() - 1);
+        chalk.set_color(theme.ink(new_colors[slot]));
+    }
+}
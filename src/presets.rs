@@ -0,0 +1,200 @@
+#![allow(clippy::needless_pass_by_value)]
+
+use bevy::color::palettes::css::{RED, WHITE, YELLOW};
+use bevy::prelude::*;
+use lavagna_collab::{Dash, StrokeStyle};
+
+use crate::local_chalk::LocalChalk;
+use crate::palette::parse_color;
+
+/// Presets are picked with keys 1 to 9
+const PRESETS: usize = 9;
+
+/// Pens ready to pick with a key: a color, a width and a style, from the
+/// options or else the default ones. The current pen can be saved in place of
+/// any of them.
+pub(crate) struct PresetsPlugin {
+    pens: Vec<Pen>,
+}
+
+impl PresetsPlugin {
+    /// Without `pens`, the presets are the default ones
+    pub(crate) fn new(pens: Vec<Pen>) -> Self {
+        Self { pens }
+    }
+}
+
+impl Plugin for PresetsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Presets::new(&self.pens))
+            .add_event::<UsePresetEvent>()
+            .add_event::<SavePresetEvent>()
+            .add_systems(Update, handle_use_preset_event)
+            .add_systems(Update, handle_save_preset_event);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pen {
+    /// Ink, as drawn on the blackboard
+    color: Srgba,
+    line_width: u32,
+    style: StrokeStyle,
+}
+
+#[derive(Resource)]
+struct Presets([Option<Pen>; PRESETS]);
+
+impl Default for Presets {
+    /// A fine pen, the chalk, a marker and a dashed arrow
+    fn default() -> Self {
+        let pen = |color, line_width, style| {
+            Some(Pen {
+                color,
+                line_width,
+                style,
+            })
+        };
+
+        let dashed_arrow = StrokeStyle {
+            dash: Dash::Dashed,
+            end_arrow: true,
+            ..default()
+        };
+
+        let mut presets = [None; PRESETS];
+        presets[0] = pen(WHITE, 2, StrokeStyle::default());
+        presets[1] = pen(WHITE, 8, StrokeStyle::default());
+        presets[2] = pen(YELLOW, 32, StrokeStyle::default());
+        presets[3] = pen(RED, 4, dashed_arrow);
+        Self(presets)
+    }
+}
+
+impl Presets {
+    /// Pens after the ninth are left out
+    fn new(pens: &[Pen]) -> Self {
+        if pens.is_empty() {
+            return Self::default();
+        }
+
+        let mut presets = [None; PRESETS];
+        for (preset, pen) in presets.iter_mut().zip(pens) {
+            *preset = Some(*pen);
+        }
+        Self(presets)
+    }
+}
+
+/// Pens separated by commas, see `parse_pen`
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn parse_pens(s: &str) -> Result<Vec<Pen>, String> {
+    s.split(',')
+        .map(str::trim)
+        .filter(|x| !x.is_empty())
+        .map(parse_pen)
+        .collect()
+}
+
+/// A color in hex and a width, then the style if any, separated by colons:
+/// `#ff0000:4:dashed:end-arrow`. Styles are `dashed`, `dotted`,
+/// `start-arrow` and `end-arrow`.
+pub(crate) fn parse_pen(s: &str) -> Result<Pen, String> {
+    let mut fields = s.split(':').map(str::trim);
+    let color = parse_color(fields.next().unwrap_or_default())?;
+    let line_width = fields
+        .next()
+        .ok_or_else(|| format!("missing width in pen {s}"))?;
+    let line_width = line_width
+        .parse()
+        .map_err(|e| format!("invalid width {line_width}: {e}"))?;
+
+    let mut style = StrokeStyle::default();
+    for field in fields {
+        match field {
+            "dashed" => style.dash = Dash::Dashed,
+            "dotted" => style.dash = Dash::Dotted,
+            "start-arrow" => style.start_arrow = true,
+            "end-arrow" => style.end_arrow = true,
+            _ => return Err(format!("invalid style {field}")),
+        }
+    }
+
+    Ok(Pen {
+        color,
+        line_width,
+        style,
+    })
+}
+
+/// Pick the pen saved in a preset, counting from 0
+#[derive(Event)]
+pub(crate) struct UsePresetEvent(pub(crate) usize);
+
+/// Save the current pen in a preset, counting from 0
+#[derive(Event)]
+pub(crate) struct SavePresetEvent(pub(crate) usize);
+
+fn handle_use_preset_event(
+    mut events: EventReader<UsePresetEvent>,
+    presets: Res<Presets>,
+    mut chalk: ResMut<LocalChalk>,
+) {
+    for UsePresetEvent(i) in events.read() {
+        if let Some(Some(pen)) = presets.0.get(*i) {
+            chalk.set_color(pen.color);
+            chalk.set_line_width(pen.line_width);
+            chalk.set_style(pen.style);
+        }
+    }
+}
+
+fn handle_save_preset_event(
+    mut events: EventReader<SavePresetEvent>,
+    mut presets: ResMut<Presets>,
+    chalk: Res<LocalChalk>,
+) {
+    for SavePresetEvent(i) in events.read() {
+        if let Some(preset) = presets.0.get_mut(*i) {
+            *preset = Some(Pen {
+                color: chalk.color(),
+                line_width: chalk.line_width(),
+                style: chalk.style(),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pens_are_parsed() {
+        let pen = parse_pen("#ff0000:4:dashed:end-arrow").unwrap();
+        assert_eq!(pen.color, RED);
+        assert_eq!(pen.line_width, 4);
+        assert_eq!(pen.style.dash, Dash::Dashed);
+        assert!(pen.style.end_arrow && !pen.style.start_arrow);
+
+        assert_eq!(parse_pen("fff:2").unwrap().style, StrokeStyle::default());
+
+        for invalid in ["#ff0000", "#ff0000:wide", "#ff0000:4:wavy", "red:4"] {
+            assert!(parse_pen(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn pens_from_the_options_replace_the_default_ones() {
+        let pen = parse_pen("#00ff00:16").unwrap();
+
+        let presets = Presets::new(&[pen; 12]);
+        assert!(presets.0.iter().all(|x| *x == Some(pen)));
+
+        let presets = Presets::new(&[pen]);
+        assert_eq!(presets.0[0], Some(pen));
+        assert!(presets.0[1..].iter().all(Option::is_none));
+
+        assert_eq!(Presets::new(&[]).0, Presets::default().0);
+    }
+}
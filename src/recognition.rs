@@ -0,0 +1,393 @@
+#![allow(clippy::needless_pass_by_value)]
+#![allow(clippy::cast_precision_loss)]
+
+use bevy::prelude::*;
+use lavagna_collab::{simplify, ItemId, Placement, ShapeKind};
+
+use crate::drawing::{AddShapeEvent, DiscardStrokeEvent, DrawnShape, ItemIds, Polyline};
+use crate::local_chalk::{LocalChalk, LocalChalkFlag, Tool};
+use crate::Chalk;
+
+/// How long, in seconds, the chalk must be held still at the end of a stroke
+const HOLD_DURATION: f32 = 0.5;
+
+/// Moving less than this is still holding still
+const HOLD_TOLERANCE: f32 = 3.0;
+
+/// Shorter strokes are not recognized
+const MIN_POINTS: usize = 5;
+
+/// Strokes ending this close to their start, relative to their length, are closed
+const CLOSED_THRESHOLD: f32 = 0.15;
+
+/// Open strokes whose ends are this far apart, relative to their length, are lines
+const LINE_STRAIGHTNESS: f32 = 0.95;
+
+/// Corners are found by simplifying the stroke with this tolerance, relative
+/// to the diagonal of its bounding box
+const CORNER_TOLERANCE: f32 = 0.08;
+
+/// Maximum average distance from the fitted ellipse, relative to its radii
+const ELLIPSE_TOLERANCE: f32 = 0.1;
+
+/// Quadrilaterals whose sides are within this angle of the axes, in degrees,
+/// are rectangles, other ones are kept as drawn
+const RECTANGLE_ANGLE: f32 = 10.0;
+
+/// Holding the chalk still at the end of a stroke replaces a roughly drawn
+/// line, rectangle, ellipse or triangle with the clean shape
+pub(crate) struct RecognitionPlugin;
+
+impl Plugin for RecognitionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Hold>().add_systems(Update, update);
+    }
+}
+
+#[derive(Resource, Default)]
+struct Hold {
+    position: Vec2,
+    since: f32,
+    /// Recognition has been tried already for this hold
+    tried: bool,
+}
+
+fn update(
+    mut chalk: ResMut<LocalChalk>,
+    polyline_q: Query<(Entity, &Polyline), With<LocalChalkFlag>>,
+    mut hold: ResMut<Hold>,
+    time: Res<Time>,
+    mut add_shape_event: EventWriter<AddShapeEvent>,
+    mut discard_stroke_event: EventWriter<DiscardStrokeEvent>,
+    mut ids: ResMut<ItemIds>,
+) {
+    let now = time.elapsed_seconds();
+    let local = *chalk.get();
+    let position = Vec2::new(local.x as f32, local.y as f32);
+
+    let drawing = local.pressed && chalk.tool() == Tool::Chalk && !chalk.is_stroke_replaced();
+
+    if !drawing || position.distance(hold.position) > HOLD_TOLERANCE {
+        *hold = Hold {
+            position,
+            since: now,
+            tried: false,
+        };
+        return;
+    }
+
+    if hold.tried || now - hold.since < HOLD_DURATION {
+        return;
+    }
+
+    hold.tried = true;
+
+    let Ok((entity, polyline)) = polyline_q.get_single() else {
+        return;
+    };
+
+    let Some(mut shape) = recognize(&polyline.stroke(), &local) else {
+        return;
+    };
+
+    shape.id = ids.allocate();
+    chalk.replace_stroke();
+    discard_stroke_event.send(DiscardStrokeEvent(entity));
+    add_shape_event.send(AddShapeEvent::replacing_stroke(shape));
+}
+
+/// The clean shape a stroke looks like
+#[derive(Debug, PartialEq)]
+struct Outline {
+    kind: ShapeKind,
+    /// Ends of a line, or corners of the bounding box
+    from: Vec2,
+    to: Vec2,
+    /// Corners of a polygon
+    vertices: Vec<Vec2>,
+}
+
+fn recognize(points: &[Vec2], chalk: &Chalk) -> Option<DrawnShape> {
+    let Outline {
+        kind,
+        from,
+        to,
+        vertices,
+    } = outline(points)?;
+
+    Some(DrawnShape {
+        kind,
+        from,
+        to,
+        color: chalk.color,
+        line_width: chalk.line_width,
+        vertices,
+        style: chalk.style,
+        fill: None,
+        id: ItemId::default(),
+        placement: Placement::default(),
+        layer: chalk.layer,
+        page: chalk.page,
+    })
+}
+
+fn outline(points: &[Vec2]) -> Option<Outline> {
+    if points.len() < MIN_POINTS {
+        return None;
+    }
+
+    let first = points[0];
+    let last = points[points.len() - 1];
+    let length: f32 = points.windows(2).map(|w| w[0].distance(w[1])).sum();
+
+    let min = points.iter().copied().fold(first, Vec2::min);
+    let max = points.iter().copied().fold(first, Vec2::max);
+    let size = max - min;
+
+    let shape = |kind, from, to, vertices| Outline {
+        kind,
+        from,
+        to,
+        vertices,
+    };
+
+    if first.distance(last) > length * CLOSED_THRESHOLD {
+        let straight = first.distance(last) >= length * LINE_STRAIGHTNESS;
+        return straight.then(|| shape(ShapeKind::Line, first, last, Vec::new()));
+    }
+
+    if size.min_element() <= 0.0 {
+        return None;
+    }
+
+    if is_ellipse(points, min, max) {
+        return Some(shape(ShapeKind::Ellipse, min, max, Vec::new()));
+    }
+
+    let tolerance = size.length() * CORNER_TOLERANCE;
+    let xy = |p: &Vec2| (p.x, p.y);
+
+    let mut corners = simplify(points, tolerance, xy);
+
+    // The last point is close to the first one
+    corners.pop();
+
+    // The stroke may start in the middle of a side
+    if corners.len() > 3 {
+        let before = corners[corners.len() - 1];
+        if simplify(&[before, corners[0], corners[1]], tolerance, xy).len() == 2 {
+            corners.remove(0);
+        }
+    }
+
+    match corners.len() {
+        4 if is_upright(&corners) => Some(shape(ShapeKind::Rectangle, min, max, Vec::new())),
+        3 | 4 => Some(shape(ShapeKind::Polygon, min, max, corners)),
+        _ => None,
+    }
+}
+
+/// Check that every side of the closed polygon is almost horizontal or vertical
+fn is_upright(corners: &[Vec2]) -> bool {
+    let tolerance = RECTANGLE_ANGLE.to_radians();
+    let mut sides = corners.iter().zip(corners.iter().cycle().skip(1));
+
+    sides.all(|(&a, &b)| {
+        let side = (b - a).abs();
+        side.y.atan2(side.x).min(side.x.atan2(side.y)) <= tolerance
+    })
+}
+
+/// Check how close the points are to the ellipse inscribed in the bounding box
+fn is_ellipse(points: &[Vec2], min: Vec2, max: Vec2) -> bool {
+    let center = (min + max) / 2.0;
+    let radii = (max - min) / 2.0;
+
+    let deviation: f32 = points
+        .iter()
+        .map(|&p| (((p - center) / radii).length() - 1.0).abs())
+        .sum();
+
+    deviation / (points.len() as f32) < ELLIPSE_TOLERANCE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A stroke through `corners`, with `steps` points on each side
+    fn trace(corners: &[Vec2], steps: u8) -> Vec<Vec2> {
+        let mut points: Vec<_> = corners
+            .windows(2)
+            .flat_map(|w| (0..steps).map(move |i| w[0].lerp(w[1], f32::from(i) / f32::from(steps))))
+            .collect();
+        points.extend(corners.last());
+        points
+    }
+
+    /// A stroke around an ellipse, back to where it started
+    fn ellipse(center: Vec2, radii: Vec2, steps: u8) -> Vec<Vec2> {
+        (0..=steps)
+            .map(|i| {
+                let angle = std::f32::consts::TAU * f32::from(i) / f32::from(steps);
+                center + radii * Vec2::new(angle.cos(), angle.sin())
+            })
+            .collect()
+    }
+
+    fn kind(points: &[Vec2]) -> Option<ShapeKind> {
+        outline(points).map(|x| x.kind)
+    }
+
+    #[test]
+    fn short_strokes() {
+        let points = trace(&[Vec2::ZERO, Vec2::new(100.0, 0.0)], 3);
+        assert_eq!(outline(&points), None);
+    }
+
+    #[test]
+    fn line() {
+        // A bit wobbly
+        let mut points = trace(&[Vec2::ZERO, Vec2::new(100.0, 50.0)], 10);
+        points[4].y += 2.0;
+        points[7].x -= 2.0;
+
+        assert_eq!(
+            outline(&points),
+            Some(Outline {
+                kind: ShapeKind::Line,
+                from: Vec2::ZERO,
+                to: Vec2::new(100.0, 50.0),
+                vertices: Vec::new(),
+            })
+        );
+    }
+
+    #[test]
+    fn curves_are_not_lines() {
+        let arc: Vec<_> = ellipse(Vec2::ZERO, Vec2::splat(50.0), 20)
+            .into_iter()
+            .take(11)
+            .collect();
+        assert_eq!(kind(&arc), None);
+    }
+
+    #[test]
+    fn rectangle() {
+        let corners = [
+            Vec2::ZERO,
+            Vec2::new(100.0, 0.0),
+            Vec2::new(100.0, 60.0),
+            Vec2::new(0.0, 60.0),
+            Vec2::ZERO,
+        ];
+        let points = trace(&corners, 10);
+
+        assert_eq!(
+            outline(&points),
+            Some(Outline {
+                kind: ShapeKind::Rectangle,
+                from: Vec2::ZERO,
+                to: Vec2::new(100.0, 60.0),
+                vertices: Vec::new(),
+            })
+        );
+    }
+
+    #[test]
+    fn rectangle_started_in_the_middle_of_a_side() {
+        let corners = [
+            Vec2::new(50.0, 0.0),
+            Vec2::new(100.0, 0.0),
+            Vec2::new(100.0, 60.0),
+            Vec2::new(0.0, 60.0),
+            Vec2::ZERO,
+            Vec2::new(50.0, 0.0),
+        ];
+        assert_eq!(kind(&trace(&corners, 10)), Some(ShapeKind::Rectangle));
+    }
+
+    #[test]
+    fn circle_and_ellipse() {
+        let circle = ellipse(Vec2::new(10.0, 20.0), Vec2::splat(50.0), 40);
+        let outline = outline(&circle).unwrap();
+        assert_eq!(outline.kind, ShapeKind::Ellipse);
+        assert!(outline.from.abs_diff_eq(Vec2::new(-40.0, -30.0), 0.01));
+        assert!(outline.to.abs_diff_eq(Vec2::new(60.0, 70.0), 0.01));
+
+        let ellipse = ellipse(Vec2::ZERO, Vec2::new(80.0, 30.0), 40);
+        assert_eq!(kind(&ellipse), Some(ShapeKind::Ellipse));
+    }
+
+    #[test]
+    fn triangle() {
+        let corners = [
+            Vec2::ZERO,
+            Vec2::new(100.0, 0.0),
+            Vec2::new(50.0, 80.0),
+            Vec2::ZERO,
+        ];
+        let outline = outline(&trace(&corners, 10)).unwrap();
+
+        assert_eq!(outline.kind, ShapeKind::Polygon);
+        assert_eq!(outline.vertices, corners[..3]);
+    }
+
+    #[test]
+    fn tilted_rectangle() {
+        let corners = [
+            Vec2::ZERO,
+            Vec2::new(100.0, 5.0),
+            Vec2::new(97.0, 65.0),
+            Vec2::new(-3.0, 60.0),
+            Vec2::ZERO,
+        ];
+        assert_eq!(kind(&trace(&corners, 10)), Some(ShapeKind::Rectangle));
+    }
+
+    #[test]
+    fn diamond() {
+        let corners = [
+            Vec2::new(50.0, 0.0),
+            Vec2::new(100.0, 50.0),
+            Vec2::new(50.0, 100.0),
+            Vec2::new(0.0, 50.0),
+            Vec2::new(50.0, 0.0),
+        ];
+        let outline = outline(&trace(&corners, 10)).unwrap();
+
+        assert_eq!(outline.kind, ShapeKind::Polygon);
+        assert_eq!(outline.vertices, corners[..4]);
+    }
+
+    #[test]
+    fn rotated_rectangle() {
+        // 30 degrees
+        let (sin, cos) = (0.5, 0.866);
+        let corners = [
+            Vec2::ZERO,
+            Vec2::new(100.0 * cos, 100.0 * sin),
+            Vec2::new(100.0 * cos - 60.0 * sin, 100.0 * sin + 60.0 * cos),
+            Vec2::new(-60.0 * sin, 60.0 * cos),
+            Vec2::ZERO,
+        ];
+        let outline = outline(&trace(&corners, 10)).unwrap();
+
+        assert_eq!(outline.kind, ShapeKind::Polygon);
+        assert_eq!(outline.vertices, corners[..4]);
+    }
+
+    #[test]
+    fn scribbles_are_not_recognized() {
+        // Closed, but with five corners
+        let corners = [
+            Vec2::ZERO,
+            Vec2::new(100.0, 0.0),
+            Vec2::new(120.0, 60.0),
+            Vec2::new(50.0, 100.0),
+            Vec2::new(-20.0, 60.0),
+            Vec2::ZERO,
+        ];
+        assert_eq!(kind(&trace(&corners, 10)), None);
+    }
+}
@@ -0,0 +1,282 @@
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::Mutex;
+
+use futures_channel::mpsc::{UnboundedReceiver, UnboundedSender};
+use lavagna_collab::{RelayStatus, RELAY_ACTIVATE};
+
+/// Scheme prefix to force the relay, e.g. `relay+ws://example.com:3536/demo`
+const RELAY_SCHEME_PREFIX: &str = "relay+";
+
+enum Outgoing {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+enum Incoming {
+    Status(RelayStatus),
+    Payload(Vec<u8>),
+    Closed,
+}
+
+/// A plain WebSocket connection to the relay of `lavagna-server`, used when
+/// WebRTC data channels cannot be established (e.g. corporate networks).
+pub(crate) struct RelaySocket {
+    outgoing: UnboundedSender<Outgoing>,
+    incoming: Mutex<Receiver<Incoming>>,
+    status: Option<RelayStatus>,
+    closed: bool,
+    payloads: Vec<Vec<u8>>,
+}
+
+impl RelaySocket {
+    /// Connect to the relay. If not `active`, payloads are neither sent nor
+    /// received until [`RelaySocket::activate`] is called.
+    pub(crate) fn new(url: &str, active: bool) -> Self {
+        let url = if active {
+            format!("{url}?active")
+        } else {
+            url.to_owned()
+        };
+
+        let (outgoing, outgoing_rx) = futures_channel::mpsc::unbounded();
+        let (incoming_tx, incoming) = std::sync::mpsc::channel();
+
+        connect(url, outgoing_rx, incoming_tx);
+
+        Self {
+            outgoing,
+            incoming: Mutex::new(incoming),
+            status: None,
+            closed: false,
+            payloads: Vec::new(),
+        }
+    }
+
+    pub(crate) fn update(&mut self) {
+        let incoming = self
+            .incoming
+            .get_mut()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+
+        for message in incoming.try_iter() {
+            match message {
+                Incoming::Status(status) => self.status = Some(status),
+                Incoming::Payload(payload) => self.payloads.push(payload),
+                Incoming::Closed => self.closed = true,
+            }
+        }
+    }
+
+    /// Status of the room, `None` until the relay answers
+    pub(crate) fn status(&self) -> Option<RelayStatus> {
+        self.status.filter(|_| !self.closed)
+    }
+
+    pub(crate) fn activate(&self) {
+        let _ = self
+            .outgoing
+            .unbounded_send(Outgoing::Text(RELAY_ACTIVATE.to_owned()));
+    }
+
+    /// Returns `false` if the connection is closed
+    pub(crate) fn send(&self, payload: Vec<u8>) -> bool {
+        self.outgoing
+            .unbounded_send(Outgoing::Binary(payload))
+            .is_ok()
+    }
+
+    pub(crate) fn receive(&mut self) -> Vec<Vec<u8>> {
+        self.update();
+        std::mem::take(&mut self.payloads)
+    }
+}
+
+/// If the url explicitly asks for the relay, returns the relay url
+pub(crate) fn forced_relay_url(url: &str) -> Option<String> {
+    url.strip_prefix(RELAY_SCHEME_PREFIX).and_then(relay_url)
+}
+
+/// The relay is on the same server of the signaling, e.g.
+/// `ws://example.com:3536/demo` has its relay at `ws://example.com:3536/relay/demo`
+pub(crate) fn relay_url(url: &str) -> Option<String> {
+    let authority_start = url.find("://")? + 3;
+    let path_start = authority_start + url[authority_start..].find('/')?;
+    let (base, path) = url.split_at(path_start);
+    Some(format!("{base}/relay{path}"))
+}
+
+fn parse_text(text: &str) -> Option<Incoming> {
+    text.parse().ok().map(Incoming::Status)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn connect(url: String, mut outgoing: UnboundedReceiver<Outgoing>, incoming: Sender<Incoming>) {
+    use std::time::Duration;
+    use tungstenite::stream::MaybeTlsStream;
+    use tungstenite::Message;
+
+    // Reading blocks for at most this time, then pending messages are sent
+    const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+    std::thread::spawn(move || {
+        let mut ws = match tungstenite::connect(&url) {
+            Ok((ws, _)) => ws,
+            Err(e) => {
+                log::info!("cannot connect to relay {url}: {e}");
+                let _ = incoming.send(Incoming::Closed);
+                return;
+            }
+        };
+
+        let tcp = match ws.get_ref() {
+            MaybeTlsStream::Plain(s) => Some(s),
+            MaybeTlsStream::Rustls(s) => Some(s.get_ref()),
+            _ => None,
+        };
+
+        if let Some(tcp) = tcp {
+            let _ = tcp.set_read_timeout(Some(POLL_INTERVAL));
+        }
+
+        loop {
+            let message = match ws.read() {
+                Ok(Message::Binary(payload)) => Some(Incoming::Payload(payload)),
+                Ok(Message::Text(text)) => parse_text(&text),
+                Ok(_) => None,
+                Err(tungstenite::Error::Io(e))
+                    if matches!(
+                        e.kind(),
+                        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    None
+                }
+                Err(e) => {
+                    log::info!("relay disconnected: {e}");
+                    let _ = incoming.send(Incoming::Closed);
+                    return;
+                }
+            };
+
+            if let Some(message) = message {
+                if incoming.send(message).is_err() {
+                    // The socket has been dropped
+                    let _ = ws.close(None);
+                    return;
+                }
+            }
+
+            loop {
+                let message = match outgoing.try_next() {
+                    Ok(Some(Outgoing::Text(text))) => Message::Text(text),
+                    Ok(Some(Outgoing::Binary(payload))) => Message::Binary(payload),
+                    Ok(None) => {
+                        // The socket has been dropped
+                        let _ = ws.close(None);
+                        return;
+                    }
+                    Err(_) => break,
+                };
+                if let Err(e) = ws.send(message) {
+                    log::error!("cannot send to relay: {e}");
+                }
+            }
+        }
+    });
+}
+
+#[cfg(target_arch = "wasm32")]
+fn connect(url: String, mut outgoing: UnboundedReceiver<Outgoing>, incoming: Sender<Incoming>) {
+    use futures_util::StreamExt;
+    use wasm_bindgen::closure::Closure;
+    use wasm_bindgen::JsCast;
+    use web_sys::{BinaryType, MessageEvent, WebSocket};
+
+    let ws = match WebSocket::new(&url) {
+        Ok(ws) => ws,
+        Err(e) => {
+            log::info!("cannot connect to relay {url}: {e:?}");
+            let _ = incoming.send(Incoming::Closed);
+            return;
+        }
+    };
+
+    ws.set_binary_type(BinaryType::Arraybuffer);
+
+    let on_message = {
+        let incoming = incoming.clone();
+        Closure::<dyn FnMut(_)>::new(move |e: MessageEvent| {
+            let data = e.data();
+            let message = if let Some(text) = data.as_string() {
+                parse_text(&text)
+            } else if let Ok(buffer) = data.dyn_into::<js_sys::ArrayBuffer>() {
+                Some(Incoming::Payload(js_sys::Uint8Array::new(&buffer).to_vec()))
+            } else {
+                None
+            };
+            if let Some(message) = message {
+                let _ = incoming.send(message);
+            }
+        })
+    };
+
+    let on_close = Closure::<dyn FnMut()>::new(move || {
+        let _ = incoming.send(Incoming::Closed);
+    });
+
+    ws.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+    ws.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+
+    // Callbacks must live as long as the WebSocket
+    on_message.forget();
+    on_close.forget();
+
+    wasm_bindgen_futures::spawn_local(async move {
+        while let Some(message) = outgoing.next().await {
+            // Messages sent before the connection is open are lost, but
+            // nothing is sent before receiving the first status anyway.
+            if ws.ready_state() != WebSocket::OPEN {
+                continue;
+            }
+            let result = match message {
+                Outgoing::Text(text) => ws.send_with_str(&text),
+                Outgoing::Binary(payload) => ws.send_with_u8_array(&payload),
+            };
+            if let Err(e) = result {
+                log::error!("cannot send to relay: {e:?}");
+            }
+        }
+
+        // The socket has been dropped
+        let _ = ws.close();
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relay_next_to_the_signaling() {
+        assert_eq!(
+            relay_url("ws://example.com:3536/demo").as_deref(),
+            Some("ws://example.com:3536/relay/demo")
+        );
+        assert_eq!(
+            relay_url("wss://example.com/a/b").as_deref(),
+            Some("wss://example.com/relay/a/b")
+        );
+        assert_eq!(relay_url("ws://example.com"), None);
+        assert_eq!(relay_url("example.com/demo"), None);
+    }
+
+    #[test]
+    fn relay_forced_by_the_scheme() {
+        assert_eq!(
+            forced_relay_url("relay+ws://example.com:3536/demo").as_deref(),
+            Some("ws://example.com:3536/relay/demo")
+        );
+        assert_eq!(forced_relay_url("ws://example.com:3536/demo"), None);
+        assert_eq!(forced_relay_url("relay+ws://example.com"), None);
+    }
+}
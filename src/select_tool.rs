@@ -0,0 +1,379 @@
+#![allow(clippy::needless_pass_by_value)]
+#![allow(clippy::cast_precision_loss)]
+
+use bevy::color::palettes::css::DEEP_SKY_BLUE;
+use bevy::prelude::*;
+use bevy::render::primitives::Aabb;
+use bevy_prototype_lyon::prelude::*;
+use lavagna_collab::{ItemId, Placement};
+
+use crate::drawing::{
+    DeleteItemsEvent, DuplicateItemsEvent, Item, ItemIds, TransformItemsEvent, PENDING_Z,
+};
+use crate::layers::Layers;
+use crate::local_chalk::{LocalChalk, Tool};
+use crate::pages::Pages;
+use crate::MainCamera;
+
+/// Size of the handles, in pixels
+const HANDLE_SIZE: f32 = 8.0;
+
+/// Distance of the rotation handle from the top of the selection, in pixels
+const ROTATION_HANDLE_DISTANCE: f32 = 24.0;
+
+/// Scaling below this would make the selection too small to grab again
+const MIN_SCALE: f32 = 0.05;
+
+/// Where copies go, relative to the originals
+const DUPLICATE_OFFSET: Vec2 = Vec2::new(20.0, -20.0);
+
+/// Select items by dragging a rectangle over them, or by clicking on them.
+/// The selection is moved by dragging it, scaled by dragging its corners and
+/// rotated by dragging the handle above it. Items on hidden or locked
+/// layers cannot be selected.
+pub(crate) struct SelectToolPlugin;
+
+impl Plugin for SelectToolPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Selection>()
+            .add_event::<DeleteSelectionEvent>()
+            .add_event::<DuplicateSelectionEvent>()
+            .add_systems(Startup, startup)
+            .add_systems(Update, update)
+            .add_systems(Update, draw_frame.after(update))
+            .add_systems(Update, handle_delete_selection_event)
+            .add_systems(Update, handle_duplicate_selection_event);
+    }
+}
+
+#[derive(Resource, Default)]
+pub(crate) struct Selection {
+    items: Vec<ItemId>,
+    drag: Option<Drag>,
+}
+
+impl Selection {
+    pub(crate) fn items(&self) -> &[ItemId] {
+        &self.items
+    }
+
+    pub(crate) fn select(&mut self, items: Vec<ItemId>) {
+        self.items = items;
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Action {
+    /// Drag a rectangle over the items to select
+    Select,
+    Move,
+    Scale,
+    Rotate,
+}
+
+#[derive(Clone, Copy)]
+struct Drag {
+    action: Action,
+    from: Vec2,
+    to: Vec2,
+    /// Center of the selection when the drag started
+    pivot: Vec2,
+}
+
+impl Drag {
+    /// How the selection is placed by the drag, `None` when selecting
+    fn placement(&self) -> Option<Placement> {
+        let pivot = self.pivot.into();
+        let (from, to) = (self.from - self.pivot, self.to - self.pivot);
+
+        match self.action {
+            Action::Select => None,
+            Action::Move => Some(Placement {
+                translation: (self.to - self.from).into(),
+                ..default()
+            }),
+            Action::Scale => {
+                let scale = (to.length() / from.length().max(1.0)).max(MIN_SCALE);
+                Some(Placement::around(pivot, (0.0, 0.0), 0.0, scale))
+            }
+            Action::Rotate => {
+                let angle = to.y.atan2(to.x) - from.y.atan2(from.x);
+                Some(Placement::around(pivot, (0.0, 0.0), angle, 1.0))
+            }
+        }
+    }
+}
+
+/// The rectangle of the current selection, with its handles
+#[derive(Component)]
+struct Frame;
+
+fn startup(mut commands: Commands) {
+    let transform = Transform {
+        translation: Vec3::new(0., 0., PENDING_Z),
+        ..default()
+    };
+
+    commands.spawn((
+        Frame,
+        ShapeBundle {
+            path: PathBuilder::new().build(),
+            spatial: SpatialBundle {
+                transform,
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            ..default()
+        },
+        Stroke::new(DEEP_SKY_BLUE, 1.0),
+        Fill::color(Color::NONE),
+    ));
+}
+
+/// Bounding box of an item, in world coordinates
+pub(crate) fn world_rect(transform: &Transform, aabb: &Aabb) -> Rect {
+    let center = aabb.center.truncate();
+    let half = aabb.half_extents.truncate();
+
+    let corners = [(-1.0, -1.0), (-1.0, 1.0), (1.0, -1.0), (1.0, 1.0)].map(|(x, y)| {
+        let corner = center + half * Vec2::new(x, y);
+        transform.transform_point(corner.extend(0.0)).truncate()
+    });
+
+    corners
+        .into_iter()
+        .fold(Rect::from_corners(corners[0], corners[0]), |rect, p| {
+            rect.union_point(p)
+        })
+}
+
+/// Unlike `Rect::intersect`, also true for rectangles of zero size
+fn overlaps(a: Rect, b: Rect) -> bool {
+    a.min.cmple(b.max).all() && b.min.cmple(a.max).all()
+}
+
+fn selection_rect<'a>(
+    selection: &[ItemId],
+    items: impl Iterator<Item = (&'a Item, &'a Transform, &'a Aabb)>,
+) -> Option<Rect> {
+    items
+        .filter(|(item, ..)| selection.contains(&item.id))
+        .map(|(_, transform, aabb)| world_rect(transform, aabb))
+        .reduce(|a, b| a.union(b))
+}
+
+fn rotation_handle(rect: Rect, scale: f32) -> Vec2 {
+    Vec2::new(
+        rect.center().x,
+        rect.max.y + ROTATION_HANDLE_DISTANCE * scale,
+    )
+}
+
+fn corners(rect: Rect) -> [Vec2; 4] {
+    [
+        rect.min,
+        Vec2::new(rect.min.x, rect.max.y),
+        rect.max,
+        Vec2::new(rect.max.x, rect.min.y),
+    ]
+}
+
+/// What the drag does depends on where it starts
+fn start_drag(position: Vec2, selection: Option<Rect>, scale: f32) -> Drag {
+    let near = |p: Vec2| p.distance(position) < HANDLE_SIZE * scale;
+
+    let action = match selection {
+        Some(rect) if near(rotation_handle(rect, scale)) => Action::Rotate,
+        Some(rect) if corners(rect).into_iter().any(near) => Action::Scale,
+        Some(rect) if rect.contains(position) => Action::Move,
+        _ => Action::Select,
+    };
+
+    Drag {
+        action,
+        from: position,
+        to: position,
+        pivot: selection.map_or(position, |rect| rect.center()),
+    }
+}
+
+fn update(
+    chalk: Res<LocalChalk>,
+    layers: Res<Layers>,
+    pages: Res<Pages>,
+    mut selection: ResMut<Selection>,
+    mut item_q: Query<(&mut Item, &Transform, &Aabb)>,
+    camera_q: Query<&OrthographicProjection, With<MainCamera>>,
+    mut transform_items_event: EventWriter<TransformItemsEvent>,
+) {
+    if chalk.tool() != Tool::Select {
+        if selection.drag.take().is_some() {
+            reset_preview(&mut item_q);
+        }
+        selection.items.clear();
+        return;
+    }
+
+    if layers.is_changed() || pages.is_changed() {
+        let editable: Vec<_> = item_q
+            .iter()
+            .filter(|(item, ..)| layers.is_editable(item.layer) && pages.is_shown(item.page))
+            .map(|(item, ..)| item.id)
+            .collect();
+        selection.items.retain(|id| editable.contains(id));
+    }
+
+    let local = chalk.get();
+    let position = Vec2::new(local.x as f32, local.y as f32);
+    let scale = camera_q
+        .get_single()
+        .map_or(1.0, |projection| projection.scale);
+
+    if local.pressed {
+        let drag = if let Some(drag) = selection.drag {
+            Drag {
+                to: position,
+                ..drag
+            }
+        } else {
+            let rect = selection_rect(&selection.items, item_q.iter());
+            start_drag(position, rect, scale)
+        };
+
+        selection.drag = Some(drag);
+
+        if let Some(placement) = drag.placement() {
+            for (mut item, ..) in &mut item_q {
+                if selection.items.contains(&item.id) {
+                    item.preview = placement;
+                }
+            }
+        }
+    } else if let Some(drag) = selection.drag.take() {
+        if let Some(placement) = drag.placement() {
+            reset_preview(&mut item_q);
+            if !placement.is_identity() && !selection.items.is_empty() {
+                transform_items_event
+                    .send(TransformItemsEvent::new(selection.items.clone(), placement));
+            }
+        } else {
+            let area = Rect::from_corners(drag.from, drag.to);
+            let mut items: Vec<_> = item_q
+                .iter()
+                .filter(|(item, ..)| layers.is_editable(item.layer) && pages.is_shown(item.page))
+                .filter(|(_, transform, aabb)| overlaps(area, world_rect(transform, aabb)))
+                .map(|(item, ..)| item.id)
+                .collect();
+            // Chunks of long strokes share the same id
+            items.sort_unstable_by_key(|id| (id.src.0, id.n));
+            items.dedup();
+            selection.items = items;
+        }
+    }
+}
+
+fn reset_preview(item_q: &mut Query<(&mut Item, &Transform, &Aabb)>) {
+    for (mut item, ..) in item_q {
+        if !item.preview.is_identity() {
+            item.preview = Placement::default();
+        }
+    }
+}
+
+fn draw_frame(
+    selection: Res<Selection>,
+    item_q: Query<(&Item, &Transform, &Aabb)>,
+    camera_q: Query<&OrthographicProjection, With<MainCamera>>,
+    mut frame_q: Query<(&mut Path, &mut Stroke, &mut Visibility), With<Frame>>,
+) {
+    let (mut path, mut stroke, mut visibility) = frame_q.single_mut();
+
+    let scale = camera_q
+        .get_single()
+        .map_or(1.0, |projection| projection.scale);
+
+    let marquee = selection
+        .drag
+        .filter(|drag| drag.action == Action::Select)
+        .map(|drag| Rect::from_corners(drag.from, drag.to));
+
+    let rect = |rect: Rect| shapes::Rectangle {
+        extents: rect.size(),
+        origin: RectangleOrigin::CustomCenter(rect.center()),
+    };
+
+    let mut builder = GeometryBuilder::new();
+
+    if let Some(marquee) = marquee {
+        builder = builder.add(&rect(marquee));
+    } else if let Some(bounds) = selection_rect(&selection.items, item_q.iter()) {
+        let handle_size = Vec2::splat(HANDLE_SIZE * scale);
+        let rotation_handle = rotation_handle(bounds, scale);
+
+        builder = builder
+            .add(&rect(bounds))
+            .add(&shapes::Line(
+                Vec2::new(bounds.center().x, bounds.max.y),
+                rotation_handle,
+            ))
+            .add(&shapes::Circle {
+                radius: HANDLE_SIZE * scale / 2.0,
+                center: rotation_handle,
+            });
+
+        for corner in corners(bounds) {
+            builder = builder.add(&rect(Rect::from_center_size(corner, handle_size)));
+        }
+    } else {
+        *visibility = Visibility::Hidden;
+        return;
+    }
+
+    *path = builder.build();
+    *stroke = Stroke::new(DEEP_SKY_BLUE, 1.5 * scale);
+    *visibility = Visibility::Visible;
+}
+
+#[derive(Event)]
+pub(crate) struct DeleteSelectionEvent;
+
+#[derive(Event)]
+pub(crate) struct DuplicateSelectionEvent;
+
+fn handle_delete_selection_event(
+    mut events: EventReader<DeleteSelectionEvent>,
+    mut selection: ResMut<Selection>,
+    mut delete_items_event: EventWriter<DeleteItemsEvent>,
+) {
+    for _ in events.read() {
+        let items = std::mem::take(&mut selection.items);
+        if !items.is_empty() {
+            delete_items_event.send(DeleteItemsEvent::new(items));
+        }
+    }
+}
+
+/// The copies are selected, so they can be moved right away
+fn handle_duplicate_selection_event(
+    mut events: EventReader<DuplicateSelectionEvent>,
+    mut selection: ResMut<Selection>,
+    mut ids: ResMut<ItemIds>,
+    mut duplicate_items_event: EventWriter<DuplicateItemsEvent>,
+) {
+    for _ in events.read() {
+        if selection.items.is_empty() {
+            continue;
+        }
+
+        let items = std::mem::take(&mut selection.items);
+        let first = ids.reserve(items.len());
+        let placement = Placement {
+            translation: DUPLICATE_OFFSET.into(),
+            ..default()
+        };
+
+        let event = DuplicateItemsEvent::new(items, first, placement);
+        selection.items = event.copies().collect();
+        duplicate_items_event.send(event);
+    }
+}
@@ -0,0 +1,185 @@
+#![allow(clippy::needless_pass_by_value)]
+#![allow(clippy::cast_precision_loss)]
+
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+use lavagna_collab::{Background, ItemId, Placement, ShapeKind};
+
+use crate::background::{paper_spacing, snap_to_paper, Paper};
+use crate::drawing::{AddShapeEvent, DrawnShape, ItemIds, PENDING_Z};
+use crate::local_chalk::LocalChalk;
+use crate::theme::Theme;
+use crate::MainCamera;
+
+/// Lines snap to multiples of this angle, in degrees
+const SNAP_ANGLE: f32 = 15.0;
+
+/// Draw shapes by dragging, with a rubber band preview of the shape. While
+/// shift is held, shapes start on a corner of the paper shown, and lines and
+/// arrows are at multiples of `SNAP_ANGLE`, other shapes end on a corner too.
+pub(crate) struct ShapeToolPlugin;
+
+impl Plugin for ShapeToolPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Drag>()
+            .add_systems(Startup, startup)
+            .add_systems(Update, update);
+    }
+}
+
+/// Where the current drag started
+#[derive(Resource, Default)]
+struct Drag(Option<Vec2>);
+
+#[derive(Component)]
+struct Preview;
+
+fn startup(mut commands: Commands) {
+    let transform = Transform {
+        translation: Vec3::new(0., 0., PENDING_Z),
+        ..default()
+    };
+
+    commands.spawn((
+        Preview,
+        ShapeBundle {
+            path: PathBuilder::new().build(),
+            spatial: SpatialBundle {
+                transform,
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            ..default()
+        },
+        Stroke::new(Color::WHITE, 1.0),
+        Fill::color(Color::NONE),
+    ));
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update(
+    chalk: Res<LocalChalk>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    paper: Res<Paper>,
+    camera_q: Query<&OrthographicProjection, With<MainCamera>>,
+    theme: Res<Theme>,
+    mut drag: ResMut<Drag>,
+    mut preview_q: Query<(&mut Path, &mut Stroke, &mut Visibility), With<Preview>>,
+    mut add_shape_event: EventWriter<AddShapeEvent>,
+    mut ids: ResMut<ItemIds>,
+) {
+    let (mut path, mut stroke, mut visibility) = preview_q.single_mut();
+
+    let Some(kind) = chalk.tool().shape().filter(|_| !chalk.layer_locked()) else {
+        drag.0 = None;
+        *visibility = Visibility::Hidden;
+        return;
+    };
+
+    let chalk = chalk.get();
+    let position = Vec2::new(chalk.x as f32, chalk.y as f32);
+    let snap = keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    let spacing = paper_spacing(camera_q.get_single().map_or(1.0, |p| p.scale));
+
+    let shape = |from: Vec2| DrawnShape {
+        kind,
+        from,
+        to: if snap {
+            snap_end(kind, paper.background(), spacing, from, position)
+        } else {
+            position
+        },
+        color: chalk.color,
+        line_width: chalk.line_width,
+        vertices: Vec::new(),
+        style: chalk.style,
+        fill: None,
+        id: ItemId::default(),
+        placement: Placement::default(),
+        layer: chalk.layer,
+        page: chalk.page,
+    };
+
+    if chalk.pressed {
+        let start = if snap {
+            snap_to_paper(paper.background(), spacing, position)
+        } else {
+            position
+        };
+        let from = *drag.0.get_or_insert(start);
+        let shape = shape(from);
+        *path = (&shape).into();
+        *stroke = shape.stroke(*theme);
+        *visibility = Visibility::Visible;
+    } else if let Some(from) = drag.0.take() {
+        add_shape_event.send(AddShapeEvent::new(DrawnShape {
+            id: ids.allocate(),
+            ..shape(from)
+        }));
+        *visibility = Visibility::Hidden;
+    }
+}
+
+/// Where a shape from `from` ends when snapping to `to`, on the paper drawn
+/// with lines `spacing` apart
+fn snap_end(kind: ShapeKind, background: Background, spacing: f32, from: Vec2, to: Vec2) -> Vec2 {
+    match kind {
+        ShapeKind::Line | ShapeKind::Arrow => {
+            let step = SNAP_ANGLE.to_radians();
+            let (dx, dy) = (to - from).into();
+            let angle = (dy.atan2(dx) / step).round() * step;
+            from + Vec2::from_angle(angle) * from.distance(to)
+        }
+        ShapeKind::Rectangle | ShapeKind::Ellipse | ShapeKind::Polygon => {
+            snap_to_paper(background, spacing, to)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::background::SPACING;
+
+    #[test]
+    fn lines_snap_to_angles() {
+        let from = Vec2::new(10.0, 20.0);
+
+        // Almost horizontal, and almost diagonal
+        for (to, angle) in [
+            (Vec2::new(110.0, 30.0), 0.0),
+            (Vec2::new(110.0, 110.0), 45.0),
+        ] {
+            for kind in [ShapeKind::Line, ShapeKind::Arrow] {
+                let end = snap_end(kind, Background::Plain, SPACING, from, to);
+                let expected = from + Vec2::from_angle(f32::to_radians(angle)) * from.distance(to);
+                assert!(end.abs_diff_eq(expected, 0.001), "{end} != {expected}");
+            }
+        }
+    }
+
+    #[test]
+    fn angles_are_multiples_of_15_degrees() {
+        for step in 0..72u16 {
+            let to = Vec2::from_angle(f32::from(step * 5).to_radians()) * 50.0;
+            let end = snap_end(ShapeKind::Line, Background::Plain, SPACING, Vec2::ZERO, to);
+
+            let angle = end.y.atan2(end.x).to_degrees() / SNAP_ANGLE;
+            assert!((angle - angle.round()).abs() < 0.001, "{end}");
+            assert!(to.angle_between(end).to_degrees().abs() <= SNAP_ANGLE / 2.0 + 0.001);
+            assert!((end.length() - 50.0).abs() < 0.001);
+        }
+    }
+
+    #[test]
+    fn other_shapes_snap_to_the_paper() {
+        let from = Vec2::new(10.0, 20.0);
+        let to = Vec2::new(55.0, -25.0);
+        for kind in [ShapeKind::Rectangle, ShapeKind::Ellipse, ShapeKind::Polygon] {
+            assert_eq!(
+                snap_end(kind, Background::Grid, SPACING, from, to),
+                Vec2::new(40.0, -40.0)
+            );
+        }
+    }
+}
@@ -0,0 +1,158 @@
+#![allow(clippy::needless_pass_by_value)]
+#![allow(clippy::cast_precision_loss)]
+
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::input::ButtonState;
+use bevy::prelude::*;
+use lavagna_collab::Placement;
+
+use crate::drawing::{label_bundle, AddLabelEvent, DrawnLabel, ItemIds, PENDING_Z};
+use crate::local_chalk::{LocalChalk, Tool};
+use crate::theme::Theme;
+use crate::ui::default_font;
+
+/// Shown at the end of the text being typed
+const CARET: char = '|';
+
+/// Click to place a caret, then type. Enter confirms, escape cancels.
+pub(crate) struct TextToolPlugin;
+
+impl Plugin for TextToolPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TextEditor>()
+            .add_systems(Update, place_caret)
+            .add_systems(Update, type_text.after(place_caret));
+    }
+}
+
+/// The label being typed, if any
+#[derive(Resource, Default)]
+pub(crate) struct TextEditor {
+    editing: Option<Editing>,
+    was_pressed: bool,
+}
+
+impl TextEditor {
+    /// While typing, keys must not trigger any key binding
+    pub(crate) fn is_editing(&self) -> bool {
+        self.editing.is_some()
+    }
+}
+
+struct Editing {
+    /// Preview of the label, with the caret
+    entity: Entity,
+    label: DrawnLabel,
+}
+
+impl Editing {
+    fn finish(self, commands: &mut Commands, add_label_event: &mut EventWriter<AddLabelEvent>) {
+        commands.entity(self.entity).despawn();
+
+        if !self.label.content.trim().is_empty() {
+            add_label_event.send(AddLabelEvent::new(self.label));
+        }
+    }
+}
+
+/// Font size for a line width
+fn font_size(line_width: u32) -> f32 {
+    (line_width as f32 * 4.0).clamp(12.0, 200.0)
+}
+
+fn place_caret(
+    mut commands: Commands,
+    chalk: Res<LocalChalk>,
+    mut editor: ResMut<TextEditor>,
+    asset_server: Res<AssetServer>,
+    mut add_label_event: EventWriter<AddLabelEvent>,
+    mut ids: ResMut<ItemIds>,
+    theme: Res<Theme>,
+) {
+    let local = chalk.get();
+    let just_pressed = local.pressed && !editor.was_pressed;
+    editor.was_pressed = local.pressed;
+
+    // Clicking somewhere else, or changing tool, confirms the current text
+    if chalk.tool() != Tool::Text {
+        if let Some(editing) = editor.editing.take() {
+            editing.finish(&mut commands, &mut add_label_event);
+        }
+        return;
+    }
+
+    if !just_pressed || chalk.layer_locked() {
+        return;
+    }
+
+    if let Some(editing) = editor.editing.take() {
+        editing.finish(&mut commands, &mut add_label_event);
+    }
+
+    let label = DrawnLabel {
+        content: String::new(),
+        position: Vec2::new(local.x as f32, local.y as f32),
+        size: font_size(local.line_width),
+        color: local.color,
+        id: ids.allocate(),
+        placement: Placement::default(),
+        layer: local.layer,
+        page: local.page,
+    };
+
+    let mut preview = label_bundle(&label, default_font(&asset_server), PENDING_Z, *theme);
+    preview.text.sections[0].value = CARET.into();
+
+    let entity = commands.spawn(preview).id();
+
+    editor.editing = Some(Editing { entity, label });
+}
+
+fn type_text(
+    mut commands: Commands,
+    mut keyboard_events: EventReader<KeyboardInput>,
+    mut editor: ResMut<TextEditor>,
+    mut text_q: Query<&mut Text>,
+    mut add_label_event: EventWriter<AddLabelEvent>,
+) {
+    let Some(editing) = &mut editor.editing else {
+        keyboard_events.clear();
+        return;
+    };
+
+    let mut done = false;
+    let mut cancelled = false;
+
+    for event in keyboard_events.read() {
+        if event.state != ButtonState::Pressed {
+            continue;
+        }
+
+        let content = &mut editing.label.content;
+
+        match &event.logical_key {
+            Key::Character(s) => content.push_str(s),
+            Key::Space => content.push(' '),
+            Key::Backspace => {
+                content.pop();
+            }
+            Key::Enter => done = true,
+            Key::Escape => cancelled = true,
+            _ => {}
+        }
+    }
+
+    if let Ok(mut text) = text_q.get_mut(editing.entity) {
+        text.sections[0].value = format!("{}{CARET}", editing.label.content);
+    }
+
+    if cancelled {
+        editing.label.content.clear();
+    }
+
+    if done || cancelled {
+        if let Some(editing) = editor.editing.take() {
+            editing.finish(&mut commands, &mut add_label_event);
+        }
+    }
+}
@@ -0,0 +1,159 @@
+#![allow(clippy::needless_pass_by_value)]
+
+use std::fmt;
+use std::str::FromStr;
+
+use bevy::color::palettes::css::{
+    AQUA, BLACK, BLUE, DARK_CYAN, DARK_ORANGE, FUCHSIA, GREEN, LIME, ORANGE, PURPLE, RED,
+    TURQUOISE, WHITE, YELLOW,
+};
+use bevy::prelude::*;
+
+/// Colors closer than this in each channel are the same
+const COLOR_TOLERANCE: f32 = 0.01;
+
+/// The look of the board: its color, the palette of the chalk and the colors
+/// of the toolbar and panels. Ink is stored as drawn on the blackboard and
+/// shown through [`Theme::ink`], so peers with different themes see the same
+/// drawing, each readable on their own board.
+#[derive(Debug, Resource, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Theme {
+    #[default]
+    Blackboard,
+    /// Dark ink on a light board, for bright rooms and printing
+    Whiteboard,
+    /// Saturated ink and opaque controls on a black board
+    HighContrast,
+}
+
+impl Theme {
+    pub const ALL: [Self; 3] = [Self::Blackboard, Self::Whiteboard, Self::HighContrast];
+
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Blackboard => "blackboard",
+            Self::Whiteboard => "whiteboard",
+            Self::HighContrast => "high-contrast",
+        }
+    }
+
+    fn board(self) -> Srgba {
+        match self {
+            Self::Blackboard | Self::HighContrast => BLACK,
+            Self::Whiteboard => Srgba::rgb(0.98, 0.98, 0.96),
+        }
+    }
+
+    /// Colors of the chalk, as shown on the board, unless the palette is
+    /// chosen with options
+    pub(crate) fn palette(self) -> &'static [Srgba] {
+        match self {
+            Self::Blackboard => &[WHITE, BLUE, TURQUOISE, GREEN, YELLOW, ORANGE, RED],
+            Self::Whiteboard => &[BLACK, BLUE, DARK_CYAN, GREEN, PURPLE, DARK_ORANGE, RED],
+            Self::HighContrast => &[WHITE, YELLOW, AQUA, LIME, FUCHSIA, ORANGE, RED],
+        }
+    }
+
+    /// How ink is shown with this theme: on light boards white and black are
+    /// swapped, like every shade of gray. Applied twice it gives the ink back,
+    /// so it also turns a color picked on the board into ink.
+    pub(crate) fn ink(self, color: Srgba) -> Srgba {
+        let gray = (color.red - color.green).abs() < COLOR_TOLERANCE
+            && (color.green - color.blue).abs() < COLOR_TOLERANCE;
+
+        match self {
+            Self::Whiteboard if gray => Srgba {
+                red: 1.0 - color.red,
+                green: 1.0 - color.green,
+                blue: 1.0 - color.blue,
+                ..color
+            },
+            _ => color,
+        }
+    }
+
+    pub(crate) fn ui_text(self) -> Srgba {
+        match self {
+            Self::Blackboard | Self::HighContrast => WHITE,
+            Self::Whiteboard => BLACK,
+        }
+    }
+
+    pub(crate) fn ui_button(self) -> Srgba {
+        match self {
+            Self::Blackboard => Srgba::new(0.2, 0.2, 0.2, 0.75),
+            Self::Whiteboard => Srgba::new(0.85, 0.85, 0.85, 0.75),
+            Self::HighContrast => BLACK,
+        }
+    }
+
+    /// Buttons showing what is in use, like the active layer
+    pub(crate) fn ui_active(self) -> Srgba {
+        match self {
+            Self::Blackboard => Srgba::new(0.4, 0.4, 0.4, 0.9),
+            Self::Whiteboard => Srgba::new(0.65, 0.65, 0.65, 0.9),
+            Self::HighContrast => Srgba::rgb(0.0, 0.0, 0.6),
+        }
+    }
+}
+
+impl fmt::Display for Theme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Theme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|x| x.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                let names: Vec<_> = Self::ALL.iter().map(|x| x.name()).collect();
+                format!("unknown theme {s}, expected one of {}", names.join(", "))
+            })
+    }
+}
+
+/// Each peer chooses their own theme, it is not shared
+pub(crate) struct ThemePlugin {
+    theme: Theme,
+}
+
+impl ThemePlugin {
+    pub(crate) fn new(theme: Theme) -> Self {
+        Self { theme }
+    }
+}
+
+impl Plugin for ThemePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.theme)
+            .add_event::<NextThemeEvent>()
+            .add_systems(Update, handle_next_theme_event)
+            .add_systems(Update, apply_theme.after(handle_next_theme_event));
+    }
+}
+
+/// Switch to the next theme
+#[derive(Event)]
+pub(crate) struct NextThemeEvent;
+
+fn handle_next_theme_event(mut events: EventReader<NextThemeEvent>, mut theme: ResMut<Theme>) {
+    for _ in events.read() {
+        let all = Theme::ALL;
+        let i = all.iter().position(|x| x == &*theme).unwrap_or(0);
+        *theme = all[(i + 1) % all.len()];
+    }
+}
+
+fn apply_theme(theme: Res<Theme>, mut clear_color: ResMut<ClearColor>) {
+    if !theme.is_changed() {
+        return;
+    }
+
+    clear_color.0 = theme.board().into();
+}
@@ -1,9 +1,20 @@
 #![allow(clippy::type_complexity)]
 #![allow(clippy::needless_pass_by_value)]
 
+use bevy::color::Luminance;
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::input::ButtonState;
 use bevy::prelude::*;
+use bevy::ui::RelativeCursorPosition;
+use lavagna_collab::{Dash, StrokeStyle};
 
-use crate::{drawing::ClearEvent, local_chalk::LocalChalk, Stats};
+use crate::{
+    drawing::ClearEvent,
+    local_chalk::{LocalChalk, Tool, MAX_CHALK_WIDTH},
+    palette::{Palette, PickColorEvent, SelectSlotEvent},
+    theme::Theme,
+    MainCamera, Stats,
+};
 
 #[derive(Copy, Clone, Resource)]
 struct UiPluginOpt {
@@ -25,13 +36,27 @@ pub(crate) fn default_font(asset_server: &Res<AssetServer>) -> Handle<Font> {
 impl Plugin for UiPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
         app.insert_resource(self.0)
+            .init_resource::<ColorPicker>()
             .add_systems(Startup, setup)
+            .add_systems(Startup, setup_picker)
             .add_event::<ToggleUiEvent>()
-            .add_systems(Update, toggle_ui_system)
+            .add_event::<TogglePickerEvent>()
+            .add_systems(Update, toggle_picker)
+            .add_systems(Update, toggle_visibility::<TogglePickerEvent, PickerPanel>)
+            .add_systems(Update, draw_picker)
+            .add_systems(Update, picker_btn_system)
+            .add_systems(Update, type_hex.after(picker_btn_system))
+            .add_systems(Update, update_hex_text.after(type_hex))
+            .add_systems(Update, toggle_visibility::<ToggleUiEvent, Toolbar>)
             .add_systems(Update, color_btn_system)
+            .add_systems(Update, tool_btn_system)
+            .add_systems(Update, style_btn_system)
             .add_systems(Update, clear_btn_system)
             .add_systems(Update, incr_btn_system)
+            .add_systems(Update, width_slider_system)
+            .add_systems(Update, update_width_ui.after(width_slider_system))
             .add_systems(Update, update_collab_info)
+            .add_systems(Update, apply_theme)
             .add_systems(Update, decr_btn_system);
     }
 }
@@ -40,8 +65,14 @@ impl Plugin for UiPlugin {
 struct Toolbar;
 
 // Add a green button in the bottom left corner
-fn setup(mut commands: Commands, asset_server: Res<AssetServer>, opt: Res<UiPluginOpt>) {
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    opt: Res<UiPluginOpt>,
+    theme: Res<Theme>,
+) {
     let font = default_font(&asset_server);
+    let theme = *theme;
 
     let visibility = if opt.visible {
         Visibility::Visible
@@ -67,75 +98,149 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>, opt: Res<UiPlug
             },
         ))
         .with_children(|parent| {
-            parent.spawn((ColorButton, button()));
+            parent.spawn((ColorButton, button(theme)));
         })
         .with_children(|parent| {
             parent
-                .spawn((DecrementButton, button()))
+                .spawn((ToolButton, button(theme)))
                 .with_children(|parent| {
-                    parent.spawn(label("-", &font));
+                    parent.spawn((ToolText, label(Tool::default().label(), &font, theme)));
                 });
         })
         .with_children(|parent| {
             parent
-                .spawn((IncrementButton, button()))
+                .spawn((StyleButton, button(theme)))
                 .with_children(|parent| {
-                    parent.spawn(label("+", &font));
+                    parent.spawn((
+                        StyleText,
+                        label(style_label(StrokeStyle::default()), &font, theme),
+                    ));
                 });
         })
         .with_children(|parent| {
             parent
-                .spawn((ClearButton, button()))
+                .spawn((DecrementButton, button(theme)))
                 .with_children(|parent| {
-                    parent.spawn(label("x", &font));
+                    parent.spawn(label("-", &font, theme));
                 });
         })
+        .with_children(|parent| width_slider(parent, &font, theme))
         .with_children(|parent| {
-            parent.spawn(button()).with_children(|parent| {
-                parent.spawn((CollabText, label(" ", &font)));
+            parent
+                .spawn((IncrementButton, button(theme)))
+                .with_children(|parent| {
+                    parent.spawn(label("+", &font, theme));
+                });
+        })
+        .with_children(|parent| {
+            parent
+                .spawn((ClearButton, button(theme)))
+                .with_children(|parent| {
+                    parent.spawn(label("x", &font, theme));
+                });
+        })
+        .with_children(|parent| {
+            parent.spawn(button(theme)).with_children(|parent| {
+                parent.spawn((CollabText, label(" ", &font, theme)));
             });
         });
 }
 
-fn label(text: &str, font: &Handle<Font>) -> TextBundle {
+/// The slider, with the width written on it, and the preview of the width
+fn width_slider(parent: &mut ChildBuilder, font: &Handle<Font>, theme: Theme) {
+    parent
+        .spawn((
+            WidthSlider,
+            RelativeCursorPosition::default(),
+            button(theme),
+        ))
+        .insert(Style {
+            width: Val::Px(SLIDER_WIDTH),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                WidthFill,
+                NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        left: Val::Px(0.),
+                        height: Val::Percent(100.),
+                        ..default()
+                    },
+                    ..default()
+                },
+            ));
+            parent.spawn((WidthText, label(" ", font, theme)));
+        });
+
+    parent.spawn(button(theme)).with_children(|parent| {
+        parent.spawn((
+            WidthPreview,
+            NodeBundle {
+                border_radius: BorderRadius::MAX,
+                ..default()
+            },
+        ));
+    });
+}
+
+/// Painted with the colors of the theme, again when it changes
+#[derive(Component)]
+struct Themed;
+
+fn label(text: &str, font: &Handle<Font>, theme: Theme) -> impl Bundle {
     const FONT_SIZE: f32 = 40.0;
 
-    TextBundle {
+    let label = TextBundle {
         text: Text::from_section(
             text,
             TextStyle {
                 font: font.clone(),
                 font_size: FONT_SIZE,
-                color: Color::WHITE,
+                color: theme.ui_text().into(),
             },
         ),
         ..default()
-    }
+    };
+
+    (label, Themed)
 }
 
-fn button() -> ButtonBundle {
+fn button(theme: Theme) -> impl Bundle {
     const BTN_WIDTH: f32 = 50.0;
 
-    ButtonBundle {
+    let button = ButtonBundle {
         style: Style {
             width: Val::Px(BTN_WIDTH),
             justify_content: JustifyContent::Center,
             align_items: AlignItems::Center,
             ..default()
         },
-        background_color: BackgroundColor(Color::Srgba(Srgba {
-            red: 0.2,
-            green: 0.2,
-            blue: 0.2,
-            alpha: 0.75,
-        })),
+        background_color: BackgroundColor(theme.ui_button().into()),
         ..default()
-    }
+    };
+
+    (button, Themed)
 }
 
 #[derive(Component)]
 struct ColorButton;
 
+#[derive(Component)]
+struct ToolButton;
+
+#[derive(Component)]
+struct ToolText;
+
+#[derive(Component)]
+struct StyleButton;
+
+#[derive(Component)]
+struct StyleText;
+
 #[derive(Component)]
 struct IncrementButton;
 
@@ -145,21 +250,91 @@ struct DecrementButton;
 #[derive(Component)]
 struct ClearButton;
 
+/// Drag to change the width, from 1 to the widest chalk
+#[derive(Component)]
+struct WidthSlider;
+
+/// The part of the slider up to the width
+#[derive(Component)]
+struct WidthFill;
+
+#[derive(Component)]
+struct WidthText;
+
+/// A dot as wide as the strokes on screen
+#[derive(Component)]
+struct WidthPreview;
+
+const SLIDER_WIDTH: f32 = 150.0;
+
+/// Largest preview, fitting in a button
+const MAX_PREVIEW_SIZE: f32 = 44.0;
+
 #[derive(Component)]
 struct CollabText;
 
+/// The button shows the chalk as it is drawn on the board, and opens the
+/// color picker
 fn color_btn_system(
-    mut chalk: ResMut<LocalChalk>,
+    chalk: Res<LocalChalk>,
+    theme: Res<Theme>,
     mut btn_query: Query<&mut BackgroundColor, With<ColorButton>>,
     mut interaction_query: Query<&Interaction, (Changed<Interaction>, With<ColorButton>)>,
+    mut toggle_picker_event: EventWriter<TogglePickerEvent>,
 ) {
     let mut bg = btn_query.single_mut();
-    *bg = chalk.as_mut().color().into();
+    *bg = theme.ink(chalk.color()).into();
+    for interaction in &mut interaction_query {
+        if *interaction == Interaction::Pressed {
+            toggle_picker_event.send(TogglePickerEvent);
+        }
+    }
+}
+
+fn tool_btn_system(
+    mut chalk: ResMut<LocalChalk>,
+    mut txt_query: Query<&mut Text, With<ToolText>>,
+    mut interaction_query: Query<&Interaction, (Changed<Interaction>, With<ToolButton>)>,
+) {
+    for interaction in &mut interaction_query {
+        if *interaction == Interaction::Pressed {
+            chalk.as_mut().next_tool();
+        }
+    }
+
+    // The tool can be selected with keys too
+    let mut text = txt_query.single_mut();
+    let label = chalk.tool().label();
+    if text.sections[0].value != label {
+        label.clone_into(&mut text.sections[0].value);
+    }
+}
+
+fn style_label(style: StrokeStyle) -> &'static str {
+    match style.dash {
+        Dash::Solid => "__",
+        Dash::Dashed => "--",
+        Dash::Dotted => "..",
+    }
+}
+
+/// Arrowheads are toggled with keys only
+fn style_btn_system(
+    mut chalk: ResMut<LocalChalk>,
+    mut txt_query: Query<&mut Text, With<StyleText>>,
+    mut interaction_query: Query<&Interaction, (Changed<Interaction>, With<StyleButton>)>,
+) {
     for interaction in &mut interaction_query {
         if *interaction == Interaction::Pressed {
-            *bg = chalk.as_mut().next_color().into();
+            chalk.as_mut().next_dash();
         }
     }
+
+    let mut text = txt_query.single_mut();
+    let label = style_label(chalk.style());
+    if text.sections[0].value != label {
+        label.clone_into(&mut text.sections[0].value);
+    }
 }
 
 fn incr_btn_system(
@@ -184,6 +359,87 @@ fn decr_btn_system(
     }
 }
 
+/// Widths are spread on a logarithmic scale, fine ones get as much room as
+/// wide ones
+#[allow(clippy::cast_precision_loss)]
+fn slider_position(line_width: u32) -> f32 {
+    (line_width as f32).ln() / (MAX_CHALK_WIDTH as f32).ln()
+}
+
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_precision_loss)]
+#[allow(clippy::cast_sign_loss)]
+fn slider_width(position: f32) -> u32 {
+    (MAX_CHALK_WIDTH as f32)
+        .powf(position.clamp(0.0, 1.0))
+        .round() as u32
+}
+
+fn width_slider_system(
+    mut chalk: ResMut<LocalChalk>,
+    slider_q: Query<(&Interaction, &RelativeCursorPosition), With<WidthSlider>>,
+) {
+    for (interaction, cursor) in &slider_q {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        if let Some(position) = cursor.normalized {
+            let line_width = slider_width(position.x);
+            if chalk.line_width() != line_width {
+                chalk.set_line_width(line_width);
+            }
+        }
+    }
+}
+
+/// The width changes with keys and the wheel too, and the preview with the
+/// zoom
+#[allow(clippy::cast_precision_loss)]
+fn update_width_ui(
+    chalk: Res<LocalChalk>,
+    theme: Res<Theme>,
+    projection_q: Query<&OrthographicProjection, With<MainCamera>>,
+    mut fill_q: Query<(&mut Style, &mut BackgroundColor), With<WidthFill>>,
+    mut text_q: Query<&mut Text, With<WidthText>>,
+    mut preview_q: Query<
+        (&mut Style, &mut BackgroundColor),
+        (With<WidthPreview>, Without<WidthFill>),
+    >,
+) {
+    let line_width = chalk.line_width();
+
+    for (mut style, mut background) in &mut fill_q {
+        let width = Val::Percent(slider_position(line_width) * 100.0);
+        if style.width != width {
+            style.width = width;
+        }
+        let color = BackgroundColor(theme.ui_active().into());
+        if *background != color {
+            *background = color;
+        }
+    }
+
+    let value = line_width.to_string();
+    for mut text in &mut text_q {
+        if text.sections[0].value != value {
+            text.sections[0].value.clone_from(&value);
+        }
+    }
+
+    let scale = projection_q.get_single().map_or(1.0, |x| x.scale);
+    let size = Val::Px((line_width as f32 / scale).clamp(1.0, MAX_PREVIEW_SIZE));
+    for (mut style, mut background) in &mut preview_q {
+        if style.width != size {
+            style.width = size;
+            style.height = size;
+        }
+        let color = BackgroundColor(theme.ink(chalk.color()).into());
+        if *background != color {
+            *background = color;
+        }
+    }
+}
+
 fn clear_btn_system(
     mut event: EventWriter<ClearEvent>,
     mut interaction_query: Query<&Interaction, (Changed<Interaction>, With<ClearButton>)>,
@@ -195,16 +451,36 @@ fn clear_btn_system(
     }
 }
 
+fn apply_theme(
+    theme: Res<Theme>,
+    mut button_q: Query<&mut BackgroundColor, (With<Themed>, Without<ColorButton>)>,
+    mut text_q: Query<&mut Text, With<Themed>>,
+) {
+    if !theme.is_changed() {
+        return;
+    }
+
+    for mut background in &mut button_q {
+        *background = theme.ui_button().into();
+    }
+    for mut text in &mut text_q {
+        for section in &mut text.sections {
+            section.style.color = theme.ui_text().into();
+        }
+    }
+}
+
 #[derive(Event)]
 pub(crate) struct ToggleUiEvent;
 
-fn toggle_ui_system(
-    mut events: EventReader<ToggleUiEvent>,
-    mut visibility_q: Query<&mut Visibility, With<Toolbar>>,
+/// Show or hide the panel marked with `M` on each `E` event
+pub(crate) fn toggle_visibility<E: Event, M: Component>(
+    mut events: EventReader<E>,
+    mut visibility_q: Query<&mut Visibility, With<M>>,
 ) {
     let visibility: &mut Visibility = &mut visibility_q.single_mut();
 
-    for _ in &mut events.read() {
+    for _ in events.read() {
         *visibility = match *visibility {
             Visibility::Visible => Visibility::Hidden,
             Visibility::Hidden => Visibility::Visible,
@@ -226,3 +502,326 @@ fn update_collab_info(mut txt_query: Query<&mut Text, With<CollabText>>, stats:
 
     txt_query.single_mut().sections[0].value = text;
 }
+
+/// Hues in the grid of the color picker
+const PICKER_HUES: usize = 12;
+
+/// Saturation and value of the rows of the grid, under a row of grays
+const PICKER_SHADES: [(f32, f32); 5] =
+    [(0.3, 1.0), (0.6, 1.0), (1.0, 1.0), (1.0, 0.7), (1.0, 0.45)];
+
+const SWATCH_SIZE: f32 = 24.0;
+
+const PICKER_FONT_SIZE: f32 = 16.0;
+
+/// The color being typed in hex, if any
+#[derive(Resource, Default)]
+pub(crate) struct ColorPicker {
+    hex: Option<String>,
+}
+
+impl ColorPicker {
+    /// While typing, keys must not trigger any key binding
+    pub(crate) fn is_typing(&self) -> bool {
+        self.hex.is_some()
+    }
+}
+
+/// Open or close the color picker
+#[derive(Event)]
+pub(crate) struct TogglePickerEvent;
+
+/// The palette, numbered for keys 1 to 9, the colors picked recently, a grid
+/// of hues and shades, and a field to type a color in hex
+#[derive(Component)]
+struct PickerPanel;
+
+#[derive(Component, Clone, Copy)]
+enum PickerButton {
+    /// As shown on the board
+    Pick(Srgba),
+    Slot(usize),
+    Hex,
+}
+
+#[derive(Component)]
+struct HexText;
+
+fn setup_picker(mut commands: Commands) {
+    commands.spawn((
+        PickerPanel,
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                left: Val::Px(0.),
+                bottom: Val::Px(50.),
+                flex_direction: FlexDirection::Column,
+                padding: UiRect::all(Val::Px(4.)),
+                ..default()
+            },
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+    ));
+}
+
+fn toggle_picker(mut events: EventReader<TogglePickerEvent>, mut picker: ResMut<ColorPicker>) {
+    // Opened or closed, the color being typed is dropped
+    if events.read().count() > 0 {
+        picker.hex = None;
+    }
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn picker_grid() -> Vec<Vec<Srgba>> {
+    let grays = (0..PICKER_HUES)
+        .map(|i| {
+            let v = 1.0 - i as f32 / (PICKER_HUES - 1) as f32;
+            Srgba::rgb(v, v, v)
+        })
+        .collect();
+
+    let shades = PICKER_SHADES.iter().map(|&(saturation, value)| {
+        (0..PICKER_HUES)
+            .map(|i| {
+                let hue = 360.0 * i as f32 / PICKER_HUES as f32;
+                Hsva::new(hue, saturation, value, 1.0).into()
+            })
+            .collect()
+    });
+
+    std::iter::once(grays).chain(shades).collect()
+}
+
+fn draw_picker(
+    mut commands: Commands,
+    palette: Res<Palette>,
+    theme: Res<Theme>,
+    panel_q: Query<Entity, With<PickerPanel>>,
+    asset_server: Res<AssetServer>,
+) {
+    if !(palette.is_changed() || theme.is_changed()) {
+        return;
+    }
+
+    let font = default_font(&asset_server);
+    let panel = panel_q.single();
+
+    let row = NodeBundle::default();
+    let swatch = |parent: &mut ChildBuilder, button: PickerButton, color: Srgba, text: &str| {
+        // Readable on light and dark colors alike
+        let text_color = if color.luminance() > 0.5 {
+            Color::BLACK
+        } else {
+            Color::WHITE
+        };
+
+        parent
+            .spawn((
+                button,
+                ButtonBundle {
+                    style: Style {
+                        width: Val::Px(SWATCH_SIZE),
+                        height: Val::Px(SWATCH_SIZE),
+                        margin: UiRect::all(Val::Px(1.)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    background_color: color.into(),
+                    ..default()
+                },
+            ))
+            .with_children(|parent| {
+                parent.spawn(TextBundle::from_section(
+                    text,
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: PICKER_FONT_SIZE,
+                        color: text_color,
+                    },
+                ));
+            });
+    };
+
+    commands.entity(panel).despawn_descendants();
+    commands
+        .entity(panel)
+        .insert(BackgroundColor(theme.ui_button().into()))
+        .with_children(|parent| {
+            parent.spawn(row.clone()).with_children(|parent| {
+                for (slot, color) in palette.colors(*theme).iter().enumerate() {
+                    let key = if slot < 9 {
+                        (slot + 1).to_string()
+                    } else {
+                        String::new()
+                    };
+                    swatch(parent, PickerButton::Slot(slot), *color, &key);
+                }
+            });
+
+            if !palette.recent().is_empty() {
+                parent.spawn(row.clone()).with_children(|parent| {
+                    for ink in palette.recent() {
+                        let color = theme.ink(*ink);
+                        swatch(parent, PickerButton::Pick(color), color, "");
+                    }
+                });
+            }
+
+            for colors in picker_grid() {
+                parent.spawn(row.clone()).with_children(|parent| {
+                    for color in colors {
+                        swatch(parent, PickerButton::Pick(color), color, "");
+                    }
+                });
+            }
+
+            parent
+                .spawn((
+                    PickerButton::Hex,
+                    ButtonBundle {
+                        style: Style {
+                            margin: UiRect::all(Val::Px(1.)),
+                            padding: UiRect::axes(Val::Px(4.), Val::Px(2.)),
+                            ..default()
+                        },
+                        background_color: theme.ui_active().into(),
+                        ..default()
+                    },
+                ))
+                .with_children(|parent| {
+                    parent.spawn((
+                        HexText,
+                        TextBundle::from_section(
+                            "",
+                            TextStyle {
+                                font: font.clone(),
+                                font_size: PICKER_FONT_SIZE,
+                                color: theme.ui_text().into(),
+                            },
+                        ),
+                    ));
+                });
+        });
+}
+
+fn picker_btn_system(
+    interaction_q: Query<(&Interaction, &PickerButton), Changed<Interaction>>,
+    mut picker: ResMut<ColorPicker>,
+    mut pick_color_event: EventWriter<PickColorEvent>,
+    mut select_slot_event: EventWriter<SelectSlotEvent>,
+) {
+    for (interaction, button) in &interaction_q {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        match *button {
+            PickerButton::Pick(color) => {
+                picker.hex = None;
+                pick_color_event.send(PickColorEvent(color));
+            }
+            PickerButton::Slot(slot) => {
+                picker.hex = None;
+                select_slot_event.send(SelectSlotEvent(slot));
+            }
+            PickerButton::Hex => picker.hex = Some(String::new()),
+        }
+    }
+}
+
+/// Enter picks the color typed, escape cancels
+fn type_hex(
+    mut keyboard_events: EventReader<KeyboardInput>,
+    mut picker: ResMut<ColorPicker>,
+    mut pick_color_event: EventWriter<PickColorEvent>,
+) {
+    let Some(hex) = &mut picker.hex else {
+        keyboard_events.clear();
+        return;
+    };
+
+    let mut done = false;
+    let mut cancelled = false;
+
+    for event in keyboard_events.read() {
+        if event.state != ButtonState::Pressed {
+            continue;
+        }
+
+        match &event.logical_key {
+            Key::Character(s) => {
+                // With alpha, like #rrggbbaa
+                let digits = s.chars().filter(char::is_ascii_hexdigit);
+                hex.extend(digits);
+                hex.truncate(8);
+            }
+            Key::Backspace => {
+                hex.pop();
+            }
+            Key::Enter => done = true,
+            Key::Escape => cancelled = true,
+            _ => {}
+        }
+    }
+
+    if done {
+        match Srgba::hex(&*hex) {
+            Ok(color) => pick_color_event.send(PickColorEvent(color)),
+            Err(e) => {
+                debug!("Invalid color {hex}: {e}");
+                return;
+            }
+        };
+    }
+
+    if done || cancelled {
+        picker.hex = None;
+    }
+}
+
+/// The color being typed, or the one of the chalk
+fn update_hex_text(
+    chalk: Res<LocalChalk>,
+    theme: Res<Theme>,
+    picker: Res<ColorPicker>,
+    mut text_q: Query<&mut Text, With<HexText>>,
+) {
+    let value = match &picker.hex {
+        Some(hex) => format!("#{hex}|"),
+        None => theme.ink(chalk.color()).to_hex(),
+    };
+
+    for mut text in &mut text_q {
+        if text.sections[0].value != value {
+            text.sections[0].value.clone_from(&value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slider_ends() {
+        assert!(slider_position(1).abs() < f32::EPSILON);
+        assert!((slider_position(MAX_CHALK_WIDTH) - 1.0).abs() < f32::EPSILON);
+        assert_eq!(slider_width(0.0), 1);
+        assert_eq!(slider_width(1.0), MAX_CHALK_WIDTH);
+    }
+
+    #[test]
+    fn slider_round_trip() {
+        for width in 1..=MAX_CHALK_WIDTH {
+            assert_eq!(slider_width(slider_position(width)), width);
+        }
+    }
+
+    #[test]
+    fn slider_past_the_ends() {
+        assert_eq!(slider_width(-0.5), 1);
+        assert_eq!(slider_width(1.5), MAX_CHALK_WIDTH);
+    }
+}
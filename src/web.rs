@@ -1,5 +1,12 @@
 use crate::collab::CollabPluginOpt as CollabOpt;
+use crate::palette::parse_palette;
+use crate::presets::parse_pens;
+use crate::Background;
 use crate::Opt;
+use crate::Smoothing;
+use crate::Theme;
+use lavagna_collab::CollabId;
+use rand::Rng;
 
 /// On wasm, some options are hardcoded, other are read from URL
 pub fn options_from_url() -> Opt {
@@ -42,7 +49,8 @@ impl TryFrom<&Request> for CollabOpt {
 
         if let Some(url) = url {
             // If collab-url is set, then collab-id must be set too. Randomize it if not.
-            let collab_id = collab_id.unwrap_or_else(|| rand::random());
+            let collab_id =
+                collab_id.unwrap_or_else(|| rand::thread_rng().gen_range(CollabId::PEERS));
             Ok(CollabOpt { url, collab_id })
         } else {
             Err(())
@@ -64,6 +72,16 @@ impl From<&Request> for Opt {
                 "v" | "verbose" => opt.verbose = v.parse().unwrap_or_default(),
                 "dbg" | "show-debug-pane" => opt.show_debug_pane = v.parse().unwrap_or_default(),
                 "ui" => opt.ui = v.parse().unwrap_or_default(),
+                "smoothing" => {
+                    if let Ok(level) = v.parse() {
+                        opt.smoothing = Smoothing::new(level);
+                    }
+                }
+                "background" => opt.background = v.parse::<Background>().ok(),
+                "theme" => opt.theme = v.parse::<Theme>().unwrap_or_default(),
+                // Hex colors, without `#` which starts the fragment of URLs
+                "palette" => opt.palette = parse_palette(v).unwrap_or_default(),
+                "presets" => opt.presets = parse_pens(v).unwrap_or_default(),
                 _ => (),
             }
         }